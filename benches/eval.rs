@@ -0,0 +1,64 @@
+//! Times PestoEval on positions from random games, with and without the pawn hash table
+//!
+//! Run with `cargo bench --bench eval`.
+
+use kingfisher::board::Board;
+use kingfisher::boardstack::BoardStack;
+use kingfisher::eval::{EvalConfig, Evaluator, PestoEval};
+use kingfisher::move_generation::MoveGen;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Number of times each position set is evaluated
+const ROUNDS: usize = 20;
+
+/// Plays random games and collects every position reached
+fn random_positions(move_gen: &MoveGen, games: usize, plies: usize) -> Vec<Board> {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut positions = Vec::new();
+    for _ in 0..games {
+        let mut board = BoardStack::new();
+        for _ in 0..plies {
+            let position = board.current_state().clone();
+            let (captures, moves) = move_gen.gen_pseudo_legal_moves(&position);
+            let legal: Vec<_> = captures.into_iter().chain(moves)
+                .filter(|&m| position.apply_move_to_board(m).is_legal(move_gen))
+                .collect();
+            positions.push(position);
+            if legal.is_empty() {
+                break;
+            }
+            board.make_move(legal[rng.gen_range(0..legal.len())]);
+        }
+    }
+    positions
+}
+
+/// Evaluates every position ROUNDS times and prints the time per evaluation
+fn time(name: &str, positions: &[Board], mut eval: impl FnMut(&Board) -> i32) {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for board in positions {
+            black_box(eval(black_box(board)));
+        }
+    }
+    let evals = (ROUNDS * positions.len()) as f64;
+    println!("{:<28} {:>8.1} ns/eval", name, start.elapsed().as_nanos() as f64 / evals);
+}
+
+fn main() {
+    let move_gen = MoveGen::new();
+    let positions = random_positions(&move_gen, 200, 80);
+    println!("{} positions, {} rounds", positions.len(), ROUNDS);
+
+    let pesto = PestoEval::new();
+    let no_pawns = PestoEval::with_config(EvalConfig { pawn_structure: false, ..EvalConfig::default() });
+    time("eval (pawn hash)", &positions, |board| pesto.eval(board, &move_gen));
+    time("eval (no pawn structure)", &positions, |board| no_pawns.eval(board, &move_gen));
+    time("pawn structure (computed)", &positions, |board| {
+        let (mg, eg) = pesto.pawn_structure(board);
+        mg[0] - mg[1] + eg[0] - eg[1]
+    });
+}
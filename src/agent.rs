@@ -1,10 +1,71 @@
 //! This module specifies various agents, which can use any combination of search and eval routines.
 
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::board::Board;
 use crate::boardstack::BoardStack;
+use crate::egtb::{Tablebases, Wdl};
 use crate::eval::PestoEval;
 use crate::move_types::Move;
 use crate::move_generation::MoveGen;
-use crate::search::{iterative_deepening_ab_search, mate_search};
+use crate::piece_types::{PAWN, WHITE, BLACK};
+use crate::search::{allocate_time, alpha_beta_search, aspiration_window_ab_search, iterative_deepening_ab_search, mate_search, principal_variation, AspirationWindow, MateSearchMode, SearchContext, SearchLimits, MATE_SCORE, MATE_THRESHOLD};
+use crate::transposition::TranspositionTable;
+
+/// A move chosen by an agent, with the result of the search that chose it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AgentMove {
+    /// The move to play.
+    pub best_move: Move,
+    /// The score of the move in centipawns, or a mate score, from the point of view of the side to move.
+    pub score: i32,
+    /// The principal variation, starting with `best_move`.
+    pub pv: Vec<Move>,
+    /// The depth of the search that chose the move.
+    pub depth: i32,
+    /// The number of nodes searched.
+    pub nodes: u64,
+}
+
+/// The outcome of asking an agent to move.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AgentResult {
+    /// The agent chose a move.
+    Move(AgentMove),
+    /// The side to move has been checkmated, so there is no move to make.
+    Checkmate,
+    /// The side to move has no legal moves but is not in check.
+    Stalemate,
+    /// The agent gives up the game.
+    Resign,
+    /// The agent claims a draw in a position it considers dead drawn.
+    DrawClaim,
+    /// The side to move used more than its remaining time. This is reported by the arena's
+    /// clock rather than by agents.
+    TimeForfeit,
+}
+
+/// The highest game phase (see `Board::phase`) at which an agent may claim a draw, e.g. a rook each.
+const DRAW_CLAIM_MAX_PHASE: i32 = 4;
+
+/// Checks whether so little material is left that a level position cannot be won: no pawns
+/// and at most `DRAW_CLAIM_MAX_PHASE` worth of pieces.
+fn is_low_material(board: &Board) -> bool {
+    (board.get_piece_bitboard(WHITE, PAWN) | board.get_piece_bitboard(BLACK, PAWN)) == 0 && board.phase() <= DRAW_CLAIM_MAX_PHASE
+}
+
+/// The search an agent runs after its mate search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchAlgorithm {
+    /// Iterative deepening alpha-beta search.
+    #[default]
+    AlphaBeta,
+    /// Iterative deepening with aspiration windows around the previous depth's score.
+    AspirationWindow,
+}
 
 /// Trait defining the interface for chess agents.
 pub trait Agent {
@@ -16,8 +77,21 @@ pub trait Agent {
     ///
     /// # Returns
     ///
-    /// The best `Move` as determined by the agent.
-    fn get_move(&self, board: &mut BoardStack) -> Move;
+    /// An `AgentResult` holding the best move as determined by the agent along with its score and
+    /// principal variation, or the reason why no move can be made.
+    fn get_move(&self, board: &mut BoardStack) -> AgentResult;
+
+    /// Tells the agent the time left on each clock before it is asked to move in a timed game.
+    ///
+    /// The arena cannot interrupt a move, so an agent that takes longer than the time on its own
+    /// clock only loses on time once the move is made. Agents should budget their moves from the
+    /// clock, unless their own time limits already keep them well inside it.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The time left on each side's clock, indexed by color.
+    /// * `increment` - The time added to a side's clock after each of its moves.
+    fn set_clock(&self, _clock: [Duration; 2], _increment: Duration) {}
 }
 
 /// A simple agent that uses mate search followed by aspiration window quiescence search.
@@ -30,13 +104,35 @@ pub struct SimpleAgent<'a> {
     pub q_search_max_depth: i32,
     /// Whether to print verbose output during search.
     pub verbose: bool,
+    /// The search to run when the mate search finds nothing.
+    pub algorithm: SearchAlgorithm,
+    /// The time allowed for the search of each move, or no limit if `None`.
+    pub time_limit: Option<Duration>,
+    /// The number of nodes allowed for the search of each move, or no limit if `None`.
+    pub node_limit: Option<u64>,
+    /// Resign once the score has been at least this many centipawns below zero for
+    /// `resign_moves` consecutive moves, or never if `None`.
+    pub resign_threshold: Option<i32>,
+    /// The number of consecutive moves the score must stay below the resign threshold.
+    pub resign_moves: u32,
+    /// Claim a draw when the score is within this many centipawns of zero with little
+    /// material left, or never if `None`.
+    pub draw_threshold: Option<i32>,
     /// Reference to the move generator.
     pub move_gen: &'a MoveGen,
     /// Reference to the Pesto evaluation function.
-    pub pesto: &'a PestoEval
+    pub pesto: &'a PestoEval,
+    /// The tablebases to play endings from instead of searching, if any.
+    pub tablebases: Option<&'a Tablebases>,
+    /// Transposition table kept between the moves of a game.
+    tt: RefCell<TranspositionTable>,
+    /// The number of consecutive moves the score has been below the resign threshold.
+    losing_moves: Cell<u32>,
+    /// The time left on each clock and the increment, in timed games.
+    clock: Cell<Option<([Duration; 2], Duration)>>,
 }
 
-impl SimpleAgent<'_> {
+impl<'a> SimpleAgent<'a> {
     /// Creates a new `SimpleAgent` with the specified parameters.
     ///
     /// # Arguments
@@ -50,30 +146,508 @@ impl SimpleAgent<'_> {
     /// # Returns
     ///
     /// A new `SimpleAgent` instance.
-    pub fn new<'a>(mate_search_depth: i32, ab_search_depth: i32, q_search_max_depth: i32, verbose: bool, move_gen: &'a MoveGen, pesto: &'a PestoEval) -> SimpleAgent<'a> {
+    pub fn new(mate_search_depth: i32, ab_search_depth: i32, q_search_max_depth: i32, verbose: bool, move_gen: &'a MoveGen, pesto: &'a PestoEval) -> SimpleAgent<'a> {
         SimpleAgent {
             mate_search_depth,
             ab_search_depth,
             q_search_max_depth,
             verbose,
+            algorithm: SearchAlgorithm::default(),
+            time_limit: None,
+            node_limit: None,
+            resign_threshold: None,
+            resign_moves: 1,
+            draw_threshold: None,
             move_gen,
-            pesto
+            pesto,
+            tablebases: None,
+            tt: RefCell::new(TranspositionTable::new()),
+            losing_moves: Cell::new(0),
+            clock: Cell::new(None),
         }
     }
+
+    /// Makes the agent resign hopeless positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - How many centipawns below zero the score must be for the position to count as lost.
+    /// * `moves` - The number of consecutive moves the position must count as lost before resigning (at least one).
+    ///
+    /// # Returns
+    ///
+    /// The agent, resigning with the given thresholds.
+    pub fn with_resignation(mut self, threshold: i32, moves: u32) -> Self {
+        self.resign_threshold = Some(threshold);
+        self.resign_moves = moves.max(1);
+        self
+    }
+
+    /// Makes the agent claim a draw when the score is close to zero and too little material
+    /// is left to win.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - How many centipawns from zero the score may be for the position to count as drawn.
+    ///
+    /// # Returns
+    ///
+    /// The agent, claiming draws with the given threshold.
+    pub fn with_draw_claim(mut self, threshold: i32) -> Self {
+        self.draw_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the search to run when the mate search finds nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The search algorithm to use.
+    ///
+    /// # Returns
+    ///
+    /// The agent, using the given search algorithm.
+    pub fn with_algorithm(mut self, algorithm: SearchAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Limits the search of each move by time, nodes or both.
+    ///
+    /// The limits cover the mate search and the main search together, with either search
+    /// algorithm. The search stops at whichever limit is reached first, and always stops at
+    /// `ab_search_depth`, so an agent without limits searches to that depth.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_limit` - The time allowed for each move, or no limit if `None`.
+    /// * `node_limit` - The number of nodes allowed for each move, or no limit if `None`.
+    ///
+    /// # Returns
+    ///
+    /// The agent, searching within the given limits.
+    pub fn with_search_limits(mut self, time_limit: Option<Duration>, node_limit: Option<u64>) -> Self {
+        self.time_limit = time_limit;
+        self.node_limit = node_limit;
+        self
+    }
+
+    /// Makes the agent play the endings held in the tablebases perfectly, without searching.
+    ///
+    /// # Arguments
+    ///
+    /// * `tablebases` - The tablebases to probe before each search.
+    ///
+    /// # Returns
+    ///
+    /// The agent, probing the given tablebases.
+    pub fn with_tablebases(mut self, tablebases: &'a Tablebases) -> Self {
+        self.tablebases = Some(tablebases);
+        self
+    }
 }
 
 impl Agent for SimpleAgent<'_> {
-    fn get_move(&self, board: &mut BoardStack) -> Move {
+    fn get_move(&self, board: &mut BoardStack) -> AgentResult {
+        // Report the end of the game rather than searching a terminal position
+        let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(self.move_gen);
+        if checkmate {
+            return AgentResult::Checkmate;
+        }
+        if stalemate {
+            return AgentResult::Stalemate;
+        }
+
+        // Play tablebase endings from the table, scoring mates like the search does
+        if let Some(tablebases) = self.tablebases {
+            let board = board.current_state();
+            if let (Some(result), Some(m)) = (tablebases.probe(board), tablebases.best_move(board, self.move_gen)) {
+                let score = match result.wdl {
+                    Wdl::Win => MATE_SCORE - result.dtm as i32,
+                    Wdl::Draw => 0,
+                    Wdl::Loss => -(MATE_SCORE - result.dtm as i32),
+                };
+                return AgentResult::Move(AgentMove { best_move: m, score, pv: vec![m], depth: result.dtm as i32, nodes: 0 });
+            }
+        }
+
+        // The mate search and the main search share the limits of the move. In a timed game the
+        // move also gets no more than its share of the time left on the clock
+        let side = if board.current_state().w_to_move { WHITE } else { BLACK };
+        let clock_limit = self.clock.get().map(|(clock, increment)| allocate_time(clock[side], increment, None));
+        let time_limit = match (self.time_limit, clock_limit) {
+            (Some(time_limit), Some(clock_limit)) => Some(time_limit.min(clock_limit)),
+            (time_limit, clock_limit) => time_limit.or(clock_limit),
+        };
+        let limits = SearchLimits::new(time_limit, self.node_limit);
+
         // First, perform mate search
-        let (eval, m, nodes) = mate_search(board, self.move_gen, self.mate_search_depth, self.verbose);
-        if eval == 1000000 {
-            println!("Found checkmate after searching {} nodes!", nodes);
-            return m;
+        let (eval, m, nodes, line) = mate_search(board, self.move_gen, self.mate_search_depth, MateSearchMode::ChecksOnly, &limits, self.verbose);
+        if eval > MATE_THRESHOLD {
+            if self.verbose {
+                println!("Found checkmate after searching {} nodes!", nodes);
+            }
+            self.losing_moves.set(0);
+            return AgentResult::Move(AgentMove { best_move: m, score: eval, depth: line.len() as i32, pv: line, nodes: nodes as u64 });
         }
 
-        // If no mate found, perform iterative deepening search
-        let (depth, eval, m, n) = iterative_deepening_ab_search(board, self.move_gen, self.pesto, self.ab_search_depth, self.q_search_max_depth, None, self.verbose);
-        println!("Mate search searched {} nodes, iterative deepening search searched another {} nodes at a depth of {} ({} total nodes). Eval: {}", nodes, n, depth, nodes + n, eval);
-        m
+        // If no mate found, perform the main search
+        let ctx = SearchContext::new(self.move_gen, self.pesto, &limits).with_q_search_depth(self.q_search_max_depth).with_verbose(self.verbose);
+        let (mut depth, mut eval, mut m, mut n) = match self.algorithm {
+            SearchAlgorithm::AlphaBeta => {
+                iterative_deepening_ab_search(board, &mut self.tt.borrow_mut(), &ctx, self.ab_search_depth)
+            }
+            SearchAlgorithm::AspirationWindow => {
+                // The aspiration search only searches even depths, counting in pairs of plies
+                aspiration_window_ab_search(board, &mut self.tt.borrow_mut(), &ctx, (self.ab_search_depth + 1) / 2, &AspirationWindow::default())
+            }
+        };
+
+        // Stopped before completing a depth, e.g. after a long mate search: a one-ply search
+        // outside the limits still finds a sensible move
+        if m == Move::null() {
+            let unlimited = SearchLimits::new(None, None);
+            let (one_ply_eval, one_ply_move, one_ply_nodes) = alpha_beta_search(board, &mut self.tt.borrow_mut(), &SearchContext { limits: &unlimited, ..ctx }, 1, -MATE_SCORE, MATE_SCORE);
+            (depth, eval, m, n) = (1, one_ply_eval, one_ply_move, n + one_ply_nodes);
+        }
+        if self.verbose {
+            println!("Mate search searched {} nodes, iterative deepening search searched another {} nodes at a depth of {} ({} total nodes). Eval: {}", nodes, n, depth, nodes + n, eval);
+        }
+
+        // Resign once the position has looked lost for long enough
+        if let Some(threshold) = self.resign_threshold {
+            let losing_moves = if eval <= -threshold { self.losing_moves.get() + 1 } else { 0 };
+            self.losing_moves.set(losing_moves);
+            if losing_moves >= self.resign_moves {
+                return AgentResult::Resign;
+            }
+        }
+
+        // Claim a draw when neither side can make progress
+        if let Some(threshold) = self.draw_threshold {
+            if eval.abs() <= threshold && is_low_material(board.current_state()) {
+                return AgentResult::DrawClaim;
+            }
+        }
+
+        let pv = principal_variation(board, self.move_gen, &self.tt.borrow(), depth.max(1) as usize);
+        AgentResult::Move(AgentMove { best_move: m, score: eval, pv, depth, nodes: (nodes + n) as u64 })
+    }
+
+    fn set_clock(&self, clock: [Duration; 2], increment: Duration) {
+        self.clock.set(Some((clock, increment)));
+    }
+}
+
+/// How long an external engine is given to exit after `quit` before it is killed.
+const EXTERNAL_ENGINE_QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The search depth used by an external engine that is given no other limit.
+const EXTERNAL_ENGINE_DEFAULT_DEPTH: i32 = 8;
+
+/// The progress an external engine last reported with `info`.
+#[derive(Default)]
+struct EngineInfo {
+    depth: i32,
+    score: i32,
+    nodes: u64,
+    pv: Vec<String>,
+}
+
+impl EngineInfo {
+    /// Updates the progress from an `info` line, keeping the fields the line does not mention.
+    ///
+    /// Lines without a score, such as `info string` or `info currmove`, are ignored.
+    fn update(&mut self, line: &str) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.get(1) == Some(&"string") || !tokens.contains(&"score") {
+            return;
+        }
+        for (i, &token) in tokens.iter().enumerate() {
+            let value = |offset: usize| tokens.get(i + offset).copied().unwrap_or("");
+            match token {
+                "depth" => self.depth = value(1).parse().unwrap_or(self.depth),
+                "nodes" => self.nodes = value(1).parse().unwrap_or(self.nodes),
+                "score" => {
+                    // A mate in N moves is N * 2 - 1 plies away, and being mated in N is N * 2
+                    let n: i32 = value(2).parse().unwrap_or(0);
+                    match value(1) {
+                        "cp" => self.score = n,
+                        "mate" if n > 0 => self.score = MATE_SCORE - (n * 2 - 1),
+                        "mate" => self.score = -(MATE_SCORE + n * 2),
+                        _ => {}
+                    }
+                }
+                "pv" => {
+                    self.pv = tokens[i + 1..].iter().map(|m| m.to_string()).collect();
+                    return;
+                }
+                _ => {}
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+/// A running external UCI engine and the pipes to talk to it.
+struct UciProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl UciProcess {
+    /// Sends a command to the engine.
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    /// Reads lines from the engine until one starts with `prefix`, and returns that line.
+    fn read_until(&mut self, prefix: &str) -> io::Result<String> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine closed its output"));
+            }
+            if line.trim_start().starts_with(prefix) {
+                return Ok(line.trim().to_string());
+            }
+        }
+    }
+}
+
+/// An agent that plays the moves of an external UCI engine, e.g. to play matches against
+/// Stockfish.
+///
+/// The engine is started when the agent is created and told to quit when the agent is dropped.
+/// Each move sends the game so far with `position` and searches it with `go`. If the engine
+/// stops responding sensibly, by exiting or answering with an illegal move, the agent resigns.
+pub struct ExternalUciAgent<'a> {
+    /// The name the engine reports with `id name`.
+    pub name: String,
+    /// The time allowed for each move, or no limit if `None`.
+    pub time_limit: Option<Duration>,
+    /// The number of nodes allowed for each move, or no limit if `None`.
+    pub node_limit: Option<u64>,
+    /// The depth of each search, or no limit if `None`.
+    pub depth: Option<i32>,
+    /// Reference to the move generator.
+    pub move_gen: &'a MoveGen,
+    /// The time left on each clock and the increment, in timed games.
+    clock: Cell<Option<([Duration; 2], Duration)>>,
+    /// The engine process.
+    process: RefCell<UciProcess>,
+}
+
+impl ExternalUciAgent<'_> {
+    /// Starts an external UCI engine and waits until it is ready.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the engine's executable.
+    /// * `move_gen` - Reference to the move generator.
+    ///
+    /// # Returns
+    ///
+    /// The agent, or the error from starting the engine or from the UCI handshake.
+    pub fn new<'a>(path: &str, move_gen: &'a MoveGen) -> io::Result<ExternalUciAgent<'a>> {
+        let mut child = Command::new(path).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut process = UciProcess { child, stdin, stdout };
+
+        process.send("uci")?;
+        let mut name = path.to_string();
+        loop {
+            let line = process.read_until("")?;
+            if let Some(id_name) = line.strip_prefix("id name ") {
+                name = id_name.to_string();
+            } else if line == "uciok" {
+                break;
+            }
+        }
+        process.send("ucinewgame")?;
+        process.send("isready")?;
+        process.read_until("readyok")?;
+
+        Ok(ExternalUciAgent {
+            name,
+            time_limit: None,
+            node_limit: None,
+            depth: None,
+            move_gen,
+            clock: Cell::new(None),
+            process: RefCell::new(process),
+        })
+    }
+
+    /// Sets an option of the engine, e.g. `Hash` or `Threads`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the option.
+    /// * `value` - The value to set it to.
+    ///
+    /// # Returns
+    ///
+    /// The agent, or the error from talking to the engine.
+    pub fn with_option(self, name: &str, value: &str) -> io::Result<Self> {
+        {
+            let mut process = self.process.borrow_mut();
+            process.send(&format!("setoption name {} value {}", name, value))?;
+            process.send("isready")?;
+            process.read_until("readyok")?;
+        }
+        Ok(self)
+    }
+
+    /// Limits each search by time or nodes, as with UCI `go movetime` and `go nodes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_limit` - The time allowed for each move, or no limit if `None`.
+    /// * `node_limit` - The number of nodes allowed for each move, or no limit if `None`.
+    ///
+    /// # Returns
+    ///
+    /// The agent, searching within the given limits.
+    pub fn with_search_limits(mut self, time_limit: Option<Duration>, node_limit: Option<u64>) -> Self {
+        self.time_limit = time_limit;
+        self.node_limit = node_limit;
+        self
+    }
+
+    /// Limits each search to the given depth, as with UCI `go depth`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of each search.
+    ///
+    /// # Returns
+    ///
+    /// The agent, searching to the given depth.
+    pub fn with_depth(mut self, depth: i32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Builds the `go` command for the next search.
+    ///
+    /// The arena's clock is forwarded when there is one, alongside any fixed limits. Without
+    /// either, the engine searches to `EXTERNAL_ENGINE_DEFAULT_DEPTH`.
+    fn go_command(&self) -> String {
+        let mut command = String::from("go");
+        if let Some((clock, increment)) = self.clock.get() {
+            command.push_str(&format!(" wtime {} btime {} winc {} binc {}",
+                                      clock[WHITE].as_millis(), clock[BLACK].as_millis(), increment.as_millis(), increment.as_millis()));
+        }
+        if let Some(time_limit) = self.time_limit {
+            command.push_str(&format!(" movetime {}", time_limit.as_millis()));
+        }
+        if let Some(node_limit) = self.node_limit {
+            command.push_str(&format!(" nodes {}", node_limit));
+        }
+        if let Some(depth) = self.depth {
+            command.push_str(&format!(" depth {}", depth));
+        }
+        if command == "go" {
+            command.push_str(&format!(" depth {}", EXTERNAL_ENGINE_DEFAULT_DEPTH));
+        }
+        command
+    }
+
+    /// Sends the game so far to the engine and reads its best move.
+    ///
+    /// The score, depth, node count and principal variation come from the last `info` line the
+    /// engine sent before `bestmove`. The principal variation is cut at the first move that is
+    /// not legal, and is just the best move if it does not start with it.
+    fn search(&self, board: &BoardStack) -> io::Result<Option<AgentMove>> {
+        let moves: Vec<String> = board.moves().iter().map(|m| m.to_uci()).collect();
+        let mut position = format!("position fen {}", board.root_state().to_fen());
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+
+        let mut process = self.process.borrow_mut();
+        process.send(&position)?;
+        process.send(&self.go_command())?;
+        let mut info = EngineInfo::default();
+        let line = loop {
+            let line = process.read_until("")?;
+            if line.starts_with("bestmove") {
+                break line;
+            }
+            if line.starts_with("info") {
+                info.update(&line);
+            }
+        };
+        let Some(best_move) = self.legal_move(board.current_state(), line.split_whitespace().nth(1).unwrap_or("")) else {
+            return Ok(None);
+        };
+
+        let mut pv = Vec::new();
+        let mut pv_board = board.current_state().clone();
+        for uci in &info.pv {
+            let Some(m) = self.legal_move(&pv_board, uci) else {
+                break;
+            };
+            pv.push(m);
+            pv_board = pv_board.apply_move_to_board(m);
+        }
+        if pv.first() != Some(&best_move) {
+            pv = vec![best_move];
+        }
+        Ok(Some(AgentMove { best_move, score: info.score, pv, depth: info.depth, nodes: info.nodes }))
+    }
+
+    /// Parses a move from the engine, accepting it only if it is legal.
+    ///
+    /// The move is parsed on the board so that castling and en passant are flagged.
+    fn legal_move(&self, board: &Board, uci: &str) -> Option<Move> {
+        board.move_from_uci(uci).filter(|&m| {
+            let (captures, moves) = self.move_gen.gen_pseudo_legal_moves(board);
+            captures.contains(&m) || moves.contains(&m)
+        }).filter(|&m| board.apply_move_to_board(m).is_legal(self.move_gen))
+    }
+}
+
+impl Agent for ExternalUciAgent<'_> {
+    fn get_move(&self, board: &mut BoardStack) -> AgentResult {
+        // Report the end of the game rather than asking the engine about a terminal position
+        let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(self.move_gen);
+        if checkmate {
+            return AgentResult::Checkmate;
+        }
+        if stalemate {
+            return AgentResult::Stalemate;
+        }
+
+        match self.search(board) {
+            Ok(Some(agent_move)) => AgentResult::Move(agent_move),
+            _ => AgentResult::Resign,
+        }
+    }
+
+    fn set_clock(&self, clock: [Duration; 2], increment: Duration) {
+        self.clock.set(Some((clock, increment)));
+    }
+}
+
+impl Drop for ExternalUciAgent<'_> {
+    /// Asks the engine to quit, and kills it if it has not exited shortly afterwards.
+    fn drop(&mut self) {
+        let process = self.process.get_mut();
+        let _ = process.send("quit");
+        let start = Instant::now();
+        while start.elapsed() < EXTERNAL_ENGINE_QUIT_TIMEOUT {
+            match process.child.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        let _ = process.child.kill();
+        let _ = process.child.wait();
+    }
+}
@@ -0,0 +1,73 @@
+//! A one-call interface for analysing positions.
+//!
+//! `analyze_fen` sets up the move generator, evaluation, board and transposition table that a
+//! search needs, so that embedding the engine does not require assembling them by hand.
+
+use crate::board::FenError;
+use crate::boardstack::BoardStack;
+use crate::eval::PestoEval;
+use crate::move_generation::MoveGen;
+use crate::move_types::Move;
+use crate::search::{iterative_deepening_ab_search, principal_variation, SearchContext, SearchLimits};
+use crate::transposition::TranspositionTable;
+
+/// The result of analysing a position with `analyze_fen`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnalysisResult {
+    /// The best move, or a null move if the side to move has no legal moves.
+    pub best_move: Move,
+    /// The best move in UCI notation, e.g. "e7e8q".
+    pub uci: String,
+    /// The best move in Standard Algebraic Notation, e.g. "e8=Q+".
+    pub san: String,
+    /// The score in centipawns, or a mate score, from the point of view of the side to move.
+    /// Use `search::format_uci_score` to print it.
+    pub score: i32,
+    /// The principal variation, starting with the best move.
+    pub pv: Vec<Move>,
+    /// The last depth that was searched completely.
+    pub depth: i32,
+    /// The number of nodes searched.
+    pub nodes: u64,
+}
+
+/// Searches a position given in FEN and returns the best move with its score and principal variation.
+///
+/// # Arguments
+///
+/// * `fen` - The position to analyse, in FEN.
+/// * `max_depth` - The depth to search to.
+/// * `limits` - The time and node limits of the search, e.g. `SearchLimits::new(None, None)`
+///   to search to `max_depth` regardless of time.
+///
+/// # Returns
+///
+/// The result of the search, or the error in the FEN.
+///
+/// # Examples
+///
+/// ```
+/// use kingfisher::api::analyze_fen;
+/// use kingfisher::search::{format_uci_score, SearchLimits};
+///
+/// let result = analyze_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2, &SearchLimits::new(None, None)).unwrap();
+/// assert_eq!(result.uci, "a1a8");
+/// assert_eq!(result.san, "Ra8#");
+/// assert_eq!(format_uci_score(result.score), "mate 1");
+/// assert_eq!(result.pv, vec![result.best_move]);
+/// ```
+pub fn analyze_fen(fen: &str, max_depth: i32, limits: &SearchLimits) -> Result<AnalysisResult, FenError> {
+    let mut board = BoardStack::try_from_fen(fen)?;
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut tt = TranspositionTable::new();
+
+    let (depth, score, best_move, nodes) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, limits), max_depth);
+    let (uci, san, pv) = if best_move == Move::null() {
+        (String::new(), String::new(), Vec::new())
+    } else {
+        let pv = principal_variation(&mut board, &move_gen, &tt, depth.max(1) as usize);
+        (best_move.to_uci(), board.current_state().move_to_san(best_move, &move_gen), pv)
+    };
+    Ok(AnalysisResult { best_move, uci, san, score, pv, depth, nodes: nodes as u64 })
+}
@@ -1,7 +1,10 @@
 //! This module provides an Arena for staging chess engine matches.
 
-use crate::agent::Agent;
+use std::time::{Duration, Instant};
+use crate::agent::{Agent, AgentResult};
+use crate::board::Board;
 use crate::boardstack::BoardStack;
+use crate::piece_types::{WHITE, BLACK};
 use crate::utils::print_move;
 
 /// Struct representing an arena for chess engine matches.
@@ -12,6 +15,10 @@ pub struct Arena<'a> {
     black_player: &'a dyn Agent,
     /// The maximum number of moves allowed in the game.
     max_moves: i32,
+    /// The time left on each side's clock, indexed by color, or `None` for untimed games.
+    pub clock: Option<[Duration; 2]>,
+    /// The time added to a side's clock after each of its moves.
+    pub increment: Duration,
     /// The current state of the chess board.
     pub boardstack: BoardStack
 }
@@ -33,38 +40,140 @@ impl Arena<'_> {
             white_player,
             black_player,
             max_moves,
+            clock: None,
+            increment: Duration::ZERO,
             boardstack: BoardStack::new()
         }
     }
 
+    /// Plays games with a clock, giving each side the same time budget.
+    ///
+    /// Before each move the agent is told both clocks with `Agent::set_clock`, so that it can
+    /// budget its time. A side whose move takes longer than its remaining time loses on time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The initial time on each side's clock.
+    /// * `increment` - The time added to a side's clock after each of its moves.
+    ///
+    /// # Returns
+    ///
+    /// The arena, using the given time control.
+    pub fn with_clock(mut self, time: Duration, increment: Duration) -> Self {
+        self.clock = Some([time, time]);
+        self.increment = increment;
+        self
+    }
+
     /// Plays a game between the two agents in the arena.
     ///
-    /// This method alternates moves between White and Black players until the maximum
-    /// number of moves is reached. It prints the game state after each move.
-    pub fn play_game(&mut self) {
+    /// This method alternates moves between White and Black players until one of them
+    /// reports the end of the game or the maximum number of moves is reached. It prints
+    /// the game state after each move.
+    ///
+    /// # Returns
+    ///
+    /// The `AgentResult` that ended the game (checkmate, stalemate, resignation, draw claim or
+    /// time forfeit of the side to move), or `None` if the maximum number of moves was reached.
+    pub fn play_game(&mut self) -> Option<AgentResult> {
         println!("Playing game (max {} moves)", self.max_moves);
         self.boardstack.current_state().print();
 
         for i in 0..self.max_moves {
             println!("Move {}", i);
 
-            let (current_player, color) = if i % 2 == 0 {
-                (self.white_player, "White")
+            let (current_player, color, side) = if self.boardstack.current_state().w_to_move {
+                (self.white_player, "White", WHITE)
             } else {
-                (self.black_player, "Black")
+                (self.black_player, "Black", BLACK)
             };
 
             // Get and make the move for the current player
-            let m = current_player.get_move(&mut self.boardstack);
+            if let Some(clock) = self.clock {
+                current_player.set_clock(clock, self.increment);
+            }
+            let start = Instant::now();
+            let result = current_player.get_move(&mut self.boardstack);
+            let elapsed = start.elapsed();
+            let m = match result {
+                AgentResult::Move(agent_move) => {
+                    if let Some(clock) = self.clock.as_mut() {
+                        if elapsed > clock[side] {
+                            println!("{} loses on time", color);
+                            return Some(AgentResult::TimeForfeit);
+                        }
+                        clock[side] = clock[side] - elapsed + self.increment;
+                    }
+                    agent_move.best_move
+                }
+                result => {
+                    match result {
+                        AgentResult::Checkmate => println!("{} is checkmated", color),
+                        AgentResult::Stalemate => println!("{} is stalemated", color),
+                        AgentResult::DrawClaim => println!("{} claims a draw", color),
+                        _ => println!("{} resigns", color),
+                    }
+                    return Some(result);
+                }
+            };
             println!("{} to move: {}", color, print_move(&m));
             self.boardstack.make_move(m);
 
             // Print the updated board state
             self.boardstack.current_state().print();
-
-            // TODO: Add game termination conditions (checkmate, stalemate, etc.)
         }
 
-        // TODO: Determine and print the game result
+        println!("Maximum number of moves reached");
+        None
+    }
+}
+
+/// The results of a match, counted from the point of view of the first agent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    /// Games won by the first agent.
+    pub wins: u32,
+    /// Games drawn, including games that reached the move limit.
+    pub draws: u32,
+    /// Games lost by the first agent.
+    pub losses: u32,
+}
+
+/// Plays a match between two agents, starting two games from each opening position.
+///
+/// In the first game of each pair the first agent plays White, and in the second it plays
+/// Black. Checkmate, resignation and loss on time decide a game; stalemate, draw claims and
+/// reaching the move limit count as draws.
+///
+/// # Arguments
+///
+/// * `first` - The first agent.
+/// * `second` - The second agent.
+/// * `openings` - The positions to start the games from, such as `openings::default_openings()`.
+/// * `max_moves` - The maximum number of moves in each game.
+///
+/// # Returns
+///
+/// The number of games won, drawn and lost by the first agent.
+pub fn play_match(first: &dyn Agent, second: &dyn Agent, openings: &[Board], max_moves: i32) -> MatchResult {
+    let mut result = MatchResult::default();
+    for opening in openings {
+        for first_is_white in [true, false] {
+            let (white, black) = if first_is_white { (first, second) } else { (second, first) };
+            let mut arena = Arena::new(white, black, max_moves);
+            arena.boardstack = BoardStack::new_from_board(opening.clone());
+            match arena.play_game() {
+                Some(AgentResult::Checkmate) | Some(AgentResult::Resign) | Some(AgentResult::TimeForfeit) => {
+                    // The side to move lost
+                    if arena.boardstack.current_state().w_to_move == first_is_white {
+                        result.losses += 1;
+                    } else {
+                        result.wins += 1;
+                    }
+                }
+                _ => result.draws += 1,
+            }
+        }
     }
-}
\ No newline at end of file
+    result
+}
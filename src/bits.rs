@@ -3,6 +3,38 @@
 //! This module provides various bitwise operations that are commonly used in chess engines,
 //! including bit iteration, bit manipulation, and bit counting functions.
 
+/// Iterator over the indices of the set bits of a u64, from least to most significant.
+///
+/// The iterator only holds the remaining bits, so iterating never allocates.
+#[derive(Clone, Copy, Debug)]
+pub struct Bits(u64);
+
+impl Iterator for Bits {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            let res = self.0.trailing_zeros() as usize;
+            // Clear the lowest set bit
+            self.0 &= self.0 - 1;
+            Some(res)
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Bits {}
+
+impl std::iter::FusedIterator for Bits {}
+
 /// Iterate over set bits in a u64.
 ///
 /// This function returns an iterator that yields the indices of set bits in the input number.
@@ -13,7 +45,7 @@
 ///
 /// # Returns
 ///
-/// An iterator over the indices of set bits in `n`.
+/// A `Bits` iterator over the indices of set bits in `n`, in increasing order.
 ///
 /// # Examples
 ///
@@ -24,17 +56,9 @@
 /// let set_bits: Vec<usize> = bits(&n).collect();
 /// assert_eq!(set_bits, vec![1, 3]);
 /// ```
-pub fn bits(n: &u64) -> impl Iterator<Item = usize> {
-    let mut bits_left = *n;
-    std::iter::from_fn(move || {
-        if bits_left == 0 {
-            None
-        } else {
-            let res: usize = bits_left.trailing_zeros() as usize;
-            bits_left &= !(1 << res);
-            Some(res)
-        }
-    })
+#[inline]
+pub fn bits(n: &u64) -> Bits {
+    Bits(*n)
 }
 
 // Bit operations named after Fortran intrinsics...
@@ -83,7 +107,8 @@ pub fn btest(n: u128, b: i32) -> bool {
 
 /// Count the number of set bits in a 64-bit unsigned integer.
 ///
-/// This function implements the population count (popcnt) operation.
+/// This function implements the population count (popcnt) operation using `count_ones`, which
+/// compiles to the POPCNT instruction when the target supports it.
 ///
 /// # Arguments
 ///
@@ -92,14 +117,9 @@ pub fn btest(n: u128, b: i32) -> bool {
 /// # Returns
 ///
 /// The number of set bits in `n`.
+#[inline]
 pub fn popcnt(n: u64) -> i32 {
-    let mut count = 0;
-    let mut nn = n;
-    while nn != 0 {
-        nn &= nn - 1;
-        count += 1;
-    }
-    count
+    n.count_ones() as i32
 }
 
 /// Compute the parity of a 128-bit unsigned integer.
@@ -1,8 +1,11 @@
 //! This module defines the Bitboard structure and associated functions for chess board representation.
 
-use crate::board_utils::{algebraic_to_sq_ind, bit_to_sq_ind, coords_to_sq_ind, sq_ind_to_bit};
+use std::fmt;
+
+use crate::board_utils::{algebraic_to_sq_ind, bit_to_sq_ind, coords_to_sq_ind, flip_sq_ind_vertically, flip_vertically, sq_ind_to_algebraic, sq_ind_to_bit};
 use crate::move_generation::MoveGen;
-use crate::move_types::CastlingRights;
+use crate::move_types::{CastlingRights, Move, MoveFlag};
+use crate::eval_constants::{GAMEPHASE_INC, PIECE_VALUES};
 use crate::piece_types::{PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING, WHITE, BLACK};
 
 /// Represents the chess board using bitboards.
@@ -17,12 +20,90 @@ pub struct Board {
     pub(crate) en_passant: Option<u8>,
     pub castling_rights: CastlingRights,
     pub(crate) halfmove_clock: u8,
-    pub(crate) fullmove_number: u8,
+    pub(crate) fullmove_number: u16,
     pub(crate) zobrist_hash: u64,
     pub(crate) eval: i32,
     pub game_phase: i32,
 }
 
+/// The ways in which a FEN string can be malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// The FEN does not have exactly six space-separated fields.
+    WrongFieldCount(usize),
+    /// The piece placement field does not have exactly eight ranks.
+    WrongRankCount(usize),
+    /// A rank (numbered 1 to 8) does not describe exactly eight squares.
+    BadRankLength(usize),
+    /// The piece placement field contains an unknown piece letter.
+    InvalidPiece(char),
+    /// The side to move is not `w` or `b`.
+    InvalidSideToMove(String),
+    /// The castling field is not `-` or a set of distinct letters from `KQkq`.
+    InvalidCastling(String),
+    /// The en passant field is not `-` or a square on the third or sixth rank, as appropriate
+    /// for the side to move.
+    InvalidEnPassant(String),
+    /// The halfmove clock is not a number in range.
+    InvalidHalfmoveClock(String),
+    /// The fullmove number is not a positive number in range.
+    InvalidFullmoveNumber(String),
+    /// The FEN is well formed but describes a position that cannot arise in a game.
+    IllegalPosition(IllegalPositionError),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(n) => write!(f, "expected 6 fields, found {}", n),
+            FenError::WrongRankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::BadRankLength(rank) => write!(f, "rank {} does not have 8 squares", rank),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece '{}'", c),
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling rights '{}'", s),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant square '{}'", s),
+            FenError::InvalidHalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+            FenError::IllegalPosition(e) => write!(f, "illegal position: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// The ways in which a well-formed position can be illegal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IllegalPositionError {
+    /// A side (`WHITE` or `BLACK`) does not have exactly one king.
+    WrongKingCount(usize),
+    /// A side has more than eight pawns or more than sixteen pieces.
+    TooManyPieces(usize),
+    /// A pawn stands on the first or eighth rank.
+    PawnOnBackRank,
+    /// The side not to move is in check, so its king could be captured.
+    OpponentInCheck,
+    /// A castling right is set although the king or rook is not on its original square.
+    InvalidCastlingRights,
+    /// The en passant square does not follow a double pawn push by the side that just moved.
+    InvalidEnPassant,
+}
+
+impl fmt::Display for IllegalPositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |color: &usize| if *color == WHITE { "white" } else { "black" };
+        match self {
+            IllegalPositionError::WrongKingCount(color) => write!(f, "{} does not have exactly one king", side(color)),
+            IllegalPositionError::TooManyPieces(color) => write!(f, "{} has too many pieces", side(color)),
+            IllegalPositionError::PawnOnBackRank => write!(f, "pawn on the first or eighth rank"),
+            IllegalPositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            IllegalPositionError::InvalidCastlingRights => write!(f, "castling rights without king and rook on their squares"),
+            IllegalPositionError::InvalidEnPassant => write!(f, "en passant square without a double pawn push"),
+        }
+    }
+}
+
+impl std::error::Error for IllegalPositionError {}
+
 impl Board {
     pub fn new() -> Board {
         let mut board = Board {
@@ -63,6 +144,9 @@ impl Board {
         self.update_occupancy();
     }
 
+    /// Recomputes the occupancy bitboard of each color from the per-piece bitboards.
+    ///
+    /// The combined occupancy is not stored; see `get_all_occupancy`.
     pub(crate) fn update_occupancy(&mut self) {
         for color in [WHITE, BLACK] {
             self.pieces_occ[color] = self.pieces[color].iter().fold(0, |acc, &x| acc | x);
@@ -78,8 +162,31 @@ impl Board {
     /// # Returns
     ///
     /// A new Bitboard struct representing the chess position described by the FEN string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the FEN is malformed; use `try_from_fen` for input that may be invalid.
     pub fn new_from_fen(fen: &str) -> Board {
-        let parts = fen.split(' ').collect::<Vec<&str>>();
+        match Board::try_from_fen(fen) {
+            Ok(board) => board,
+            Err(e) => panic!("Invalid FEN '{}': {}", fen, e),
+        }
+    }
+
+    /// Creates a new Bitboard from a FEN string, checking that it is well formed.
+    ///
+    /// # Arguments
+    ///
+    /// * `fen` - A string slice that holds the FEN representation of a chess position.
+    ///
+    /// # Returns
+    ///
+    /// The position described by the FEN, or a `FenError` describing the first problem found.
+    pub fn try_from_fen(fen: &str) -> Result<Board, FenError> {
+        let parts = fen.split_whitespace().collect::<Vec<&str>>();
+        if parts.len() != 6 {
+            return Err(FenError::WrongFieldCount(parts.len()));
+        }
         let mut board = Board::new();
         board.pieces = [[0; 6]; 2];
         board.pieces_occ = [0; 2];
@@ -87,122 +194,310 @@ impl Board {
         board.castling_rights.white_queenside = false;
         board.castling_rights.black_kingside = false;
         board.castling_rights.black_queenside = false;
-        let mut rank = 7;
-        let mut file = 0;
-        for c in parts[0].chars() {
-            if c == '/' {
-                rank -= 1;
-                file = 0;
-            } else if c.is_ascii_digit() {
-                file += c.to_digit(10).unwrap() as usize;
-            } else {
-                let sq_ind = coords_to_sq_ind(file, rank);
-                let bit = sq_ind_to_bit(sq_ind);
-                match c {
-                    'P' => board.pieces[WHITE][PAWN] ^= bit,
-                    'p' => board.pieces[BLACK][PAWN] ^= bit,
-                    'N' => board.pieces[WHITE][KNIGHT] ^= bit,
-                    'n' => board.pieces[BLACK][KNIGHT] ^= bit,
-                    'B' => board.pieces[WHITE][BISHOP] ^= bit,
-                    'b' => board.pieces[BLACK][BISHOP] ^= bit,
-                    'R' => board.pieces[WHITE][ROOK] ^= bit,
-                    'r' => board.pieces[BLACK][ROOK] ^= bit,
-                    'Q' => board.pieces[WHITE][QUEEN] ^= bit,
-                    'q' => board.pieces[BLACK][QUEEN] ^= bit,
-                    'K' => board.pieces[WHITE][KING] ^= bit,
-                    'k' => board.pieces[BLACK][KING] ^= bit,
-                    _ => panic!("Invalid FEN")
+
+        let ranks = parts[0].split('/').collect::<Vec<&str>>();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i;
+            let mut file = 0;
+            for c in rank_str.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|&d| d > 0) {
+                    file += empty as usize;
+                } else {
+                    let (color, piece) = match c {
+                        'P' => (WHITE, PAWN),
+                        'p' => (BLACK, PAWN),
+                        'N' => (WHITE, KNIGHT),
+                        'n' => (BLACK, KNIGHT),
+                        'B' => (WHITE, BISHOP),
+                        'b' => (BLACK, BISHOP),
+                        'R' => (WHITE, ROOK),
+                        'r' => (BLACK, ROOK),
+                        'Q' => (WHITE, QUEEN),
+                        'q' => (BLACK, QUEEN),
+                        'K' => (WHITE, KING),
+                        'k' => (BLACK, KING),
+                        _ => return Err(FenError::InvalidPiece(c)),
+                    };
+                    if file < 8 {
+                        board.pieces[color][piece] ^= sq_ind_to_bit(coords_to_sq_ind(file, rank));
+                    }
+                    file += 1;
                 }
-                file += 1;
+                if file > 8 {
+                    return Err(FenError::BadRankLength(rank + 1));
+                }
+            }
+            if file != 8 {
+                return Err(FenError::BadRankLength(rank + 1));
             }
         }
+
         match parts[1] {
             "w" => board.w_to_move = true,
             "b" => board.w_to_move = false,
-            _ => panic!("Invalid FEN")
+            _ => return Err(FenError::InvalidSideToMove(parts[1].to_string())),
         }
-        match parts[2] {
-            "-" => (),
-            _ => {
-                for c in parts[2].chars() {
-                    match c {
-                        'K' => board.castling_rights.white_kingside = true,
-                        'Q' => board.castling_rights.white_queenside = true,
-                        'k' => board.castling_rights.black_kingside = true,
-                        'q' => board.castling_rights.black_queenside = true,
-                        _ => panic!("Invalid FEN")
-                    }
+
+        if parts[2] != "-" {
+            for c in parts[2].chars() {
+                let right = match c {
+                    'K' => &mut board.castling_rights.white_kingside,
+                    'Q' => &mut board.castling_rights.white_queenside,
+                    'k' => &mut board.castling_rights.black_kingside,
+                    'q' => &mut board.castling_rights.black_queenside,
+                    _ => return Err(FenError::InvalidCastling(parts[2].to_string())),
+                };
+                if *right {
+                    return Err(FenError::InvalidCastling(parts[2].to_string()));
                 }
+                *right = true;
             }
         }
-        match parts[3] {
-            "-" => (),
-            _ => {
-                let sq_ind = algebraic_to_sq_ind(parts[3]);
-                board.en_passant = Some(sq_ind as u8);
+
+        if parts[3] != "-" {
+            // The en passant square is behind the pawn that just moved two squares
+            let ep_rank = if board.w_to_move { '6' } else { '3' };
+            let mut chars = parts[3].chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some('a'..='h'), Some(r), None) if r == ep_rank => {
+                    board.en_passant = Some(algebraic_to_sq_ind(parts[3]) as u8);
+                }
+                _ => return Err(FenError::InvalidEnPassant(parts[3].to_string())),
             }
         }
-        match parts[4] {
-            "0" => (),
-            _ => {
-                board.halfmove_clock = parts[4].parse::<u8>().unwrap();
+
+        board.halfmove_clock = parts[4].parse::<u8>()
+            .map_err(|_| FenError::InvalidHalfmoveClock(parts[4].to_string()))?;
+        board.fullmove_number = parts[5].parse::<u16>().ok().filter(|&n| n > 0)
+            .ok_or_else(|| FenError::InvalidFullmoveNumber(parts[5].to_string()))?;
+
+        board.update_occupancy();
+        board.zobrist_hash = board.compute_zobrist_hash();
+        Ok(board)
+    }
+
+    /// Creates a new Bitboard from a FEN string, checking that it is well formed and that the
+    /// position is legal (see `validate_legal`).
+    ///
+    /// # Arguments
+    ///
+    /// * `fen` - A string slice that holds the FEN representation of a chess position.
+    /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
+    ///
+    /// # Returns
+    ///
+    /// The position described by the FEN, or a `FenError` describing the first problem found.
+    pub fn try_from_fen_legal(fen: &str, move_gen: &MoveGen) -> Result<Board, FenError> {
+        let board = Board::try_from_fen(fen)?;
+        board.validate_legal(move_gen).map_err(FenError::IllegalPosition)?;
+        Ok(board)
+    }
+
+    /// Checks that the position could arise in a game.
+    ///
+    /// Each side must have exactly one king, at most eight pawns and at most sixteen pieces, no
+    /// pawn may stand on the first or eighth rank, and the side not to move must not be in check.
+    /// Castling rights require the king and rook on their original squares, and an en passant
+    /// square requires the pawn that just made a double push, with the squares it crossed empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the position is legal, otherwise the first problem found.
+    pub fn validate_legal(&self, move_gen: &MoveGen) -> Result<(), IllegalPositionError> {
+        for color in [WHITE, BLACK] {
+            if self.pieces[color][KING].count_ones() != 1 {
+                return Err(IllegalPositionError::WrongKingCount(color));
+            }
+            if self.pieces[color][PAWN].count_ones() > 8 || self.pieces_occ[color].count_ones() > 16 {
+                return Err(IllegalPositionError::TooManyPieces(color));
             }
         }
-        match parts[5] {
-            "1" => (),
-            _ => {
-                board.fullmove_number = parts[5].parse::<u8>().unwrap();
+
+        const BACK_RANKS: u64 = 0xFF000000000000FF;
+        if (self.pieces[WHITE][PAWN] | self.pieces[BLACK][PAWN]) & BACK_RANKS != 0 {
+            return Err(IllegalPositionError::PawnOnBackRank);
+        }
+
+        if !self.is_legal(move_gen) {
+            return Err(IllegalPositionError::OpponentInCheck);
+        }
+
+        // King and rook squares required by each castling right
+        let castling = [
+            (self.castling_rights.white_kingside, WHITE, 4, 7),
+            (self.castling_rights.white_queenside, WHITE, 4, 0),
+            (self.castling_rights.black_kingside, BLACK, 60, 63),
+            (self.castling_rights.black_queenside, BLACK, 60, 56),
+        ];
+        for (allowed, color, king_sq, rook_sq) in castling {
+            if allowed && (self.pieces[color][KING] & sq_ind_to_bit(king_sq) == 0
+                || self.pieces[color][ROOK] & sq_ind_to_bit(rook_sq) == 0) {
+                return Err(IllegalPositionError::InvalidCastlingRights);
             }
         }
-        for color in 0..2 {
-            board.pieces_occ[color] = board.pieces[color][PAWN];
-            for piece in 1..6 {
-                board.pieces_occ[color] |= board.pieces[color][piece];
+
+        if let Some(ep_sq) = self.en_passant {
+            // The pawn that just moved stands in front of the en passant square, and came from behind it
+            let ep_sq = ep_sq as usize;
+            let (them, pawn_sq, from_sq) = if self.w_to_move {
+                (BLACK, ep_sq - 8, ep_sq + 8)
+            } else {
+                (WHITE, ep_sq + 8, ep_sq - 8)
+            };
+            let occupied = self.get_all_occupancy();
+            if self.pieces[them][PAWN] & sq_ind_to_bit(pawn_sq) == 0
+                || occupied & (sq_ind_to_bit(ep_sq) | sq_ind_to_bit(from_sq)) != 0 {
+                return Err(IllegalPositionError::InvalidEnPassant);
             }
         }
-        board
+
+        Ok(())
+    }
+
+    /// Checks that the redundant parts of the board state agree with each other.
+    ///
+    /// Each color's occupancy must be the union of its piece bitboards, no square may hold more
+    /// than one piece, and the Zobrist hash must match a hash computed from scratch. Making and
+    /// undoing moves on a `BoardStack` runs this check in debug builds, so corruption is caught
+    /// at the move that caused it.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a description of the first inconsistency found.
+    pub fn assert_invariants(&self) {
+        let mut occupied: u64 = 0;
+        for color in [WHITE, BLACK] {
+            let mut occupancy: u64 = 0;
+            for piece in PAWN..=KING {
+                assert_eq!(occupied & self.pieces[color][piece], 0, "square occupied by more than one piece in {}", self.to_fen());
+                occupied |= self.pieces[color][piece];
+                occupancy |= self.pieces[color][piece];
+            }
+            assert_eq!(self.pieces_occ[color], occupancy, "occupancy of color {} out of sync with its pieces in {}", color, self.to_fen());
+        }
+        assert_eq!(self.zobrist_hash, self.compute_zobrist_hash(), "Zobrist hash out of sync in {}", self.to_fen());
+    }
+
+    /// Converts the board to a FEN (Forsyth–Edwards Notation) string.
+    ///
+    /// # Returns
+    ///
+    /// A String holding the FEN representation of the position, which can be read back with `new_from_fen`.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get_piece(coords_to_sq_ind(file, rank)) {
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let c = ['p', 'n', 'b', 'r', 'q', 'k'][piece];
+                        fen.push(if color == WHITE { c.to_ascii_uppercase() } else { c });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.w_to_move { " w " } else { " b " });
+
+        let mut castling = String::new();
+        if self.castling_rights.white_kingside { castling.push('K'); }
+        if self.castling_rights.white_queenside { castling.push('Q'); }
+        if self.castling_rights.black_kingside { castling.push('k'); }
+        if self.castling_rights.black_queenside { castling.push('q'); }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        match self.en_passant {
+            Some(sq_ind) => fen.push_str(&format!(" {}", sq_ind_to_algebraic(sq_ind as usize))),
+            None => fen.push_str(" -"),
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
     }
 
     /// Prints a visual representation of the chess board to the console.
     pub fn print(&self) {
-        println!("  +-----------------+");
+        print!("{}", self.render(false, true, false));
+    }
+
+    /// Renders the board as eight lines of ASCII pieces, from rank 8 down to rank 1.
+    ///
+    /// White pieces are uppercase, black pieces lowercase and empty squares are dots, as in
+    /// `r n b q k b n r`.
+    pub fn to_string_ascii(&self) -> String {
+        self.render(false, false, false)
+    }
+
+    /// Renders the board as eight lines of Unicode chess symbols, from rank 8 down to rank 1.
+    pub fn to_string_unicode(&self) -> String {
+        self.render(true, false, false)
+    }
+
+    /// Renders a visual representation of the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `unicode` - Whether to draw the pieces as Unicode chess symbols instead of letters
+    /// * `coordinates` - Whether to frame the board and label the ranks and files
+    /// * `side_to_move` - Whether to add a final line saying which side is to move
+    ///
+    /// # Returns
+    ///
+    /// The rendering, with each line ending in a newline.
+    pub fn render(&self, unicode: bool, coordinates: bool, side_to_move: bool) -> String {
+        let mut out = String::new();
+        if coordinates {
+            out.push_str("  +-----------------+\n");
+        }
         for rank in (0..8).rev() {
-            print!("{} | ", rank + 1);
-            for file in 0..8 {
-                let sq_ind = coords_to_sq_ind(file, rank);
-                let bit = sq_ind_to_bit(sq_ind);
-                if bit & self.pieces[WHITE][PAWN] != 0 {
-                    print!("P ");
-                } else if bit & self.pieces[BLACK][PAWN] != 0 {
-                    print!("p ");
-                } else if bit & self.pieces[WHITE][KNIGHT] != 0 {
-                    print!("N ");
-                } else if bit & self.pieces[BLACK][KNIGHT] != 0 {
-                    print!("n ");
-                } else if bit & self.pieces[WHITE][BISHOP] != 0 {
-                    print!("B ");
-                } else if bit & self.pieces[BLACK][BISHOP] != 0 {
-                    print!("b ");
-                } else if bit & self.pieces[WHITE][ROOK] != 0 {
-                    print!("R ");
-                } else if bit & self.pieces[BLACK][ROOK] != 0 {
-                    print!("r ");
-                } else if bit & self.pieces[WHITE][QUEEN] != 0 {
-                    print!("Q ");
-                } else if bit & self.pieces[BLACK][QUEEN] != 0 {
-                    print!("q ");
-                } else if bit & self.pieces[WHITE][KING] != 0 {
-                    print!("K ");
-                } else if bit & self.pieces[BLACK][KING] != 0 {
-                    print!("k ");
-                } else {
-                    print!(". ");
+            let squares: Vec<String> = (0..8).map(|file| {
+                match self.get_piece(coords_to_sq_ind(file, rank)) {
+                    Some((color, piece)) if unicode => {
+                        let symbols = if color == WHITE { ['♙', '♘', '♗', '♖', '♕', '♔'] } else { ['♟', '♞', '♝', '♜', '♛', '♚'] };
+                        symbols[piece].to_string()
+                    }
+                    Some((color, piece)) => {
+                        let c = ['p', 'n', 'b', 'r', 'q', 'k'][piece];
+                        (if color == WHITE { c.to_ascii_uppercase() } else { c }).to_string()
+                    }
+                    None => ".".to_string(),
                 }
+            }).collect();
+            if coordinates {
+                out.push_str(&format!("{} | {} |\n", rank + 1, squares.join(" ")));
+            } else {
+                out.push_str(&squares.join(" "));
+                out.push('\n');
             }
-            println!("|");
         }
-        println!("  +-----------------+");
-        println!("    a b c d e f g h");
+        if coordinates {
+            out.push_str("  +-----------------+\n");
+            out.push_str("    a b c d e f g h\n");
+        }
+        if side_to_move {
+            out.push_str(if self.w_to_move { "White to move\n" } else { "Black to move\n" });
+        }
+        out
     }
 
     /// Gets the piece type at a given square index.
@@ -271,33 +566,243 @@ impl Board {
         self.pieces[color][piece_type]
     }
 
-    /// Determines whether the current position is legal.
+    /// Returns the Zobrist hash of the position.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.zobrist_hash
+    }
+
+    /// Returns the combined occupancy bitboard of both colors.
+    pub fn get_all_occupancy(&self) -> u64 {
+        self.pieces_occ[WHITE] | self.pieces_occ[BLACK]
+    }
+
+    /// Gets the piece type at a given square index, regardless of its color.
+    ///
+    /// # Arguments
     ///
-    /// A position is considered legal if the side to move cannot capture the opponent's king.
+    /// * `sq_ind` - The square index to check (0-63)
+    ///
+    /// # Returns
+    ///
+    /// An Option containing the piece type if a piece is present, or None if the square is empty.
+    pub fn get_piece_type_on_sq(&self, sq_ind: usize) -> Option<usize> {
+        self.get_piece(sq_ind).map(|(_, piece)| piece)
+    }
+
+    /// Removes whatever piece is on a square, keeping the occupancy bitboards in sync.
+    ///
+    /// This does not update the Zobrist hash, so it is meant for scratch boards
+    /// (e.g. exchange evaluation) rather than positions that are searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `sq_ind` - The square index to clear (0-63)
+    pub fn clear_square(&mut self, sq_ind: usize) {
+        let mask = !sq_ind_to_bit(sq_ind);
+        for color in [WHITE, BLACK] {
+            for piece in 0..6 {
+                self.pieces[color][piece] &= mask;
+            }
+            self.pieces_occ[color] &= mask;
+        }
+    }
+
+    /// Computes the game phase from the material on the board, on the scale used by the
+    /// tapered evaluation.
+    ///
+    /// Knights and bishops count 1, rooks 2 and queens 4, so the starting position has phase 24.
+    /// The phase is capped at 24, which early promotions could otherwise exceed.
+    ///
+    /// # Returns
+    ///
+    /// The game phase, from 0 (only kings and pawns) to 24 (the middlegame).
+    pub fn phase(&self) -> i32 {
+        let phase: i32 = (KNIGHT..KING)
+            .map(|piece| GAMEPHASE_INC[piece] * (self.pieces[WHITE][piece] | self.pieces[BLACK][piece]).count_ones() as i32)
+            .sum();
+        phase.min(24)
+    }
+
+    /// Counts the material of one side.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - The side whose material to count (WHITE or BLACK)
+    ///
+    /// # Returns
+    ///
+    /// The total value in centipawns of the side's pieces and pawns, using `PIECE_VALUES`.
+    pub fn material(&self, color: usize) -> i32 {
+        (PAWN..KING).map(|piece| PIECE_VALUES[piece] * self.pieces[color][piece].count_ones() as i32).sum()
+    }
+
+    /// Returns the color-mirrored position: the board is flipped vertically and the colors of
+    /// the pieces, the castling rights and the side to move are swapped.
+    ///
+    /// The mirrored position is the same position seen from the other side, so evaluations
+    /// relative to the side to move are unchanged by mirroring.
+    ///
+    /// # Returns
+    ///
+    /// A new Board holding the mirrored position.
+    pub fn mirror(&self) -> Board {
+        let mut board = self.clone();
+        for piece in PAWN..=KING {
+            board.pieces[WHITE][piece] = flip_vertically(self.pieces[BLACK][piece]);
+            board.pieces[BLACK][piece] = flip_vertically(self.pieces[WHITE][piece]);
+        }
+        board.w_to_move = !self.w_to_move;
+        board.en_passant = self.en_passant.map(|sq| flip_sq_ind_vertically(sq as usize) as u8);
+        board.castling_rights = CastlingRights {
+            white_kingside: self.castling_rights.black_kingside,
+            white_queenside: self.castling_rights.black_queenside,
+            black_kingside: self.castling_rights.white_kingside,
+            black_queenside: self.castling_rights.white_queenside,
+        };
+        board.update_occupancy();
+        board.zobrist_hash = board.compute_zobrist_hash();
+        board
+    }
+
+    /// Places a piece on a square, replacing any piece already there and keeping the
+    /// occupancy bitboards in sync.
+    ///
+    /// Like `clear_square`, this does not update the Zobrist hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `sq_ind` - The square index to set (0-63)
+    /// * `color` - The color of the piece (WHITE or BLACK)
+    /// * `piece` - The type of the piece (PAWN to KING)
+    pub fn set_square(&mut self, sq_ind: usize, color: usize, piece: usize) {
+        self.clear_square(sq_ind);
+        let bit = sq_ind_to_bit(sq_ind);
+        self.pieces[color][piece] |= bit;
+        self.pieces_occ[color] |= bit;
+    }
+
+    /// Parses a move in UCI format and sets its flag from this position.
+    ///
+    /// `Move::from_uci` cannot tell castling, en passant or a double pawn push apart from
+    /// an ordinary move, so this looks at the piece on the source square to fill in the flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `uci` - A string representing the move in UCI format (e.g., "e2e4", "e1g1", "e7e8q").
+    ///
+    /// # Returns
+    ///
+    /// `Some(Move)` with the correct flag, or `None` if the string is invalid or there is no piece
+    /// on the source square.
+    pub fn move_from_uci(&self, uci: &str) -> Option<Move> {
+        let mv = Move::from_uci(uci)?;
+        let (_, piece) = self.get_piece(mv.from)?;
+        if mv.is_promotion() {
+            return Some(mv);
+        }
+        let flag = match piece {
+            PAWN if self.en_passant == Some(mv.to as u8) => MoveFlag::EnPassant,
+            PAWN if mv.from.abs_diff(mv.to) == 16 => MoveFlag::DoublePawnPush,
+            KING if (mv.from == 4 && mv.to == 6) || (mv.from == 60 && mv.to == 62) => MoveFlag::KingsideCastle,
+            KING if (mv.from == 4 && mv.to == 2) || (mv.from == 60 && mv.to == 58) => MoveFlag::QueensideCastle,
+            _ => MoveFlag::Normal,
+        };
+        Some(Move::new_with_flag(mv.from, mv.to, flag))
+    }
+
+    /// Writes a move in Standard Algebraic Notation (SAN), e.g. "Nf3", "exd5", "O-O" or "e8=Q+".
+    ///
+    /// The piece is disambiguated by file, rank or both when another piece of the same type could
+    /// legally move to the same square, and a check or checkmate is marked with "+" or "#".
     ///
     /// # Arguments
     ///
+    /// * `mv` - A legal move in this position.
     /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
     ///
     /// # Returns
     ///
-    /// A boolean indicating whether the current position is legal.
-    pub fn is_legal(&self, move_gen: &MoveGen) -> bool {
-        let king_sq_ind: usize;
-        if self.w_to_move {
-            king_sq_ind = bit_to_sq_ind(self.pieces[BLACK][KING]);
-            if king_sq_ind == 64 {
-                println!("No black king");
-                self.print();
-            }
-        } else {
-            king_sq_ind = bit_to_sq_ind(self.pieces[WHITE][KING]);
-            if king_sq_ind == 64 {
-                println!("No white king");
-                self.print();
+    /// The move in SAN.
+    pub fn move_to_san(&self, mv: Move, move_gen: &MoveGen) -> String {
+        let (_, piece) = self.get_piece(mv.from).expect("no piece on the source square");
+        let legal_moves = |board: &Board| {
+            let (captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+            captures.into_iter().chain(moves)
+                .filter(|&m| board.apply_move_to_board(m).is_legal(move_gen))
+                .collect::<Vec<Move>>()
+        };
+
+        let mut san = match mv.flag {
+            MoveFlag::KingsideCastle => String::from("O-O"),
+            MoveFlag::QueensideCastle => String::from("O-O-O"),
+            _ => {
+                let from = sq_ind_to_algebraic(mv.from);
+                let capture = mv.is_capture(self);
+                let mut san = String::new();
+                if piece == PAWN {
+                    if capture {
+                        san.push_str(&from[..1]);
+                    }
+                } else {
+                    san.push(['P', 'N', 'B', 'R', 'Q', 'K'][piece]);
+                    // Other pieces of the same type that can reach the same square
+                    let rivals: Vec<usize> = legal_moves(self).into_iter()
+                        .filter(|m| m.to == mv.to && m.from != mv.from && self.get_piece_type_on_sq(m.from) == Some(piece))
+                        .map(|m| m.from)
+                        .collect();
+                    if !rivals.is_empty() {
+                        if rivals.iter().all(|&sq| sq % 8 != mv.from % 8) {
+                            san.push_str(&from[..1]);
+                        } else if rivals.iter().all(|&sq| sq / 8 != mv.from / 8) {
+                            san.push_str(&from[1..]);
+                        } else {
+                            san.push_str(&from);
+                        }
+                    }
+                }
+                if capture {
+                    san.push('x');
+                }
+                san.push_str(&sq_ind_to_algebraic(mv.to));
+                if let Some(promotion) = mv.promotion {
+                    san.push('=');
+                    san.push(['P', 'N', 'B', 'R', 'Q', 'K'][promotion]);
+                }
+                san
             }
+        };
+
+        let new_board = self.apply_move_to_board(mv);
+        if new_board.is_check(move_gen) {
+            san.push(if legal_moves(&new_board).is_empty() { '#' } else { '+' });
         }
-        !self.is_square_attacked(king_sq_ind, self.w_to_move, move_gen)
+        san
+    }
+
+    /// Determines whether the current position is legal, i.e. whether the move that led to it
+    /// left the mover's own king safe.
+    ///
+    /// This is meant to be called on the board after a pseudo-legal move: it checks that the king
+    /// of the side that just moved (the side *not* to move) is not attacked, so that moves of
+    /// pinned pieces, king moves onto attacked squares (including squares only attacked through
+    /// the king's former square) and moves that ignore a check are all rejected. A position
+    /// where that king is missing is also illegal.
+    ///
+    /// Castling is the exception: the squares the king starts from and passes through are not
+    /// known here, so the move generator only generates castling moves when none of them are
+    /// attacked.
+    ///
+    /// # Arguments
+    ///
+    /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
+    ///
+    /// # Returns
+    ///
+    /// A boolean indicating whether the king of the side that just moved is safe.
+    pub fn is_legal(&self, move_gen: &MoveGen) -> bool {
+        let mover = if self.w_to_move { BLACK } else { WHITE };
+        let king = self.pieces[mover][KING];
+        king != 0 && !self.is_square_attacked(bit_to_sq_ind(king), self.w_to_move, move_gen)
     }
 
     /// Determines whether the current position is checkmate or stalemate.
@@ -367,6 +872,83 @@ impl Board {
         self.is_square_attacked(king_sq_ind, !self.w_to_move, move_gen)
     }
 
+    /// Checks whether a pseudo-legal move would give check, without making the move.
+    ///
+    /// Covers direct checks (including by a promoted piece or a castling rook) and
+    /// discovered checks (including those uncovered by removing an en passant pawn).
+    ///
+    /// # Arguments
+    ///
+    /// * `mv` - The move to test, for the side to move.
+    /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
+    ///
+    /// # Returns
+    ///
+    /// A boolean indicating whether the opponent's king would be attacked after the move.
+    pub fn gives_check(&self, mv: &Move, move_gen: &MoveGen) -> bool {
+        let (us, them) = if self.w_to_move { (WHITE, BLACK) } else { (BLACK, WHITE) };
+        if self.pieces[them][KING] == 0 {
+            return false;
+        }
+        let king_sq_ind = bit_to_sq_ind(self.pieces[them][KING]);
+        let piece = match self.get_piece(mv.from) {
+            Some((_, piece)) => piece,
+            None => return false,
+        };
+        let from_bit = sq_ind_to_bit(mv.from);
+        let to_bit = sq_ind_to_bit(mv.to);
+
+        // Our pieces and the occupancy as they will be after the move
+        let mut ours = self.pieces[us];
+        ours[piece] &= !from_bit;
+        ours[mv.promotion.unwrap_or(piece)] |= to_bit;
+        let mut occupancy = (self.get_all_occupancy() & !from_bit) | to_bit;
+        match mv.flag {
+            MoveFlag::EnPassant => {
+                let captured_sq_ind = if us == WHITE { mv.to - 8 } else { mv.to + 8 };
+                occupancy &= !sq_ind_to_bit(captured_sq_ind);
+            }
+            MoveFlag::KingsideCastle | MoveFlag::QueensideCastle => {
+                let (rook_from, rook_to) = match (mv.flag, us) {
+                    (MoveFlag::KingsideCastle, WHITE) => (7, 5),
+                    (MoveFlag::KingsideCastle, _) => (63, 61),
+                    (_, WHITE) => (0, 3),
+                    _ => (56, 59),
+                };
+                ours[ROOK] = (ours[ROOK] & !sq_ind_to_bit(rook_from)) | sq_ind_to_bit(rook_to);
+                occupancy = (occupancy & !sq_ind_to_bit(rook_from)) | sq_ind_to_bit(rook_to);
+            }
+            _ => {}
+        }
+
+        // Pawn attacks are found in reverse, as in attackers_to
+        let pawn_attacks = if us == WHITE {
+            move_gen.bp_capture_bitboard[king_sq_ind]
+        } else {
+            move_gen.wp_capture_bitboard[king_sq_ind]
+        };
+        (move_gen.bishop_attacks(king_sq_ind, occupancy) & (ours[BISHOP] | ours[QUEEN]))
+            | (move_gen.rook_attacks(king_sq_ind, occupancy) & (ours[ROOK] | ours[QUEEN]))
+            | (move_gen.n_move_bitboard[king_sq_ind] & ours[KNIGHT])
+            | (pawn_attacks & ours[PAWN])
+            != 0
+    }
+
+    /// Finds all pieces of a given side that attack a square.
+    ///
+    /// # Arguments
+    ///
+    /// * `sq_ind` - The square index (0-63) to check.
+    /// * `by_white` - If true, find white attackers; if false, find black attackers.
+    /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
+    ///
+    /// # Returns
+    ///
+    /// A bitboard of the attacking pieces.
+    pub fn attackers_to(&self, sq_ind: usize, by_white: bool, move_gen: &MoveGen) -> u64 {
+        self.find_attackers(sq_ind, by_white, move_gen, false)
+    }
+
     /// Checks if a square is attacked by a given side.
     ///
     /// # Arguments
@@ -379,51 +961,44 @@ impl Board {
     ///
     /// A boolean indicating whether the square is attacked by the specified side.
     pub fn is_square_attacked(&self, sq_ind: usize, by_white: bool, move_gen: &MoveGen) -> bool {
-        // Find out if the square is attacked by a given side (white if by_white is true, black if by_white is false).
-        if by_white {
-            // Can the king reach an enemy bishop or queen by a bishop move?
-            if (move_gen.gen_bishop_potential_captures(self, sq_ind) & (self.pieces[WHITE][BISHOP] | self.pieces[WHITE][QUEEN])) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy rook or queen by a rook move?
-            if (move_gen.gen_rook_potential_captures(self, sq_ind) & (self.pieces[WHITE][ROOK] | self.pieces[WHITE][QUEEN])) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy knight by a knight move?
-            if (move_gen.n_move_bitboard[sq_ind] & self.pieces[WHITE][KNIGHT]) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy pawn by a pawn move?
-            if (move_gen.bp_capture_bitboard[sq_ind] & self.pieces[WHITE][PAWN]) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy king by a king move?
-            if (move_gen.k_move_bitboard[sq_ind] & self.pieces[WHITE][KING]) != 0 {
-                return true;
-            }
-            false
+        self.find_attackers(sq_ind, by_white, move_gen, true) != 0
+    }
+
+    /// Finds the pieces of a given side that attack a square, for `attackers_to` and
+    /// `is_square_attacked`.
+    ///
+    /// The plain table lookups come before the magic lookups, which are skipped for a side
+    /// without the matching sliders, since the legality check of every move ends up here.
+    ///
+    /// # Arguments
+    ///
+    /// * `sq_ind` - The square index (0-63) to check.
+    /// * `by_white` - If true, find white attackers; if false, find black attackers.
+    /// * `move_gen` - A reference to a MoveGen struct for generating potential moves.
+    /// * `first_only` - If true, return as soon as any attacker is found.
+    ///
+    /// # Returns
+    ///
+    /// A bitboard of the attacking pieces, or only some of them if `first_only` is set.
+    fn find_attackers(&self, sq_ind: usize, by_white: bool, move_gen: &MoveGen, first_only: bool) -> u64 {
+        let pieces = &self.pieces[if by_white { WHITE } else { BLACK }];
+        // Pawn attacks are found in reverse: a white pawn attacks sq_ind if a black pawn on sq_ind could capture it
+        let pawn_attacks = if by_white {
+            move_gen.bp_capture_bitboard[sq_ind]
         } else {
-            // Can the king reach an enemy bishop or queen by a bishop move?
-            if (move_gen.gen_bishop_potential_captures(self, sq_ind) & (self.pieces[BLACK][BISHOP] | self.pieces[BLACK][QUEEN])) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy rook or queen by a rook move?
-            if (move_gen.gen_rook_potential_captures(self, sq_ind) & (self.pieces[BLACK][ROOK] | self.pieces[BLACK][QUEEN])) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy knight by a knight move?
-            if (move_gen.n_move_bitboard[sq_ind] & self.pieces[BLACK][KNIGHT]) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy pawn by a pawn move?
-            if (move_gen.wp_capture_bitboard[sq_ind] & self.pieces[BLACK][PAWN]) != 0 {
-                return true;
-            }
-            // Can the king reach an enemy king by a king move?
-            if (move_gen.k_move_bitboard[sq_ind] & self.pieces[BLACK][KING]) != 0 {
-                return true;
-            }
-            false
+            move_gen.wp_capture_bitboard[sq_ind]
+        };
+        let mut attackers = (pawn_attacks & pieces[PAWN])
+            | (move_gen.n_move_bitboard[sq_ind] & pieces[KNIGHT])
+            | (move_gen.k_move_bitboard[sq_ind] & pieces[KING]);
+        let diagonal = pieces[BISHOP] | pieces[QUEEN];
+        if diagonal != 0 && !(first_only && attackers != 0) {
+            attackers |= move_gen.gen_bishop_potential_captures(self, sq_ind) & diagonal;
+        }
+        let straight = pieces[ROOK] | pieces[QUEEN];
+        if straight != 0 && !(first_only && attackers != 0) {
+            attackers |= move_gen.gen_rook_potential_captures(self, sq_ind) & straight;
         }
+        attackers
     }
 }
\ No newline at end of file
@@ -1,12 +1,20 @@
 use std::collections::{HashMap, VecDeque};
-use crate::board::Board;
+use crate::board::{Board, FenError};
 use crate::move_types::Move;
 
 /// Represents a stack of boards for undoing moves.
 pub struct BoardStack {
+    /// Number of times each position has occurred in the game, including the moves played
+    /// before the current search, so that the search sees repetitions begun earlier in the game.
     pub position_history: HashMap<u64, u8>,
     pub(crate) state_stack: VecDeque<Board>,
     move_stack: VecDeque<Move>,
+    /// Length of the move stack at the root of the current search.
+    search_root: usize,
+    /// Longest the move stack has been since the search root was set.
+    max_ply: usize,
+    /// Static evaluation of each position on the state stack, if the search computed one.
+    static_evals: VecDeque<Option<i32>>,
 }
 
 impl BoardStack {
@@ -17,28 +25,43 @@ impl BoardStack {
             position_history: HashMap::new(),
             state_stack: VecDeque::new(),
             move_stack: VecDeque::new(),
+            search_root: 0,
+            max_ply: 0,
+            static_evals: VecDeque::new(),
         };
 
         board.position_history.insert(initial_state.zobrist_hash, 1);
         board.state_stack.push_front(initial_state);
+        board.static_evals.push_front(None);
         board
     }
 
     /// Generate a new boardstack whose starting board is given by the fen string
     pub fn new_from_fen(fen: &str) -> Self {
+        BoardStack::new_from_board(Board::new_from_fen(fen))
+    }
 
-        let mut board = BoardStack::new();
-        let fen_position = Board::new_from_fen(fen);
+    /// Generate a new boardstack from the fen string, or report why the fen is malformed
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        Board::try_from_fen(fen).map(BoardStack::new_from_board)
+    }
 
-        // Remove the starting position from the state stack and position history
-        board.position_history = HashMap::new();
-        board.state_stack.pop_front();
+    /// Generate a new boardstack whose starting board is the given board
+    pub fn new_from_board(board: Board) -> Self {
+        let mut stack = BoardStack {
+            position_history: HashMap::new(),
+            state_stack: VecDeque::new(),
+            move_stack: VecDeque::new(),
+            search_root: 0,
+            max_ply: 0,
+            static_evals: VecDeque::new(),
+        };
 
         // Add the starting position to the state stack and position history
-        board.position_history.insert(fen_position.zobrist_hash, 1);
-        board.state_stack.push_front(fen_position);
-
-        board
+        stack.position_history.insert(board.zobrist_hash, 1);
+        stack.state_stack.push_front(board);
+        stack.static_evals.push_front(None);
+        stack
     }
 
     /// Return the current state by peeking at the board stack
@@ -46,6 +69,16 @@ impl BoardStack {
         &self.state_stack.front().unwrap()
     }
 
+    /// Return the position the boardstack started from
+    pub fn root_state(&self) -> &Board {
+        self.state_stack.back().unwrap()
+    }
+
+    /// Return the moves made since the starting position, oldest first
+    pub fn moves(&self) -> Vec<Move> {
+        self.move_stack.iter().rev().copied().collect()
+    }
+
     /// Applies a move to the boardstack
     pub fn make_move(&mut self, mv: Move) {
         // Push the move onto the move stack
@@ -53,19 +86,79 @@ impl BoardStack {
 
         // Apply the move to the current state
         let new_board = self.current_state().apply_move_to_board(mv);
+        if cfg!(debug_assertions) {
+            new_board.assert_invariants();
+        }
 
         // Update position history
         *self.position_history.entry(new_board.zobrist_hash).or_insert(0) += 1;
 
         // Push the new board onto the stack
         self.state_stack.push_front(new_board);
+        self.static_evals.push_front(None);
+        self.max_ply = self.max_ply.max(self.move_stack.len());
+    }
+
+    /// Passes the move to the opponent, for null move pruning.
+    ///
+    /// The position after the null move is not added to the position history, since it does
+    /// not occur in the game. Use `undo_null_move` to take it back.
+    pub fn make_null_move(&mut self) {
+        let new_board = self.current_state().apply_null_move_to_board();
+        if cfg!(debug_assertions) {
+            new_board.assert_invariants();
+        }
+        self.move_stack.push_front(Move::null());
+        self.state_stack.push_front(new_board);
+        self.static_evals.push_front(None);
+        self.max_ply = self.max_ply.max(self.move_stack.len());
+    }
+
+    /// Undoes a null move made with `make_null_move`, restoring the en passant square and hash.
+    pub fn undo_null_move(&mut self) {
+        debug_assert!(self.move_stack.front() == Some(&Move::null()), "last move is not a null move");
+        self.state_stack.pop_front();
+        self.move_stack.pop_front();
+        self.static_evals.pop_front();
+        if cfg!(debug_assertions) {
+            self.current_state().assert_invariants();
+        }
+    }
+
+    /// Marks the current position as the root of a search, from which `ply` and
+    /// `seldepth` are measured.
+    pub fn set_search_root(&mut self) {
+        self.search_root = self.move_stack.len();
+        self.max_ply = self.search_root;
+    }
+
+    /// Returns the number of moves made since the search root.
+    pub fn ply(&self) -> i32 {
+        self.move_stack.len().saturating_sub(self.search_root) as i32
+    }
+
+    /// Returns the deepest ply (including quiescence) reached below the search root.
+    pub fn seldepth(&self) -> usize {
+        self.max_ply.saturating_sub(self.search_root)
+    }
+
+    /// Records the static evaluation of the current position, for comparison with later positions
+    /// in the search.
+    pub fn set_static_eval(&mut self, eval: i32) {
+        self.static_evals[0] = Some(eval);
+    }
+
+    /// Returns the static evaluation recorded for the position `plies` moves ago, if any.
+    pub fn static_eval_before(&self, plies: usize) -> Option<i32> {
+        self.static_evals.get(plies).copied().flatten()
     }
 
     /// Undoes the last move in the move stack.
     pub fn undo_move(&mut self) -> Option<Move> {
-        if let (_, Some(mv)) = (self.state_stack.pop_front(), self.move_stack.pop_front()) {
-            // Update position history for the current position we're leaving
-            let hash = self.current_state().zobrist_hash;
+        if let (Some(state), Some(mv)) = (self.state_stack.pop_front(), self.move_stack.pop_front()) {
+            self.static_evals.pop_front();
+            // Update position history for the position we're leaving
+            let hash = state.zobrist_hash;
             if let Some(count) = self.position_history.get_mut(&hash) {
                 if *count == 1 {
                     self.position_history.remove(&hash);
@@ -73,6 +166,9 @@ impl BoardStack {
                     *count -= 1;
                 }
             }
+            if cfg!(debug_assertions) {
+                self.current_state().assert_invariants();
+            }
             Some(mv)
         } else {
             None
@@ -114,7 +210,8 @@ impl BoardStack {
         // Get Zobrist hash of current position
         let hash = self.current_state().zobrist_hash;
 
-        // Check if there are 3 or more repetitions of the same hash
-        *self.position_history.get(&hash).unwrap() >= 3
+        // Check if there are 3 or more repetitions of the same hash. A position after a null
+        // move is not in the history at all
+        self.position_history.get(&hash).is_some_and(|&count| count >= 3)
     }
 }
\ No newline at end of file
@@ -0,0 +1,443 @@
+//! Endgame tablebases generated in memory.
+//!
+//! This module solves the endings of king and one piece against a lone king (KQK, KRK, KBK and
+//! KNK), and of king, bishop and knight against a lone king (KBNK), by retrograde analysis,
+//! without any tablebase files. The resulting table gives the distance to mate of every
+//! position, so that basic mates are played perfectly.
+//!
+//! Positions are stored with the stronger side as White; positions with the pieces on the Black
+//! side are mirrored before probing. Without pawns or castling the board has eight symmetries,
+//! so each table only holds the positions with the stronger king in the a1-d1-d4 triangle, and
+//! other positions are reflected into it. Castling rights and the move counters are ignored.
+//!
+//! The endings with one piece take a fraction of a second to solve, and KBNK a few seconds.
+//! Other endings with four pieces are not generated: capturing one of the pieces can leave a
+//! won ending, while these tables assume that every capture draws.
+
+use crate::bits::bits;
+use crate::board::Board;
+use crate::move_generation::MoveGen;
+use crate::move_types::Move;
+use crate::piece_types::{KNIGHT, BISHOP, ROOK, QUEEN, KING, WHITE, BLACK};
+
+/// Marks a position that is drawn, or not a legal position at all.
+const DRAW: u8 = u8::MAX;
+
+/// The most pieces the stronger side can have besides its king.
+const MAX_PIECES: usize = 2;
+
+/// The squares of the a1-d1-d4 triangle, which the stronger king is reflected into.
+const KING_SQUARES: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+/// The outcome of a tablebase position for the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wdl {
+    /// The side to move mates by force.
+    Win,
+    /// Neither side can force mate.
+    Draw,
+    /// The side to move is mated by force.
+    Loss,
+}
+
+/// The result of probing a tablebase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TablebaseResult {
+    /// The outcome for the side to move.
+    pub wdl: Wdl,
+    /// The number of plies until mate with best play by both sides, or 0 if the position is drawn.
+    pub dtm: u32,
+}
+
+/// A solved ending of king and one or two pieces against a lone king.
+pub struct Tablebase {
+    /// The types of the stronger side's pieces besides its king.
+    pieces: Vec<usize>,
+    /// The distance to mate in plies of each position, or `DRAW`.
+    dtm: Vec<u8>,
+}
+
+/// The squares of the pieces in a tablebase position, with the stronger side as White.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Position {
+    /// The stronger side's king.
+    strong_king: usize,
+    /// The stronger side's pieces, in the order of `Tablebase::pieces`.
+    pieces: [usize; MAX_PIECES],
+    /// The lone king.
+    weak_king: usize,
+}
+
+/// Iterates over the positions with the stronger king in the a1-d1-d4 triangle and the given
+/// number of pieces, legal or not.
+fn positions(num_pieces: usize) -> impl Iterator<Item = Position> {
+    let combinations = 64usize.pow(num_pieces as u32);
+    KING_SQUARES.iter().flat_map(move |&strong_king| (0..combinations).flat_map(move |combination| (0..64).map(move |weak_king| {
+        let mut pieces = [0; MAX_PIECES];
+        let mut rest = combination;
+        for sq in pieces[..num_pieces].iter_mut().rev() {
+            *sq = rest % 64;
+            rest /= 64;
+        }
+        Position { strong_king, pieces, weak_king }
+    })))
+}
+
+impl Position {
+    /// Reflects the position so that the stronger king is in the a1-d1-d4 triangle.
+    fn canonical(&self, num_pieces: usize) -> Position {
+        let (rank, file) = (self.strong_king / 8, self.strong_king % 8);
+        let flip_file = file > 3;
+        let flip_rank = rank > 3;
+        let (rank, file) = (if flip_rank { 7 - rank } else { rank }, if flip_file { 7 - file } else { file });
+        let swap = rank > file;
+        let reflect = |sq: usize| {
+            let (mut rank, mut file) = (sq / 8, sq % 8);
+            if flip_file {
+                file = 7 - file;
+            }
+            if flip_rank {
+                rank = 7 - rank;
+            }
+            if swap {
+                (rank, file) = (file, rank);
+            }
+            rank * 8 + file
+        };
+        let mut pieces = self.pieces;
+        for sq in pieces.iter_mut().take(num_pieces) {
+            *sq = reflect(*sq);
+        }
+        Position { strong_king: reflect(self.strong_king), pieces, weak_king: reflect(self.weak_king) }
+    }
+
+    /// Returns the reflections of the position held in a table: one, or two when the stronger
+    /// king is on the a1-h8 diagonal and reflecting the position in it gives another.
+    fn canonical_forms(&self, num_pieces: usize) -> impl Iterator<Item = Position> {
+        let canonical = self.canonical(num_pieces);
+        let transpose = |sq: usize| (sq % 8) * 8 + sq / 8;
+        let mut reflected = canonical;
+        for sq in reflected.pieces.iter_mut().take(num_pieces) {
+            *sq = transpose(*sq);
+        }
+        reflected.weak_king = transpose(reflected.weak_king);
+        let on_diagonal = canonical.strong_king / 8 == canonical.strong_king % 8 && reflected != canonical;
+        std::iter::once(canonical).chain(on_diagonal.then_some(reflected))
+    }
+
+    /// Returns the occupied squares.
+    fn occupancy(&self, num_pieces: usize) -> u64 {
+        self.pieces[..num_pieces].iter().fold((1 << self.strong_king) | (1 << self.weak_king), |occupancy, &sq| occupancy | (1 << sq))
+    }
+}
+
+impl Tablebase {
+    /// Solves the ending of king and the given pieces against a lone king.
+    ///
+    /// Starting from the checkmates, each pass takes back a move from the positions solved by
+    /// the previous pass. A position where the stronger side can reach a position lost for the
+    /// defender is won, and a position where every defender move reaches a position won for the
+    /// stronger side is lost. Positions that are never reached are drawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used.
+    /// * `pieces` - The stronger side's pieces: one piece from KNIGHT to QUEEN, or a bishop and a knight.
+    ///
+    /// # Returns
+    ///
+    /// The solved tablebase.
+    pub fn generate(move_gen: &MoveGen, pieces: &[usize]) -> Tablebase {
+        assert!(matches!(pieces, [KNIGHT..=QUEEN] | [BISHOP, KNIGHT] | [KNIGHT, BISHOP]),
+            "tablebases are only generated for a single piece or a bishop and knight");
+        let size = KING_SQUARES.len() * 64usize.pow(pieces.len() as u32 + 1) * 2;
+        let mut tablebase = Tablebase { pieces: pieces.to_vec(), dtm: vec![DRAW; size] };
+
+        // Checkmates
+        let mut solved: Vec<Position> = positions(pieces.len())
+            .filter(|position| tablebase.is_legal(move_gen, position, false)
+                && tablebase.defender_moves(move_gen, position).is_some_and(|moves| moves.is_empty())
+                && tablebase.is_check(move_gen, position))
+            .collect();
+        for position in &solved {
+            let i = tablebase.index(position, false);
+            tablebase.dtm[i] = 0;
+        }
+
+        // Positions won in `plies` are found from those lost in `plies - 1` and vice versa, so
+        // a pass that finds nothing new leaves nothing for the next pass to build on
+        let mut plies: u8 = 0;
+        while !solved.is_empty() {
+            plies += 1;
+            let strong_to_move = plies % 2 == 1;
+            let mut newly_solved = Vec::new();
+            for position in &solved {
+                let predecessors = if strong_to_move {
+                    tablebase.attacker_unmoves(move_gen, position)
+                } else {
+                    tablebase.defender_unmoves(move_gen, position)
+                };
+                for predecessor in predecessors.iter().flat_map(|predecessor| predecessor.canonical_forms(pieces.len())) {
+                    let i = tablebase.index(&predecessor, strong_to_move);
+                    if tablebase.dtm[i] != DRAW || !tablebase.is_legal(move_gen, &predecessor, strong_to_move) {
+                        continue;
+                    }
+                    // A move into a lost position wins, but the defender only loses once every
+                    // move has been shown to lose
+                    if strong_to_move || tablebase.is_lost(move_gen, &predecessor) {
+                        tablebase.dtm[i] = plies;
+                        newly_solved.push(predecessor);
+                    }
+                }
+            }
+            solved = newly_solved;
+        }
+        tablebase
+    }
+
+    /// The types of the stronger side's pieces besides its king.
+    pub fn pieces(&self) -> &[usize] {
+        &self.pieces
+    }
+
+    /// The longest forced mate in the ending, in plies.
+    pub fn longest_mate(&self) -> u32 {
+        self.dtm.iter().filter(|&&dtm| dtm != DRAW).max().copied().unwrap_or(0) as u32
+    }
+
+    /// Looks up a position in the tablebase.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The position to look up.
+    ///
+    /// # Returns
+    ///
+    /// The outcome and distance to mate for the side to move, or `None` if the material on the
+    /// board is not king and this tablebase's pieces against a lone king.
+    pub fn probe(&self, board: &Board) -> Option<TablebaseResult> {
+        // Mirror the board so that the stronger side is White
+        let mirrored;
+        let board = if self.pieces.iter().any(|&piece| board.get_piece_bitboard(BLACK, piece) != 0) {
+            mirrored = board.mirror();
+            &mirrored
+        } else {
+            board
+        };
+
+        let mut white_pieces = board.get_piece_bitboard(WHITE, KING);
+        let mut pieces = [0; MAX_PIECES];
+        for (sq, &piece) in pieces.iter_mut().zip(&self.pieces) {
+            let bitboard = board.get_piece_bitboard(WHITE, piece);
+            if bitboard.count_ones() != 1 {
+                return None;
+            }
+            *sq = bitboard.trailing_zeros() as usize;
+            white_pieces |= bitboard;
+        }
+        let black_pieces = board.get_piece_bitboard(BLACK, KING);
+        if board.get_piece_bitboard(WHITE, KING).count_ones() != 1
+            || black_pieces.count_ones() != 1
+            || board.get_all_occupancy() != white_pieces | black_pieces {
+            return None;
+        }
+
+        let position = Position {
+            strong_king: board.get_piece_bitboard(WHITE, KING).trailing_zeros() as usize,
+            pieces,
+            weak_king: black_pieces.trailing_zeros() as usize,
+        };
+        let dtm = self.dtm[self.index(&position.canonical(self.pieces.len()), board.w_to_move)];
+        Some(match dtm {
+            DRAW => TablebaseResult { wdl: Wdl::Draw, dtm: 0 },
+            _ if board.w_to_move => TablebaseResult { wdl: Wdl::Win, dtm: dtm as u32 },
+            _ => TablebaseResult { wdl: Wdl::Loss, dtm: dtm as u32 },
+        })
+    }
+
+    /// Chooses the best move in a tablebase position: the fastest mate when winning, the
+    /// slowest when losing, and any drawing move otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The position to move in.
+    /// * `move_gen` - A reference to the move generator.
+    ///
+    /// # Returns
+    ///
+    /// The best move, or `None` if the position is not in this tablebase or has no legal moves.
+    pub fn best_move(&self, board: &Board, move_gen: &MoveGen) -> Option<Move> {
+        let result = self.probe(board)?;
+        let (captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+        captures.into_iter().chain(moves)
+            .filter_map(|m| {
+                let child = board.apply_move_to_board(m);
+                if !child.is_legal(move_gen) {
+                    return None;
+                }
+                // Capturing a piece leaves a drawn ending that is not in the table
+                let child_result = self.probe(&child).unwrap_or(TablebaseResult { wdl: Wdl::Draw, dtm: 0 });
+                Some((m, child_result))
+            })
+            .max_by_key(|&(_, child)| match (result.wdl, child.wdl) {
+                (Wdl::Win, Wdl::Loss) => -(child.dtm as i64),
+                (Wdl::Loss, Wdl::Win) => child.dtm as i64,
+                (Wdl::Draw, Wdl::Draw) => 0,
+                _ => i64::MIN,
+            })
+            .map(|(m, _)| m)
+    }
+
+    /// Returns the index of a position with the stronger king in the a1-d1-d4 triangle, with
+    /// the stronger side (White) to move if `strong_to_move`.
+    fn index(&self, position: &Position, strong_to_move: bool) -> usize {
+        let king = KING_SQUARES.iter().position(|&sq| sq == position.strong_king).expect("the position is reflected into the triangle");
+        let squares = position.pieces[..self.pieces.len()].iter().fold(king, |index, &sq| index * 64 + sq);
+        (squares * 64 + position.weak_king) * 2 + if strong_to_move { 0 } else { 1 }
+    }
+
+    /// Returns the squares attacked by a piece on the given square.
+    fn attacks(move_gen: &MoveGen, piece: usize, sq: usize, occupancy: u64) -> u64 {
+        match piece {
+            KNIGHT => move_gen.n_move_bitboard[sq],
+            BISHOP => move_gen.bishop_reach(sq, occupancy),
+            ROOK => move_gen.rook_reach(sq, occupancy),
+            _ => move_gen.bishop_reach(sq, occupancy) | move_gen.rook_reach(sq, occupancy),
+        }
+    }
+
+    /// Checks whether any of the stronger side's pieces, except the one at `skip`, attacks the
+    /// square with the given occupancy.
+    fn is_attacked(&self, move_gen: &MoveGen, position: &Position, target: usize, occupancy: u64, skip: Option<usize>) -> bool {
+        for (i, &piece) in self.pieces.iter().enumerate() {
+            if Some(i) != skip && Tablebase::attacks(move_gen, piece, position.pieces[i], occupancy) & (1 << target) != 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Checks whether the lone king is in check.
+    fn is_check(&self, move_gen: &MoveGen, position: &Position) -> bool {
+        self.is_attacked(move_gen, position, position.weak_king, position.occupancy(self.pieces.len()), None)
+    }
+
+    /// Checks that the squares are distinct, the kings are apart, and the side not to move is not in check.
+    fn is_legal(&self, move_gen: &MoveGen, position: &Position, strong_to_move: bool) -> bool {
+        position.occupancy(self.pieces.len()).count_ones() as usize == self.pieces.len() + 2
+            && move_gen.k_move_bitboard[position.strong_king] & (1 << position.weak_king) == 0
+            && !(strong_to_move && self.is_check(move_gen, position))
+    }
+
+    /// Returns the squares the lone king can move to, or `None` if it can capture a piece.
+    fn defender_moves(&self, move_gen: &MoveGen, position: &Position) -> Option<Vec<usize>> {
+        let mut moves = Vec::new();
+        self.visit_defender_moves(move_gen, position, |king| {
+            moves.push(king);
+            true
+        }).then_some(moves)
+    }
+
+    /// Passes each square the lone king can move to to `visit` until it returns `false`.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the king can capture a piece or `visit` returned `false`, `true` otherwise.
+    fn visit_defender_moves(&self, move_gen: &MoveGen, position: &Position, mut visit: impl FnMut(usize) -> bool) -> bool {
+        // The king does not block the pieces' attacks along the line it moves away on
+        let others = position.occupancy(self.pieces.len()) & !(1 << position.weak_king);
+        let targets = move_gen.k_move_bitboard[position.weak_king] & !move_gen.k_move_bitboard[position.strong_king];
+        for king in bits(&targets) {
+            let captured = position.pieces[..self.pieces.len()].iter().position(|&sq| sq == king);
+            if !self.is_attacked(move_gen, position, king, others | (1 << king), captured)
+                && (captured.is_some() || !visit(king)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks whether the lone king is to move and every one of its moves reaches a position
+    /// already known to be won for the stronger side.
+    fn is_lost(&self, move_gen: &MoveGen, position: &Position) -> bool {
+        let mut has_moves = false;
+        self.visit_defender_moves(move_gen, position, |king| {
+            has_moves = true;
+            let child = Position { weak_king: king, ..*position }.canonical(self.pieces.len());
+            self.dtm[self.index(&child, true)] != DRAW
+        }) && has_moves
+    }
+
+    /// Returns the positions the stronger side could have moved from to reach this one.
+    fn attacker_unmoves(&self, move_gen: &MoveGen, position: &Position) -> Vec<Position> {
+        let occupancy = position.occupancy(self.pieces.len());
+        let king_sources = move_gen.k_move_bitboard[position.strong_king] & !move_gen.k_move_bitboard[position.weak_king] & !occupancy;
+        let mut unmoves: Vec<Position> = bits(&king_sources).map(|king| Position { strong_king: king, ..*position }).collect();
+        for (i, &piece) in self.pieces.iter().enumerate() {
+            // Pieces move the same way backwards, and never capture in these endings
+            let sources = Tablebase::attacks(move_gen, piece, position.pieces[i], occupancy) & !occupancy;
+            unmoves.extend(bits(&sources).map(|sq| {
+                let mut unmove = *position;
+                unmove.pieces[i] = sq;
+                unmove
+            }));
+        }
+        unmoves
+    }
+
+    /// Returns the positions the lone king could have moved from to reach this one.
+    fn defender_unmoves(&self, move_gen: &MoveGen, position: &Position) -> Vec<Position> {
+        let sources = move_gen.k_move_bitboard[position.weak_king] & !move_gen.k_move_bitboard[position.strong_king]
+            & !position.occupancy(self.pieces.len());
+        bits(&sources).map(|king| Position { weak_king: king, ..*position }).collect()
+    }
+}
+
+/// The tablebases of the endings with mating material against a lone king: KQK, KRK and KBNK.
+pub struct Tablebases {
+    tables: Vec<Tablebase>,
+}
+
+impl Tablebases {
+    /// Solves every ending with mating material against a lone king.
+    ///
+    /// # Arguments
+    ///
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used.
+    ///
+    /// # Returns
+    ///
+    /// The solved tablebases.
+    pub fn generate(move_gen: &MoveGen) -> Tablebases {
+        Tablebases { tables: [&[QUEEN][..], &[ROOK], &[BISHOP, KNIGHT]].iter().map(|pieces| Tablebase::generate(move_gen, pieces)).collect() }
+    }
+
+    /// Looks up a position in the tablebase for its material.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The position to look up.
+    ///
+    /// # Returns
+    ///
+    /// The outcome and distance to mate for the side to move, or `None` if no tablebase holds
+    /// the material on the board.
+    pub fn probe(&self, board: &Board) -> Option<TablebaseResult> {
+        self.tables.iter().find_map(|table| table.probe(board))
+    }
+
+    /// Chooses the best move in a position of one of the tablebases, like `Tablebase::best_move`.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The position to move in.
+    /// * `move_gen` - A reference to the move generator.
+    ///
+    /// # Returns
+    ///
+    /// The best move, or `None` if no tablebase holds the material on the board or the position
+    /// has no legal moves.
+    pub fn best_move(&self, board: &Board, move_gen: &MoveGen) -> Option<Move> {
+        self.tables.iter().find(|table| table.probe(board).is_some())?.best_move(board, move_gen)
+    }
+}
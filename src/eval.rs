@@ -2,29 +2,185 @@
 //!
 //! This module implements the Pesto evaluation function, which uses tapered evaluation
 //! to interpolate between piece-square tables for opening and endgame, optimized by Texel tuning.
-//!
-//! TODO: Add pawn structure and king safety, possibly using a sum of over all pairs of adjacent pawns
-//! and counting pieces and pawns in front of the king.
 
+use std::cell::RefCell;
 use std::cmp::min;
 use crate::board_utils::flip_sq_ind_vertically;
-use crate::bits::popcnt;
+use crate::bits::{bits, popcnt};
 use crate::board::Board;
 use crate::move_generation::MoveGen;
-use crate::piece_types::{PAWN, KNIGHT, ROOK, QUEEN, KING, WHITE, BLACK};
-use crate::eval_constants::{MG_VALUE, MG_PESTO_TABLE, EG_VALUE, EG_PESTO_TABLE, GAMEPHASE_INC};
+use crate::piece_types::{PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING, WHITE, BLACK};
+use crate::eval_constants::{MG_VALUE, MG_PESTO_TABLE, EG_VALUE, EG_PESTO_TABLE,
+                            MG_PASSED_PAWN_BONUS, EG_PASSED_PAWN_BONUS, CONNECTED_PASSER_BONUS, PROTECTED_PASSER_BONUS, DOUBLED_PAWN_PENALTY,
+                            KING_ATTACK_UNITS, KING_ATTACK_WEIGHT, BISHOP_PAIR_BONUS, BISHOP_PAIR_OPEN_BONUS, BAD_BISHOP_PENALTY, SPACE_BONUS, MOBILITY_BONUS, MOP_UP_EDGE_BONUS, MOP_UP_KING_PROXIMITY_BONUS,
+                            KBN_CORNER_BONUS, UNSTOPPABLE_PASSER_BONUS, KPK_KEY_SQUARE_BONUS, KPK_DRAW_SCALE};
+
+/// Bitboard of the a-file
+const FILE_A: u64 = 0x0101010101010101;
+
+/// Bitboard of the c-, d-, e- and f-files, where space is counted
+const CENTER_FILES: u64 = 0x3C3C3C3C3C3C3C3C;
+
+/// Bitboard of the light squares
+const LIGHT_SQUARES: u64 = 0x55AA55AA55AA55AA;
+
+/// Number of entries in the pawn hash table
+const PAWN_TABLE_SIZE: usize = 1 << 12;
+
+/// The pawn structure terms of one pawn skeleton, as cached in the pawn hash table.
+///
+/// The default entry is correct for a board without pawns, so an empty table needs no
+/// special marker.
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    pawns: [u64; 2], // [Color]: the pawns of each side, which identify the entry
+    passers: [u64; 2], // [Color]: the passed pawns of each side
+    mg: [i32; 2], // [Color]: middlegame score of the terms that depend only on the pawns
+    eg: [i32; 2], // [Color]: endgame score of the terms that depend only on the pawns
+}
+
+thread_local! {
+    /// The pawn hash table, holding the pawn structure terms of recently seen pawn skeletons
+    ///
+    /// The terms depend only on the pawns, so every evaluator on a thread can share the table,
+    /// and evaluators themselves hold no cache and can be shared between threads.
+    static PAWN_TABLE: RefCell<Vec<PawnEntry>> = RefCell::new(vec![PawnEntry::default(); PAWN_TABLE_SIZE]);
+}
+
+/// Returns the pawn hash table index of a pawn skeleton
+fn pawn_table_index(pawns: [u64; 2]) -> usize {
+    let hash = pawns[WHITE].wrapping_mul(0x9E3779B97F4A7C15) ^ pawns[BLACK].wrapping_mul(0xC2B2AE3D27D4EB4F);
+    (hash >> (64 - PAWN_TABLE_SIZE.trailing_zeros())) as usize
+}
+
+/// Returns the bitboard of the files adjacent to the given file (0 = a-file)
+fn adjacent_files(file: usize) -> u64 {
+    let mut files = 0;
+    if file > 0 {
+        files |= FILE_A << (file - 1);
+    }
+    if file < 7 {
+        files |= FILE_A << (file + 1);
+    }
+    files
+}
+
+/// Returns the Manhattan distance between two squares
+fn manhattan_distance(sq1: usize, sq2: usize) -> i32 {
+    ((sq1 % 8) as i32 - (sq2 % 8) as i32).abs() + ((sq1 / 8) as i32 - (sq2 / 8) as i32).abs()
+}
+
+/// Returns the number of king moves between two squares
+fn king_distance(sq1: usize, sq2: usize) -> i32 {
+    ((sq1 % 8) as i32 - (sq2 % 8) as i32).abs().max(((sq1 / 8) as i32 - (sq2 / 8) as i32).abs())
+}
+
+/// Returns the square on the given file and rank relative to the given color (rank 0 = its back rank)
+fn relative_sq(color: usize, file: usize, rank: usize) -> usize {
+    if color == WHITE { 8 * rank + file } else { 8 * (7 - rank) + file }
+}
+
+/// Returns the Manhattan distance of a square from the nearest of the four centre squares
+fn center_distance(sq: usize) -> i32 {
+    [27, 28, 35, 36].iter().map(|&center| manhattan_distance(sq, center)).min().unwrap()
+}
+
+/// A position evaluation function that the search can be run with
+pub trait Evaluator {
+    /// Evaluates a position
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the position to evaluate
+    /// * `move_gen` - A reference to the move generator
+    ///
+    /// # Returns
+    ///
+    /// The evaluation of the position in centipawns, relative to the side to move
+    fn eval(&self, board: &Board, move_gen: &MoveGen) -> i32;
+
+    /// Evaluates a position from White's point of view, e.g. for comparing with game results
+    /// that are also given from White's point of view
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the position to evaluate
+    /// * `move_gen` - A reference to the move generator
+    ///
+    /// # Returns
+    ///
+    /// The evaluation of the position in centipawns, positive when White is better
+    fn eval_white_pov(&self, board: &Board, move_gen: &MoveGen) -> i32 {
+        let eval = self.eval(board, move_gen);
+        if board.w_to_move { eval } else { -eval }
+    }
+
+    /// Scores a non-capture for move ordering, where higher scores are searched first
+    ///
+    /// The default scores all moves equally, leaving them in generation order.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the position before the move
+    /// * `move_gen` - A reference to the move generator
+    /// * `from_sq_ind` - The starting square index of the move
+    /// * `to_sq_ind` - The ending square index of the move
+    ///
+    /// # Returns
+    ///
+    /// The ordering score of the move
+    fn move_eval(&self, _board: &Board, _move_gen: &MoveGen, _from_sq_ind: usize, _to_sq_ind: usize) -> i32 {
+        0
+    }
+}
+
+/// Selects which terms the evaluation includes on top of material and piece-square tables,
+/// so that individual terms can be compared or debugged. All terms are enabled by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalConfig {
+    /// Passed pawn bonuses and doubled pawn penalties
+    pub pawn_structure: bool,
+    /// King danger from enemy pieces attacking the king zone
+    pub king_safety: bool,
+    /// Bishop pair bonus and bad bishop penalty
+    pub bishops: bool,
+    /// Space behind and protected by the pawns
+    pub space: bool,
+    /// Safe squares the knights, bishops, rooks and queens can move to
+    pub mobility: bool,
+    /// Mop-up against a lone king, and the rule of the square and key squares in pawn endings
+    pub endgame_knowledge: bool,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        EvalConfig { pawn_structure: true, king_safety: true, bishops: true, space: true, mobility: true, endgame_knowledge: true }
+    }
+}
 
 /// Struct representing the Pesto evaluation function
 pub struct PestoEval {
     mg_table: [[[i32; 64]; 6]; 2], // [Color][PieceType][Square]
     eg_table: [[[i32; 64]; 6]; 2], // [Color][PieceType][Square]
+    passed_pawn_masks: [[u64; 64]; 2], // [Color][Square]: squares that enemy pawns must avoid for a pawn to be passed
+    pawn_defender_masks: [[u64; 64]; 2], // [Color][Square]: squares from which a friendly pawn defends the square
+    config: EvalConfig, // The evaluation terms in use
 }
 
 impl PestoEval {
-    /// Creates a new PestoEval instance
+    /// Creates a new PestoEval instance with all evaluation terms enabled
     ///
     /// Initializes the middlegame and endgame tables for all piece types
-    pub fn new() -> PestoEval
+    pub fn new() -> PestoEval {
+        PestoEval::with_config(EvalConfig::default())
+    }
+
+    /// Creates a new PestoEval instance using only the given evaluation terms
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The evaluation terms to use
+    pub fn with_config(config: EvalConfig) -> PestoEval
     {
         let mut mg_table = [[[0; 64]; 6]; 2];
         let mut eg_table = [[[0; 64]; 6]; 2];
@@ -39,9 +195,34 @@ impl PestoEval {
             }
         }
 
+        // Initialize the pawn structure masks
+        let mut passed_pawn_masks = [[0; 64]; 2];
+        let mut pawn_defender_masks = [[0; 64]; 2];
+        for sq in 0..64 {
+            let (file, rank) = (sq % 8, sq / 8);
+            let files = (FILE_A << file) | adjacent_files(file);
+            for r in 0..8 {
+                let rank_mask = 0xFFu64 << (8 * r);
+                if r > rank {
+                    passed_pawn_masks[WHITE][sq] |= files & rank_mask;
+                } else if r < rank {
+                    passed_pawn_masks[BLACK][sq] |= files & rank_mask;
+                }
+            }
+            if rank > 0 {
+                pawn_defender_masks[WHITE][sq] = adjacent_files(file) & (0xFFu64 << (8 * (rank - 1)));
+            }
+            if rank < 7 {
+                pawn_defender_masks[BLACK][sq] = adjacent_files(file) & (0xFFu64 << (8 * (rank + 1)));
+            }
+        }
+
         PestoEval {
             mg_table,
             eg_table,
+            passed_pawn_masks,
+            pawn_defender_masks,
+            config,
         }
     }
 
@@ -51,15 +232,15 @@ impl PestoEval {
     /// # Arguments
     ///
     /// * `board` - A reference to the current Bitboard
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used
     ///
     /// # Returns
     ///
     /// (eval, game_phase)
-    fn eval_plus_game_phase(&self, board: &Board) -> (i32, i32) {
-
+    fn eval_plus_game_phase(&self, board: &Board, move_gen: &MoveGen) -> (i32, i32) {
         let mut mg: [i32; 2] = [0, 0];
         let mut eg: [i32; 2] = [0, 0];
-        let mut game_phase: i32 = 0;
+        let game_phase = board.phase();
 
         // Evaluate each piece
         for color in 0..2 {
@@ -68,20 +249,71 @@ impl PestoEval {
                     if board.pieces[color][piece] & (1u64 << sq) != 0 {
                         mg[color] += self.mg_table[color][piece][sq];
                         eg[color] += self.eg_table[color][piece][sq];
-                        game_phase += GAMEPHASE_INC[piece];
                     }
                 }
             }
         }
 
+        // Pawn structure
+        if self.config.pawn_structure {
+            let entry = self.pawn_entry(board);
+            for color in [WHITE, BLACK] {
+                mg[color] += entry.mg[color];
+                eg[color] += entry.eg[color];
+            }
+            // A rook behind a passer depends on where the rooks and the other pieces are, so
+            // the pawn hash key cannot cover it; only the passers it looks at come from the table
+            self.add_rook_supported_passer_bonuses(board, entry.passers, &mut mg, &mut eg);
+        }
+
+        // Bishops depend on the pawns around them
+        if self.config.bishops {
+            self.add_bishop_terms(board, &mut mg, &mut eg);
+        }
+
+        // Space helps to manoeuvre while there are pieces on the board, so it is a middlegame term
+        if self.config.space {
+            mg[WHITE] += SPACE_BONUS * self.space(board, move_gen, WHITE);
+            mg[BLACK] += SPACE_BONUS * self.space(board, move_gen, BLACK);
+        }
+
+        // Mobility
+        if self.config.mobility {
+            for color in [WHITE, BLACK] {
+                let (mg_mobility, eg_mobility) = self.mobility(board, move_gen, color);
+                mg[color] += mg_mobility;
+                eg[color] += eg_mobility;
+            }
+        }
+
+        // King safety only matters while there are pieces left to attack with, so it is a middlegame term
+        if self.config.king_safety {
+            mg[WHITE] -= self.king_danger(board, move_gen, WHITE);
+            mg[BLACK] -= self.king_danger(board, move_gen, BLACK);
+        }
+
         // Tapered eval
         let mg_score = mg[0] - mg[1]; // White - Black
         let eg_score = eg[0] - eg[1]; // White - Black
 
-        let mg_phase: i32 = min(24, game_phase);
+        let mg_phase: i32 = game_phase;
         let eg_phase: i32 = 24 - mg_phase;
 
-        let score = (mg_score * mg_phase + eg_score * eg_phase) / 24;
+        let mut score = (mg_score * mg_phase + eg_score * eg_phase) / 24;
+
+        if self.config.endgame_knowledge {
+            // Mop-up in won endgames against a lone king, which the tables do not understand
+            score += self.mop_up_bonus(board, WHITE) - self.mop_up_bonus(board, BLACK);
+
+            // Pawn endings, where the race to promote decides the game
+            score += self.unstoppable_passer_bonus(board, WHITE) - self.unstoppable_passer_bonus(board, BLACK);
+            match self.kpk_result(board) {
+                Some(Some(WHITE)) => score += KPK_KEY_SQUARE_BONUS,
+                Some(Some(_)) => score -= KPK_KEY_SQUARE_BONUS,
+                Some(None) => score /= KPK_DRAW_SCALE,
+                None => {},
+            }
+        }
 
         // Return score from the perspective of the side to move
         if board.w_to_move {
@@ -91,19 +323,434 @@ impl PestoEval {
         }
     }
 
-    /// Evaluates the current board position (in centipawns),
-    /// relative to the side to move, according to the Pesto evaluation function
+    /// Computes the danger (in centipawns) to the king of the given color from enemy pieces
+    /// attacking its king zone (the king square and the squares around it)
+    ///
+    /// Each attacking piece contributes attack units per attacked zone square, and the total
+    /// is scaled by a non-linear weight indexed by the number of attacking pieces.
     ///
     /// # Arguments
     ///
     /// * `board` - A reference to the current Bitboard
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used
+    /// * `color` - The color of the king under attack
     ///
     /// # Returns
     ///
-    /// A tuple (i32, i32) representing the middlegame and endgame scores
-    pub fn eval(&self, board: &Board) -> i32 {
-        let (eval, _) = self.eval_plus_game_phase(board);
-        eval
+    /// The king danger score (non-negative), to be subtracted from the middlegame score of `color`
+    pub fn king_danger(&self, board: &Board, move_gen: &MoveGen, color: usize) -> i32 {
+        if board.pieces[color][KING] == 0 {
+            return 0;
+        }
+        let king_sq = board.pieces[color][KING].trailing_zeros() as usize;
+        let zone = move_gen.k_move_bitboard[king_sq] | (1u64 << king_sq);
+        let enemy = 1 - color;
+        let occupancy = board.get_all_occupancy();
+
+        let mut attackers: usize = 0;
+        let mut units: i32 = 0;
+        for piece in [KNIGHT, BISHOP, ROOK, QUEEN] {
+            for sq in bits(&board.pieces[enemy][piece]) {
+                let attacks = match piece {
+                    KNIGHT => move_gen.n_move_bitboard[sq],
+                    BISHOP => move_gen.bishop_reach(sq, occupancy),
+                    ROOK => move_gen.rook_reach(sq, occupancy),
+                    _ => move_gen.bishop_reach(sq, occupancy) | move_gen.rook_reach(sq, occupancy),
+                };
+                let attacked = popcnt(attacks & zone);
+                if attacked > 0 {
+                    attackers += 1;
+                    units += KING_ATTACK_UNITS[piece] * attacked;
+                }
+            }
+        }
+
+        units * KING_ATTACK_WEIGHT[attackers.min(KING_ATTACK_WEIGHT.len() - 1)] / 100
+    }
+
+    /// Computes the mop-up bonus (in centipawns) for the given color against a lone enemy king
+    ///
+    /// When the enemy has only its king left and `color` has enough material to force mate,
+    /// the lone king should be driven to the edge with the help of the attacking king. The bonus
+    /// grows as the lone king gets further from the centre and as the kings get closer. With
+    /// only a bishop and a knight, mate is only possible in a corner of the bishop's color, so
+    /// the lone king is also driven towards those corners.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    /// * `color` - The color of the attacking side
+    ///
+    /// # Returns
+    ///
+    /// The mop-up bonus for `color`, or 0 if the enemy has more than a king or `color` cannot force mate
+    pub fn mop_up_bonus(&self, board: &Board, color: usize) -> i32 {
+        let enemy = 1 - color;
+        if board.pieces_occ[enemy] != board.pieces[enemy][KING] || board.pieces[color][KING] == 0 || board.pieces[enemy][KING] == 0 {
+            return 0;
+        }
+        let knights = popcnt(board.pieces[color][KNIGHT]);
+        let bishops = popcnt(board.pieces[color][BISHOP]);
+        let heavy_pieces = board.pieces[color][ROOK] | board.pieces[color][QUEEN];
+        if heavy_pieces == 0 && !(bishops >= 1 && knights + bishops >= 2) {
+            return 0;
+        }
+
+        let king_sq = board.pieces[color][KING].trailing_zeros() as usize;
+        let lone_king_sq = board.pieces[enemy][KING].trailing_zeros() as usize;
+        let mut bonus = MOP_UP_EDGE_BONUS * center_distance(lone_king_sq)
+            + MOP_UP_KING_PROXIMITY_BONUS * (14 - manhattan_distance(king_sq, lone_king_sq));
+
+        // KBNvK: mate in a corner of the bishop's color (a1 and h8 are dark, a8 and h1 are light)
+        if heavy_pieces == 0 && knights == 1 && bishops == 1 {
+            let bishop_sq = board.pieces[color][BISHOP].trailing_zeros() as usize;
+            let light_bishop = (bishop_sq % 8 + bishop_sq / 8) % 2 == 1;
+            let corners = if light_bishop { [7, 56] } else { [0, 63] };
+            let corner_distance = corners.iter().map(|&corner| manhattan_distance(lone_king_sq, corner)).min().unwrap();
+            bonus += KBN_CORNER_BONUS * (14 - corner_distance);
+        }
+        bonus
+    }
+
+    /// Computes the bonus (in centipawns) for an unstoppable passed pawn of the given color in a
+    /// pawn ending
+    ///
+    /// A passed pawn is unstoppable if the enemy king is outside its square, i.e. cannot reach
+    /// the promotion square before the pawn, and the pawn's path is clear. Only applies when
+    /// neither side has any pieces left, since a piece could stop the pawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    /// * `color` - The color of the passed pawn
+    ///
+    /// # Returns
+    ///
+    /// The bonus for `color` if it has an unstoppable passer, otherwise 0
+    pub fn unstoppable_passer_bonus(&self, board: &Board, color: usize) -> i32 {
+        let kings = board.pieces[WHITE][KING] | board.pieces[BLACK][KING];
+        let pawns = board.pieces[WHITE][PAWN] | board.pieces[BLACK][PAWN];
+        let occupied = board.pieces_occ[WHITE] | board.pieces_occ[BLACK];
+        if occupied != kings | pawns || board.pieces[1 - color][KING] == 0 {
+            return 0;
+        }
+        let enemy_king_sq = board.pieces[1 - color][KING].trailing_zeros() as usize;
+        let defender_to_move = board.w_to_move == (color == BLACK);
+
+        for sq in bits(&board.pieces[color][PAWN]) {
+            if self.passed_pawn_masks[color][sq] & board.pieces[1 - color][PAWN] != 0 {
+                continue;
+            }
+            let (file, rank) = (sq % 8, if color == WHITE { sq / 8 } else { 7 - sq / 8 });
+            let path = (rank + 1..8).fold(0u64, |path, r| path | (1u64 << relative_sq(color, file, r)));
+            if path & occupied != 0 {
+                continue;
+            }
+            // A pawn on its starting rank can advance two squares at once
+            let steps = if rank == 1 { 5 } else { 7 - rank as i32 };
+            let defender_steps = king_distance(enemy_king_sq, relative_sq(color, file, 7)) - defender_to_move as i32;
+            if defender_steps > steps {
+                return UNSTOPPABLE_PASSER_BONUS;
+            }
+        }
+        0
+    }
+
+    /// Determines the result of a king and pawn versus king ending from the key squares
+    ///
+    /// The attacker wins if its king controls a key square of the pawn (the squares in front of
+    /// the pawn from which the king can escort it to promotion), unless the defender can capture
+    /// the pawn right away. The position is drawn if the defending king stands in front of the
+    /// pawn instead, or in the promotion corner of a rook pawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    ///
+    /// # Returns
+    ///
+    /// `None` if the position is not KPvK or its result is unclear, `Some(Some(color))` if `color`
+    /// wins, and `Some(None)` if it is drawn
+    pub fn kpk_result(&self, board: &Board) -> Option<Option<usize>> {
+        let pawns = board.pieces[WHITE][PAWN] | board.pieces[BLACK][PAWN];
+        let kings = board.pieces[WHITE][KING] | board.pieces[BLACK][KING];
+        if popcnt(pawns) != 1 || popcnt(kings) != 2 || (board.pieces_occ[WHITE] | board.pieces_occ[BLACK]) != pawns | kings {
+            return None;
+        }
+        let color = if board.pieces[WHITE][PAWN] != 0 { WHITE } else { BLACK };
+        let pawn_sq = pawns.trailing_zeros() as usize;
+        let king_sq = board.pieces[color][KING].trailing_zeros() as usize;
+        let defender_sq = board.pieces[1 - color][KING].trailing_zeros() as usize;
+        let (file, rank) = (pawn_sq % 8, if color == WHITE { pawn_sq / 8 } else { 7 - pawn_sq / 8 });
+
+        // The defender to move takes an undefended pawn
+        let defender_to_move = board.w_to_move == (color == BLACK);
+        if defender_to_move && king_distance(defender_sq, pawn_sq) == 1 && king_distance(king_sq, pawn_sq) > 1 {
+            return Some(None);
+        }
+
+        let (key_squares, corner) = if file == 0 || file == 7 {
+            // Rook pawn: the king must get to the adjacent file near promotion, and the
+            // defender draws by reaching the corner
+            let adjacent = if file == 0 { 1 } else { 6 };
+            (vec![relative_sq(color, adjacent, 6), relative_sq(color, adjacent, 7)],
+             vec![relative_sq(color, file, 7), relative_sq(color, file, 6), relative_sq(color, adjacent, 7), relative_sq(color, adjacent, 6)])
+        } else {
+            // Two ranks in front of the pawn, and also one rank in front once it is past the middle
+            let ranks = if rank >= 4 { (rank + 1)..(rank + 3).min(8) } else { (rank + 2)..(rank + 3) };
+            let mut squares = Vec::new();
+            for r in ranks {
+                for f in file - 1..=file + 1 {
+                    squares.push(relative_sq(color, f, r));
+                }
+            }
+            (squares, Vec::new())
+        };
+
+        if key_squares.contains(&king_sq) {
+            return Some(Some(color));
+        }
+        let in_front = (rank + 1..8).any(|r| relative_sq(color, file, r) == defender_sq);
+        if in_front || corner.contains(&defender_sq) {
+            return Some(None);
+        }
+        None
+    }
+
+    /// Looks up the pawn structure terms of the board's pawns in this thread's pawn hash table,
+    /// computing and storing them if the pawns are not there
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    ///
+    /// # Returns
+    ///
+    /// The entry for the board's pawns
+    fn pawn_entry(&self, board: &Board) -> PawnEntry {
+        let pawns = [board.pieces[WHITE][PAWN], board.pieces[BLACK][PAWN]];
+        let index = pawn_table_index(pawns);
+        PAWN_TABLE.with(|table| {
+            let cached = table.borrow()[index];
+            if cached.pawns == pawns {
+                return cached;
+            }
+            let entry = self.compute_pawn_entry(pawns);
+            table.borrow_mut()[index] = entry;
+            entry
+        })
+    }
+
+    /// Computes the pawn structure terms that depend only on the pawns, without the pawn hash table
+    ///
+    /// These are the passed pawn bonuses, including connected passers and passers protected by
+    /// a pawn, and the doubled pawn penalties. Passers protected by a rook behind them depend on
+    /// the other pieces too, and are scored separately on every evaluation.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    ///
+    /// # Returns
+    ///
+    /// The (middlegame, endgame) pawn structure scores, indexed by color
+    pub fn pawn_structure(&self, board: &Board) -> ([i32; 2], [i32; 2]) {
+        let entry = self.compute_pawn_entry([board.pieces[WHITE][PAWN], board.pieces[BLACK][PAWN]]);
+        (entry.mg, entry.eg)
+    }
+
+    /// Computes the pawn hash table entry for the given pawns of each color
+    ///
+    /// Passed pawns are rewarded by rank, with extra bonuses for connected passers (another
+    /// passed pawn on an adjacent file) and protected passers (defended by a friendly pawn).
+    /// Every pawn beyond the first on a file is penalized, so the penalty grows with the number
+    /// of pawns stacked on the file.
+    fn compute_pawn_entry(&self, pawns: [u64; 2]) -> PawnEntry {
+        let mut entry = PawnEntry { pawns, ..PawnEntry::default() };
+        for color in [WHITE, BLACK] {
+            let own_pawns = pawns[color];
+            let enemy_pawns = pawns[1 - color];
+
+            for sq in bits(&own_pawns) {
+                if self.passed_pawn_masks[color][sq] & enemy_pawns == 0 {
+                    entry.passers[color] |= 1u64 << sq;
+                }
+            }
+
+            for sq in bits(&entry.passers[color]) {
+                let file = sq % 8;
+                let rank = if color == WHITE { sq / 8 } else { 7 - sq / 8 };
+                entry.mg[color] += MG_PASSED_PAWN_BONUS[rank];
+                entry.eg[color] += EG_PASSED_PAWN_BONUS[rank];
+
+                // Connected: another passer on an adjacent file
+                if entry.passers[color] & adjacent_files(file) != 0 {
+                    entry.mg[color] += CONNECTED_PASSER_BONUS[0];
+                    entry.eg[color] += CONNECTED_PASSER_BONUS[1];
+                }
+
+                // Protected by a pawn
+                if self.pawn_defender_masks[color][sq] & own_pawns != 0 {
+                    entry.mg[color] += PROTECTED_PASSER_BONUS[0];
+                    entry.eg[color] += PROTECTED_PASSER_BONUS[1];
+                }
+            }
+
+            // Doubled and tripled pawns
+            for file in 0..8 {
+                let pawns_on_file = popcnt(own_pawns & (FILE_A << file));
+                if pawns_on_file > 1 {
+                    entry.mg[color] -= DOUBLED_PAWN_PENALTY[0] * (pawns_on_file - 1);
+                    entry.eg[color] -= DOUBLED_PAWN_PENALTY[1] * (pawns_on_file - 1);
+                }
+            }
+        }
+        entry
+    }
+
+    /// Adds the protected passer bonus for passed pawns that are not defended by a pawn, but by a
+    /// friendly rook behind them on the same file with nothing in between
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    /// * `passers` - The passed pawns of each color
+    /// * `mg` - The middlegame scores to update, indexed by color
+    /// * `eg` - The endgame scores to update, indexed by color
+    fn add_rook_supported_passer_bonuses(&self, board: &Board, passers: [u64; 2], mg: &mut [i32; 2], eg: &mut [i32; 2]) {
+        let occupied = board.pieces_occ[WHITE] | board.pieces_occ[BLACK];
+        for color in [WHITE, BLACK] {
+            if board.pieces[color][ROOK] == 0 {
+                continue;
+            }
+            for sq in bits(&passers[color]) {
+                if self.pawn_defender_masks[color][sq] & board.pieces[color][PAWN] != 0 {
+                    continue;
+                }
+                let file = sq % 8;
+                let behind = if color == WHITE { (FILE_A << file) & ((1u64 << sq) - 1) } else { (FILE_A << file) & !((2u64 << sq).wrapping_sub(1)) };
+                let blockers = behind & occupied;
+                let nearest = if blockers == 0 {
+                    0
+                } else if color == WHITE {
+                    1u64 << (63 - blockers.leading_zeros())
+                } else {
+                    blockers & blockers.wrapping_neg()
+                };
+                if nearest & board.pieces[color][ROOK] != 0 {
+                    mg[color] += PROTECTED_PASSER_BONUS[0];
+                    eg[color] += PROTECTED_PASSER_BONUS[1];
+                }
+            }
+        }
+    }
+
+    /// Counts the space of the given color: the safe central squares it controls with its pawns
+    ///
+    /// These are the squares on the c- to f-files from the third to the sixth rank (relative to
+    /// `color`) that are behind one of its pawns or attacked by one, and neither occupied by its
+    /// own pawns nor attacked by enemy pawns. Pieces can use these squares without being driven
+    /// away by pawns.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used
+    /// * `color` - The color whose space is counted
+    ///
+    /// # Returns
+    ///
+    /// The number of squares
+    pub fn space(&self, board: &Board, move_gen: &MoveGen, color: usize) -> i32 {
+        let own_pawns = board.pieces[color][PAWN];
+        let (own_captures, enemy_captures) = if color == WHITE {
+            (&move_gen.wp_capture_bitboard, &move_gen.bp_capture_bitboard)
+        } else {
+            (&move_gen.bp_capture_bitboard, &move_gen.wp_capture_bitboard)
+        };
+        let own_attacks = bits(&own_pawns).fold(0, |attacks, sq| attacks | own_captures[sq]);
+        let enemy_attacks = bits(&board.pieces[1 - color][PAWN]).fold(0, |attacks, sq| attacks | enemy_captures[sq]);
+
+        // Squares on the same file behind each pawn
+        let behind = bits(&own_pawns).fold(0, |behind, sq| {
+            let file = FILE_A << (sq % 8);
+            behind | if color == WHITE { file & ((1u64 << sq) - 1) } else { file & !((2u64 << sq).wrapping_sub(1)) }
+        });
+
+        // The third to sixth ranks are the same from either side
+        let area = CENTER_FILES & 0x0000FFFFFFFF0000 & !own_pawns & !enemy_attacks;
+        popcnt(area & (behind | own_attacks))
+    }
+
+    /// Computes the mobility bonus of the given color
+    ///
+    /// Each knight, bishop, rook and queen earns a bonus per square it can move to that is
+    /// neither occupied by its own pieces nor attacked by enemy pawns.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used
+    /// * `color` - The color whose mobility is counted
+    ///
+    /// # Returns
+    ///
+    /// The (middlegame, endgame) mobility bonus
+    pub fn mobility(&self, board: &Board, move_gen: &MoveGen, color: usize) -> (i32, i32) {
+        let enemy_captures = if color == WHITE { &move_gen.bp_capture_bitboard } else { &move_gen.wp_capture_bitboard };
+        let enemy_pawn_attacks = bits(&board.pieces[1 - color][PAWN]).fold(0, |attacks, sq| attacks | enemy_captures[sq]);
+        let safe = !board.pieces_occ[color] & !enemy_pawn_attacks;
+        let occupancy = board.get_all_occupancy();
+
+        let (mut mg, mut eg) = (0, 0);
+        for piece in [KNIGHT, BISHOP, ROOK, QUEEN] {
+            for sq in bits(&board.pieces[color][piece]) {
+                let reach = match piece {
+                    KNIGHT => move_gen.n_move_bitboard[sq],
+                    BISHOP => move_gen.bishop_reach(sq, occupancy),
+                    ROOK => move_gen.rook_reach(sq, occupancy),
+                    _ => move_gen.bishop_reach(sq, occupancy) | move_gen.rook_reach(sq, occupancy),
+                };
+                let squares = popcnt(reach & safe);
+                mg += MOBILITY_BONUS[piece][0] * squares;
+                eg += MOBILITY_BONUS[piece][1] * squares;
+            }
+        }
+        (mg, eg)
+    }
+
+    /// Adds the bishop pair bonus and the bad bishop penalty to the middlegame and endgame scores of each color
+    ///
+    /// The bishop pair is worth more the fewer pawns are left, as the position opens up. A bishop
+    /// is penalized for each friendly pawn on its own square color, which blocks its diagonals.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - A reference to the current Bitboard
+    /// * `mg` - The middlegame scores to update, indexed by color
+    /// * `eg` - The endgame scores to update, indexed by color
+    fn add_bishop_terms(&self, board: &Board, mg: &mut [i32; 2], eg: &mut [i32; 2]) {
+        let missing_pawns = 16 - popcnt(board.pieces[WHITE][PAWN] | board.pieces[BLACK][PAWN]);
+        for color in [WHITE, BLACK] {
+            let bishops = board.pieces[color][BISHOP];
+            if bishops & LIGHT_SQUARES != 0 && bishops & !LIGHT_SQUARES != 0 {
+                mg[color] += BISHOP_PAIR_BONUS[0] + BISHOP_PAIR_OPEN_BONUS * missing_pawns;
+                eg[color] += BISHOP_PAIR_BONUS[1] + BISHOP_PAIR_OPEN_BONUS * missing_pawns;
+            }
+
+            let own_pawns = board.pieces[color][PAWN];
+            for sq in bits(&bishops) {
+                let same_color = if LIGHT_SQUARES & (1u64 << sq) != 0 { LIGHT_SQUARES } else { !LIGHT_SQUARES };
+                let blocking_pawns = popcnt(own_pawns & same_color);
+                mg[color] -= BAD_BISHOP_PENALTY[0] * blocking_pawns;
+                eg[color] -= BAD_BISHOP_PENALTY[1] * blocking_pawns;
+            }
+        }
+    }
+
+    /// Returns the evaluation terms in use
+    pub fn config(&self) -> EvalConfig {
+        self.config
     }
 
     /// Evaluates and updates the board's evaluation and game phase
@@ -115,13 +762,14 @@ impl PestoEval {
     /// # Arguments
     ///
     /// * `board` - A mutable reference to the current Bitboard
+    /// * `move_gen` - A reference to the move generator, whose attack tables are used
     ///
     /// # Returns
     ///
     /// An i32 representing the evaluation of the position in centipawns, relative to the side to move
-    pub fn eval_update_board(&self, board: &mut Board) -> i32 {
+    pub fn eval_update_board(&self, board: &mut Board, move_gen: &MoveGen) -> i32 {
         // Evaluate and save the eval and game phase so we can quickly compute move evals from this position
-        let (score, game_phase) = self.eval_plus_game_phase(board);
+        let (score, game_phase) = self.eval_plus_game_phase(board, move_gen);
 
         // Save eval and game phase
         board.eval = if board.w_to_move { score } else { -score };
@@ -252,9 +900,33 @@ impl PestoEval {
             }
         }
 
-        let mg_phase: i32 = min(24, board.game_phase); // Can exceed 24 in case of early promotion
+        let mg_phase: i32 = min(24, board.game_phase);
         let eg_phase: i32 = 24 - mg_phase;
 
         (mg_score * mg_phase + eg_score * eg_phase) / 24
     }
-}
\ No newline at end of file
+}
+
+impl Evaluator for PestoEval {
+    fn eval(&self, board: &Board, move_gen: &MoveGen) -> i32 {
+        let (eval, _) = self.eval_plus_game_phase(board, move_gen);
+        eval
+    }
+
+    fn move_eval(&self, board: &Board, move_gen: &MoveGen, from_sq_ind: usize, to_sq_ind: usize) -> i32 {
+        PestoEval::move_eval(self, board, move_gen, from_sq_ind, to_sq_ind)
+    }
+}
+
+/// Evaluation that only counts material, using `PIECE_VALUES`
+///
+/// Mainly useful for testing the search independently of the Pesto evaluation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaterialEval;
+
+impl Evaluator for MaterialEval {
+    fn eval(&self, board: &Board, _move_gen: &MoveGen) -> i32 {
+        let (us, them) = if board.w_to_move { (WHITE, BLACK) } else { (BLACK, WHITE) };
+        board.material(us) - board.material(them)
+    }
+}
@@ -1,4 +1,4 @@
-//! Constants for the Pesto evaluation function module
+//! Constants for the Pesto evaluation function module, and the piece values derived from them
 //! Values from Rofchade: http://www.talkchess.com/forum3/viewtopic.php?f=2&t=68311&start=19
 //! We only modify the middlegame king table, so that the king doesn't want to go forward when all the pieces are on the board.
 //! Note that these apparently use a different indexing, so we need to flip the board vertically for white.
@@ -9,6 +9,22 @@ pub const MG_VALUE: [i32; 6] = [ 82, 337, 365, 477, 1025,  0];
 // Piece values in endgame
 pub const EG_VALUE: [i32; 6] = [ 94, 281, 297, 512,  936,  0];
 
+/// Piece values in centipawns for counting material, e.g. in static exchange evaluation.
+/// These are the midpoints of the middlegame and endgame values, so that they stay in line with
+/// the Pesto evaluation whatever the phase.
+pub const PIECE_VALUES: [i32; 6] = midpoint(&MG_VALUE, &EG_VALUE);
+
+/// The midpoint of each pair of values
+const fn midpoint(a: &[i32; 6], b: &[i32; 6]) -> [i32; 6] {
+    let mut values = [0; 6];
+    let mut i = 0;
+    while i < 6 {
+        values[i] = (a[i] + b[i]) / 2;
+        i += 1;
+    }
+    values
+}
+
 // Piece-square tables
 // Values from Rofchade: http://www.talkchess.com/forum3/viewtopic.php?f=2&t=68311&start=19
 // We only modify the middlegame king table, so that the king doesn't want to go forward when all the pieces are on the board
@@ -195,4 +211,59 @@ pub const EG_PESTO_TABLE: [[i32; 64]; 6] =
 /// Values of pieces to determine the phase of the game
 /// Weighted sum of all pieces except pawns and kings.
 /// Starts at 24 when all are still on the board, and decreases to 0 when all are gone.
-pub const GAMEPHASE_INC: [i32; 6] = [0,1,1,2,4,0];
\ No newline at end of file
+pub const GAMEPHASE_INC: [i32; 6] = [0,1,1,2,4,0];
+/// Middlegame bonus for a passed pawn, indexed by rank relative to its own side (0 = back rank)
+pub const MG_PASSED_PAWN_BONUS: [i32; 8] = [0, 0, 5, 10, 20, 35, 60, 0];
+
+/// Endgame bonus for a passed pawn, indexed by rank relative to its own side (0 = back rank)
+pub const EG_PASSED_PAWN_BONUS: [i32; 8] = [0, 10, 15, 25, 45, 70, 110, 0];
+
+/// Extra bonus for a passed pawn with another passed pawn on an adjacent file [middlegame, endgame]
+pub const CONNECTED_PASSER_BONUS: [i32; 2] = [10, 25];
+
+/// Extra bonus for a passed pawn defended by a friendly pawn or by a rook behind it [middlegame, endgame]
+pub const PROTECTED_PASSER_BONUS: [i32; 2] = [10, 20];
+
+/// Penalty for each pawn beyond the first on a file, so tripled pawns cost twice as much as doubled ones [middlegame, endgame]
+pub const DOUBLED_PAWN_PENALTY: [i32; 2] = [10, 25];
+
+/// Bonus for having both bishops [middlegame, endgame]
+pub const BISHOP_PAIR_BONUS: [i32; 2] = [20, 40];
+
+/// Extra bonus for the bishop pair per pawn missing from the board, since the bishops gain scope as the position opens up
+pub const BISHOP_PAIR_OPEN_BONUS: i32 = 3;
+
+/// Penalty for a bishop for each friendly pawn on squares of the bishop's color [middlegame, endgame]
+pub const BAD_BISHOP_PENALTY: [i32; 2] = [3, 6];
+
+/// Middlegame bonus per safe central square behind or protected by a side's own pawns
+pub const SPACE_BONUS: i32 = 3;
+
+/// Bonus per safe square a piece can move to, indexed by piece type [P, N, B, R, Q, K] and
+/// then [middlegame, endgame]
+pub const MOBILITY_BONUS: [[i32; 2]; 6] = [[0, 0], [4, 4], [4, 5], [2, 4], [1, 2], [0, 0]];
+
+/// Attack units for each enemy piece type attacking a square in the king zone [P, N, B, R, Q, K]
+pub const KING_ATTACK_UNITS: [i32; 6] = [0, 20, 20, 40, 80, 0];
+
+/// Percentage of the attack units that counts towards king danger, indexed by the number of
+/// attacking pieces: a lone attacker is harmless, but the danger grows quickly with more attackers
+pub const KING_ATTACK_WEIGHT: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+/// Bonus per step of Manhattan distance of a lone king from the centre, driving it to the edge
+pub const MOP_UP_EDGE_BONUS: i32 = 10;
+
+/// Bonus per step that the attacking king is closer to the lone king (out of 14)
+pub const MOP_UP_KING_PROXIMITY_BONUS: i32 = 4;
+
+/// Bonus per step that the lone king is closer to a corner of the bishop's color in KBNvK (out of 14)
+pub const KBN_CORNER_BONUS: i32 = 15;
+
+/// Bonus for a passed pawn that the enemy king cannot catch in a pawn ending (rule of the square)
+pub const UNSTOPPABLE_PASSER_BONUS: i32 = 500;
+
+/// Bonus in KPvK when the attacking king controls a key square of the pawn, which wins
+pub const KPK_KEY_SQUARE_BONUS: i32 = 300;
+
+/// Divisor applied to the eval of a KPvK position that is known to be drawn
+pub const KPK_DRAW_SCALE: i32 = 8;
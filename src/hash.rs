@@ -13,22 +13,25 @@
 //!
 //! # Note
 //!
-//! The Zobrist keys are generated randomly at program startup and remain
-//! constant for the duration of the program's execution. This ensures
-//! that the same position will always hash to the same value within a
-//! single run of the program.
+//! The Zobrist keys are generated from a fixed seed (`ZOBRIST_SEED`) by the in-tree
+//! `SplitMix64` generator, so the same position always hashes to the same value,
+//! within a run, across runs and across versions of the `rand` crate. This keeps
+//! transposition table behaviour, and therefore search results, reproducible.
 
 use lazy_static::lazy_static;
-use rand::Rng;
 use crate::bits::bits;
 use crate::board::Board;
 use crate::boardstack::BoardStack;
 use crate::piece_types::{PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING, WHITE, BLACK};
+use crate::rng::SplitMix64;
 
 const PIECE_TYPES: usize = 6;  // Pawn, Knight, Bishop, Rook, Queen, King
 const COLORS: usize = 2;       // White, Black
 const SQUARES: usize = 64;
 
+/// Seed for the random number generator that produces the Zobrist keys
+pub const ZOBRIST_SEED: u64 = 0x6b696e6766697368;
+
 /// Represents a set of Zobrist keys used for hashing chess positions.
 ///
 /// These keys are used to create a unique hash for each chess position,
@@ -46,36 +49,36 @@ pub struct ZobristKeys {
 }
 
 impl ZobristKeys {
-    /// Generates a new set of random Zobrist keys.
+    /// Generates a new set of pseudo-random Zobrist keys from `ZOBRIST_SEED`.
     ///
     /// This method should typically only be called once to initialize the global ZOBRIST_KEYS.
     fn new() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = SplitMix64::new(ZOBRIST_SEED);
 
         let mut keys = ZobristKeys {
             piece_keys: [[[0; SQUARES]; PIECE_TYPES]; COLORS],
             castling_keys: [0; 4],
             en_passant_keys: [0; 8],
-            side_to_move_key: rng.gen(),
+            side_to_move_key: rng.next_u64(),
         };
 
         // Generate keys for each piece type, color, and square
         for color in [WHITE, BLACK] {
             for piece_type in [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING] {
                 for square in 0..SQUARES {
-                    keys.piece_keys[color][piece_type][square] = rng.gen();
+                    keys.piece_keys[color][piece_type][square] = rng.next_u64();
                 }
             }
         }
 
         // Generate keys for castling rights
         for i in 0..4 {
-            keys.castling_keys[i] = rng.gen();
+            keys.castling_keys[i] = rng.next_u64();
         }
 
         // Generate keys for en passant possibilities
         for i in 0..8 {
-            keys.en_passant_keys[i] = rng.gen();
+            keys.en_passant_keys[i] = rng.next_u64();
         }
 
         keys
@@ -93,9 +96,14 @@ impl Board {
     /// This hash takes into account:
     /// - The position of each piece
     /// - Castling rights
-    /// - En passant possibilities
+    /// - The en passant file, if the side to move has a pawn that can capture en passant
     /// - The side to move
     ///
+    /// An en passant square that no pawn can capture on does not change what can happen next, so
+    /// it is left out: the position then hashes the same as without it, and the two share
+    /// transposition table entries and count as repetitions of each other. Whether the capture
+    /// would leave the king in check is not considered.
+    ///
     /// # Returns
     ///
     /// A 64-bit hash uniquely representing the current board state.
@@ -126,8 +134,8 @@ impl Board {
             hash ^= ZOBRIST_KEYS.castling_keys[3];
         }
 
-        // Hash en passant square
-        if let Some(ep_square) = self.en_passant {
+        // Hash en passant file, only if the side to move can capture there
+        if let Some(ep_square) = self.en_passant.filter(|_| self.can_capture_en_passant()) {
             let file = ep_square % 8;
             hash ^= ZOBRIST_KEYS.en_passant_keys[file as usize];
         }
@@ -139,6 +147,31 @@ impl Board {
 
         hash
     }
+
+    /// Checks whether the side to move has a pawn next to the pawn that just made a double push,
+    /// so that it can capture en passant.
+    fn can_capture_en_passant(&self) -> bool {
+        let Some(ep_square) = self.en_passant.map(|sq| sq as usize) else {
+            return false;
+        };
+        // The pushed pawn stands one rank beyond the en passant square
+        let (side, pushed) = if self.w_to_move {
+            (WHITE, ep_square.checked_sub(8))
+        } else {
+            (BLACK, Some(ep_square + 8).filter(|&sq| sq < 64))
+        };
+        let Some(pushed) = pushed else {
+            return false;
+        };
+        let mut capturers = 0;
+        if pushed % 8 > 0 {
+            capturers |= 1 << (pushed - 1);
+        }
+        if pushed % 8 < 7 {
+            capturers |= 1 << (pushed + 1);
+        }
+        self.get_piece_bitboard(side, PAWN) & capturers != 0
+    }
 }
 
 impl BoardStack {
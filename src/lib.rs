@@ -1,19 +1,35 @@
+//! Kingfisher chess engine
+//!
+//! # Randomness
+//!
+//! The engine is deterministic. Random numbers come only from the in-tree `rng::SplitMix64`
+//! generator, whose output does not depend on any dependency's version, and are consumed by:
+//!
+//! - the Zobrist key generation in `hash`, seeded with `hash::ZOBRIST_SEED`
+//! - the (offline) magic number search in `magic_bitboard`, seeded with `magic_bitboard::MAGIC_SEED`
+//! - opening sampling for matches in `openings`, seeded by the caller
+
 pub mod agent;
+pub mod api;
 pub mod arena;
 pub mod board;
 pub mod board_utils;
 pub mod boardstack;
 pub mod bits;
+pub mod egtb;
 pub mod eval;
-mod eval_constants;
+pub mod eval_constants;
 pub mod hash;
 pub mod magic_bitboard;
 pub mod magic_constants;
 pub mod make_move;
 pub mod move_generation;
 pub mod move_types;
+pub mod openings;
 pub mod piece_types;
+pub mod rng;
 pub mod search;
+pub mod tactics;
 pub mod transposition;
 pub mod uci;
 pub mod utils;
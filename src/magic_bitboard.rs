@@ -21,6 +21,7 @@
 
 use crate::board_utils::sq_ind_to_bit;
 use crate::bits::bits;
+use crate::rng::SplitMix64;
 use crate::move_types::Move;
 use crate::magic_constants::{R_BITS, B_BITS, R_MASKS, B_MASKS};
 use crate::piece_types::{KNIGHT, BISHOP, ROOK, QUEEN};
@@ -37,9 +38,15 @@ const RANK_2: u64 = 0x000000000000ff00;
 const RANK_7: u64 = 0x00ff000000000000;
 
 
-pub fn find_magic_numbers() -> ([u64; 64], [u64; 64]) {
+/// Seed for regenerating magic numbers with `find_magic_numbers`
+pub const MAGIC_SEED: u64 = 0x6d6167696373;
+
+pub fn find_magic_numbers(seed: u64) -> ([u64; 64], [u64; 64]) {
     // Find magic numbers for magic bitboards.
     // (blockers * magic) >> (64 - n_bits) should give a unique key for each blocker combination.
+    // Candidates are drawn from a generator seeded with `seed`, so the search is reproducible.
+
+    let mut rng = SplitMix64::new(seed);
 
     let mut blockers: u64;
     let mut blocker_squares: Vec<usize> = Vec::new();
@@ -61,7 +68,7 @@ pub fn find_magic_numbers() -> ([u64; 64], [u64; 64]) {
                 blocker_squares.push(i);
             }
             for _i_magic in 0..1000000 {
-                magic = rand::random::<u64>() & rand::random::<u64>() & rand::random::<u64>();
+                magic = rng.next_u64() & rng.next_u64() & rng.next_u64();
                 // Iterate over all possible blocker combinations
                 // Require that they are all unique
                 keys.clear();
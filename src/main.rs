@@ -1,43 +1,17 @@
 //! Main entry point for the Kingfisher chess engine.
 //!
-//! This module sets up the chess engine components and runs a sample game
-//! between two simple agents.
+//! This module runs the UCI engine, or the `bench` command when given on the command line.
 
 extern crate kingfisher;
-use kingfisher::agent::SimpleAgent;
-use kingfisher::arena::Arena;
-use kingfisher::eval::PestoEval;
-use kingfisher::move_generation::MoveGen;
 use kingfisher::uci::UCIEngine;
 
-/// The main function that sets up and runs a sample chess game.
-///
-/// This function initializes the necessary components of the chess engine,
-/// creates two simple agents, and runs a game between them in an arena.
-fn run_simple_game() {
-    // Initialize the move generator
-    let move_gen = MoveGen::new();
-
-    // Initialize the Pesto evaluation function
-    let pesto = PestoEval::new();
-
-    // Create a simple agent for White
-    let white = SimpleAgent::new(3, 6, 99, false, &move_gen, &pesto);
-
-    // Create a simple agent for Black
-    let black = SimpleAgent::new(3, 6, 99, false, &move_gen, &pesto);
-
-    // Create an arena for the game with a maximum of 10 moves
-    let mut arena = Arena::new(&white, &black, 40);
-
-    // Play the game
-    arena.play_game();
-
-    // Print the final board state
-    arena.boardstack.current_state().print();
-}
-
 fn main() {
     let mut engine = UCIEngine::new();
+    // `kingfisher bench [depth]` runs the node-count benchmark and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("bench") {
+        engine.handle_command(&args.join(" "));
+        return;
+    }
     engine.run();
 }
\ No newline at end of file
@@ -4,13 +4,15 @@
 
 use crate::board::Board;
 use crate::board_utils::sq_ind_to_bit;
-use crate::move_types::Move;
+use crate::move_types::{Move, MoveFlag};
 use crate::piece_types::{PAWN, ROOK, KING, WHITE, BLACK};
 
 impl Board {
     /// Makes a move on the board, returning a new board with the move applied
     ///
     /// This method assumes the move is legal and does not perform any legality checks.
+    /// Castling, en passant and double pawn pushes are applied according to the move's flag,
+    /// so moves parsed from text should come from `Board::move_from_uci`.
     ///
     /// # Arguments
     ///
@@ -22,9 +24,13 @@ impl Board {
     pub fn apply_move_to_board(&self, the_move: Move) -> Board {
         // Make a move, returning a new board.
         // Assumes the move is legal.
+        if the_move == Move::null() {
+            return self.apply_null_move_to_board();
+        }
 
         let mut new_board = self.clone();
-        new_board.halfmove_clock += 1;
+        // Reset below on captures and pawn moves (including en passant and promotions)
+        new_board.halfmove_clock = new_board.halfmove_clock.saturating_add(1);
 
         let from_bit = sq_ind_to_bit(the_move.from);
         let to_bit = sq_ind_to_bit(the_move.to);
@@ -45,15 +51,12 @@ impl Board {
             new_board.halfmove_clock = 0;
         }
 
-        if from_piece.unwrap().1 == PAWN {
-            // En passant
-            if new_board.en_passant.is_some() && the_move.to == new_board.en_passant.unwrap() as usize {
-                // Capture the pawn.
-                if new_board.w_to_move {
-                    new_board.pieces[BLACK][PAWN] ^= sq_ind_to_bit(the_move.to - 8);
-                } else {
-                    new_board.pieces[WHITE][PAWN] ^= sq_ind_to_bit(the_move.to + 8);
-                }
+        if the_move.flag == MoveFlag::EnPassant {
+            // Capture the pawn.
+            if new_board.w_to_move {
+                new_board.pieces[BLACK][PAWN] ^= sq_ind_to_bit(the_move.to - 8);
+            } else {
+                new_board.pieces[WHITE][PAWN] ^= sq_ind_to_bit(the_move.to + 8);
             }
         }
         // Reset the en passant rule.
@@ -61,10 +64,10 @@ impl Board {
         if from_piece.unwrap().1 == PAWN {
             // Pawn move: Reset halfmove clock.
             new_board.halfmove_clock = 0;
-            if ((the_move.to as i8) - (the_move.from as i8)).abs() == 16 {
-                // Pawn double move: Set en passant square.
-                new_board.en_passant = Some(((the_move.from + the_move.to) / 2) as u8);
-            }
+        }
+        if the_move.flag == MoveFlag::DoublePawnPush {
+            // Pawn double move: Set en passant square.
+            new_board.en_passant = Some(((the_move.from + the_move.to) / 2) as u8);
         }
 
         // Finally, move the piece.
@@ -82,11 +85,11 @@ impl Board {
         if from_piece.unwrap().1 == KING {
             if from_piece.unwrap().0 == WHITE {
                 // White king
-                if the_move.from == 4 && the_move.to == 6 {
+                if the_move.flag == MoveFlag::KingsideCastle {
                     // White king-side castle
                     new_board.pieces[WHITE][ROOK] ^= sq_ind_to_bit(5);
                     new_board.pieces[WHITE][ROOK] ^= sq_ind_to_bit(7);
-                } else if the_move.from == 4 && the_move.to == 2 {
+                } else if the_move.flag == MoveFlag::QueensideCastle {
                     // White queen-side castle
                     new_board.pieces[WHITE][ROOK] ^= sq_ind_to_bit(3);
                     new_board.pieces[WHITE][ROOK] ^= sq_ind_to_bit(0);
@@ -95,11 +98,11 @@ impl Board {
                 new_board.castling_rights.white_queenside = false;
             } else {
                 // Black king
-                if the_move.from == 60 && the_move.to == 62 {
+                if the_move.flag == MoveFlag::KingsideCastle {
                     // Black king-side castle
                     new_board.pieces[BLACK][ROOK] ^= sq_ind_to_bit(61);
                     new_board.pieces[BLACK][ROOK] ^= sq_ind_to_bit(63);
-                } else if the_move.from == 60 && the_move.to == 58 {
+                } else if the_move.flag == MoveFlag::QueensideCastle {
                     // Black queen-side castle
                     new_board.pieces[BLACK][ROOK] ^= sq_ind_to_bit(59);
                     new_board.pieces[BLACK][ROOK] ^= sq_ind_to_bit(56);
@@ -130,11 +133,6 @@ impl Board {
             new_board.fullmove_number += 1;
         }
 
-        // Null move: remove en passant ability and return new board
-        if the_move.from == 0 && the_move.to == 0 {
-            new_board.en_passant = None;
-            return new_board;
-        }
         for color in 0..2 {
             new_board.pieces_occ[color] = new_board.pieces[color][PAWN];
             for piece in 1..6 {
@@ -147,4 +145,25 @@ impl Board {
 
         new_board
     }
+
+    /// Passes the move to the opponent, returning a new board
+    ///
+    /// The side to move is flipped and the en passant square is cleared, since the opponent's
+    /// double pawn push can no longer be answered. The halfmove clock and fullmove number
+    /// advance as for any other quiet move, and the Zobrist hash is updated to match.
+    ///
+    /// # Returns
+    ///
+    /// A new Bitboard representing the position with the other side to move
+    pub fn apply_null_move_to_board(&self) -> Board {
+        let mut new_board = self.clone();
+        new_board.halfmove_clock = new_board.halfmove_clock.saturating_add(1);
+        new_board.en_passant = None;
+        new_board.w_to_move = !new_board.w_to_move;
+        if new_board.w_to_move {
+            new_board.fullmove_number += 1;
+        }
+        new_board.zobrist_hash = new_board.compute_zobrist_hash();
+        new_board
+    }
 }
@@ -13,16 +13,38 @@
 //! `gen_queen_moves`, and `gen_king_moves` functions generate moves for specific
 //! piece types.
 
-use crate::move_types::Move;
+use lazy_static::lazy_static;
+use crate::move_types::{Move, MoveFlag};
 use crate::board_utils::sq_ind_to_bit;
 use crate::bits::bits;
 use crate::board::Board;
 use crate::magic_constants::{R_MAGICS, B_MAGICS, R_BITS, B_BITS, R_MASKS, B_MASKS};
 use crate::magic_bitboard::{init_pawn_moves, init_knight_moves, init_bishop_moves, init_rook_moves, init_king_moves, init_pawn_captures_promotions, append_promotions};
 
-use crate::eval::PestoEval;
+use crate::eval::Evaluator;
 use crate::piece_types::{PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING, WHITE, BLACK};
 
+/// Precomputed move tables for the sliding pieces, indexed by square and magic key.
+///
+/// These are by far the largest tables used for move generation, so they are built once
+/// and shared by every `MoveGen`.
+struct SliderTables {
+    r_moves: Vec<Vec<(Vec<usize>, Vec<usize>)>>,
+    b_moves: Vec<Vec<(Vec<usize>, Vec<usize>)>>,
+    r_move_bitboard: Vec<Vec<u64>>,
+    b_move_bitboard: Vec<Vec<u64>>,
+}
+
+lazy_static! {
+    static ref SLIDER_TABLES: SliderTables = {
+        // Generate magic numbers for sliding pieces
+        // let (b_magics, r_magics) = find_magic_numbers(MAGIC_SEED);
+        let (b_moves, b_move_bitboard) = init_bishop_moves(B_MAGICS);
+        let (r_moves, r_move_bitboard) = init_rook_moves(R_MAGICS);
+        SliderTables { r_moves, b_moves, r_move_bitboard, b_move_bitboard }
+    };
+}
+
 /// Represents the move generator, which generates pseudo-legal moves.
 ///
 /// This struct contains precomputed tables and bitboards used for move generation.
@@ -51,14 +73,14 @@ pub struct MoveGen {
     wp_moves: Vec<Vec<usize>>,
     /// Precomputed tables for pawn moves.
     bp_moves: Vec<Vec<usize>>,
-    /// Precomputed tables for rook moves.
-    r_moves: Vec<Vec<(Vec<usize>, Vec<usize>)>>,
-    /// Precomputed tables for bishop moves.
-    b_moves: Vec<Vec<(Vec<usize>, Vec<usize>)>>,
-    /// Bitboards for rook moves.
-    r_move_bitboard: Vec<Vec<u64>>,
-    /// Bitboards for bishop moves.
-    b_move_bitboard: Vec<Vec<u64>>,
+    /// Precomputed tables for rook moves (shared).
+    r_moves: &'static Vec<Vec<(Vec<usize>, Vec<usize>)>>,
+    /// Precomputed tables for bishop moves (shared).
+    b_moves: &'static Vec<Vec<(Vec<usize>, Vec<usize>)>>,
+    /// Bitboards for rook moves (shared).
+    r_move_bitboard: &'static Vec<Vec<u64>>,
+    /// Bitboards for bishop moves (shared).
+    b_move_bitboard: &'static Vec<Vec<u64>>,
     /// Magic numbers for bishop moves.
     b_magics: [u64; 64],
     /// Magic numbers for rook moves.
@@ -136,7 +158,7 @@ impl MoveGen {
             wp_moves.push(wp.clone());
             bp_moves.push(bp.clone());
         }
-        let mut move_gen: MoveGen = MoveGen {
+        let move_gen: MoveGen = MoveGen {
             wp_captures,
             bp_captures,
             wp_capture_bitboard,
@@ -149,23 +171,13 @@ impl MoveGen {
             k_move_bitboard,
             wp_moves,
             bp_moves,
-            r_moves: vec![],
-            b_moves: vec![],
-            r_move_bitboard: vec![],
-            b_move_bitboard: vec![],
-            b_magics: [0; 64],
-            r_magics: [0; 64],
+            r_moves: &SLIDER_TABLES.r_moves,
+            b_moves: &SLIDER_TABLES.b_moves,
+            r_move_bitboard: &SLIDER_TABLES.r_move_bitboard,
+            b_move_bitboard: &SLIDER_TABLES.b_move_bitboard,
+            b_magics: B_MAGICS,
+            r_magics: R_MAGICS,
         };
-        // Generate magic numbers for sliding pieces
-        // let (b_magics, r_magics) = find_magic_numbers();
-        move_gen.b_magics = B_MAGICS;
-        move_gen.r_magics = R_MAGICS;
-        let (b_moves, b_move_bitboard) = init_bishop_moves(move_gen.b_magics);
-        move_gen.b_moves = b_moves;
-        move_gen.b_move_bitboard = b_move_bitboard;
-        let (r_moves, r_move_bitboard) = init_rook_moves(move_gen.r_magics);
-        move_gen.r_moves = r_moves;
-        move_gen.r_move_bitboard = r_move_bitboard;
         move_gen
     }
 
@@ -206,7 +218,7 @@ impl MoveGen {
         (captures, moves)
     }
 
-    pub fn gen_pseudo_legal_moves_with_evals(&self, board: &Board, pesto: &PestoEval) -> (Vec<Move>, Vec<Move>) {
+    pub fn gen_pseudo_legal_moves_with_evals<E: Evaluator>(&self, board: &Board, evaluator: &E) -> (Vec<Move>, Vec<Move>) {
         // Generate all pseudo-legal moves for the current position, i.e., these moves may move into check.
         // Elsewhere we need to check for legality and perform move ordering.
         // Returns a vector of captures and a vector of non-captures.
@@ -232,8 +244,8 @@ impl MoveGen {
         // Here let's sort captures by MVV-LVA
         captures.sort_unstable_by_key(|m| -self.mvv_lva(board, m.from, m.to));
 
-        // Also sort moves by pesto eval change
-        moves.sort_unstable_by_key(|m| -pesto.move_eval(board, self, m.from, m.to));
+        // Also sort moves by the evaluator's move ordering scores
+        moves.sort_unstable_by_key(|m| -evaluator.move_eval(board, self, m.from, m.to));
 
         (captures, moves)
     }
@@ -270,6 +282,23 @@ impl MoveGen {
         captures
     }
 
+    /// Generates the pseudo-legal moves that give check, directly or by discovery.
+    ///
+    /// Each move is tested with `Board::gives_check`, which works from the attack tables
+    /// without making the move.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The current chess position.
+    ///
+    /// # Returns
+    ///
+    /// A vector of checking moves, captures and promotions first.
+    pub fn gen_checking_moves(&self, board: &Board) -> Vec<Move> {
+        let (captures, moves) = self.gen_pseudo_legal_moves(board);
+        captures.into_iter().chain(moves).filter(|m| board.gives_check(m, self)).collect()
+    }
+
     pub fn mvv_lva(&self, board: &Board, from_sq_ind: usize, to_sq_ind: usize) -> i32 {
         // Return the MVV-LVA score for a capture move.
         // To enable sorting by MVV, then by LVA, we return the score as 10 * victim - attacker,
@@ -307,12 +336,14 @@ impl MoveGen {
 
                 // Handle captures and en passant
                 for to_sq_ind in &self.wp_captures[from_sq_ind] {
-                    if board.pieces_occ[BLACK] & (1u64 << to_sq_ind) != 0 || board.en_passant == Some(*to_sq_ind as u8) {
+                    if board.pieces_occ[BLACK] & (1u64 << to_sq_ind) != 0 {
                         if is_promotion_rank {
                             append_promotions(&mut promotions, from_sq_ind, to_sq_ind, board.w_to_move);
                         } else {
                             captures.push(Move::new(from_sq_ind, *to_sq_ind, None));
                         }
+                    } else if board.en_passant == Some(*to_sq_ind as u8) {
+                        captures.push(Move::new_with_flag(from_sq_ind, *to_sq_ind, MoveFlag::EnPassant));
                     }
                 }
 
@@ -324,7 +355,11 @@ impl MoveGen {
                         } else if from_sq_ind > 7 && from_sq_ind < 16 {
                             // Double pawn push
                             if (board.pieces_occ[BLACK] + board.pieces_occ[WHITE]) & (1u64 << (from_sq_ind + 8)) == 0 {
-                                moves.push(Move::new(from_sq_ind, *to_sq_ind, None));
+                                if *to_sq_ind == from_sq_ind + 16 {
+                                    moves.push(Move::new_with_flag(from_sq_ind, *to_sq_ind, MoveFlag::DoublePawnPush));
+                                } else {
+                                    moves.push(Move::new(from_sq_ind, *to_sq_ind, None));
+                                }
                             }
                         } else {
                             moves.push(Move::new(from_sq_ind, *to_sq_ind, None));
@@ -339,12 +374,14 @@ impl MoveGen {
 
                 // Handle captures and en passant
                 for to_sq_ind in &self.bp_captures[from_sq_ind] {
-                    if board.pieces_occ[WHITE] & (1u64 << to_sq_ind) != 0 || board.en_passant == Some(*to_sq_ind as u8) {
+                    if board.pieces_occ[WHITE] & (1u64 << to_sq_ind) != 0 {
                         if is_promotion_rank {
                             append_promotions(&mut promotions, from_sq_ind, to_sq_ind, board.w_to_move);
                         } else {
                             captures.push(Move::new(from_sq_ind, *to_sq_ind, None));
                         }
+                    } else if board.en_passant == Some(*to_sq_ind as u8) {
+                        captures.push(Move::new_with_flag(from_sq_ind, *to_sq_ind, MoveFlag::EnPassant));
                     }
                 }
 
@@ -356,7 +393,11 @@ impl MoveGen {
                         } else if from_sq_ind > 47 && from_sq_ind < 56 {
                             // Double pawn push
                             if (board.pieces_occ[WHITE] + board.pieces_occ[BLACK]) & (1u64 << (from_sq_ind - 8)) == 0 {
-                                moves.push(Move::new(from_sq_ind, *to_sq_ind, None));
+                                if *to_sq_ind + 16 == from_sq_ind {
+                                    moves.push(Move::new_with_flag(from_sq_ind, *to_sq_ind, MoveFlag::DoublePawnPush));
+                                } else {
+                                    moves.push(Move::new(from_sq_ind, *to_sq_ind, None));
+                                }
                             }
                         } else {
                             moves.push(Move::new(from_sq_ind, *to_sq_ind, None));
@@ -439,7 +480,7 @@ impl MoveGen {
                     !board.is_square_attacked(4, false, self) &&
                     !board.is_square_attacked(5, false, self) &&
                     !board.is_square_attacked(6, false, self) {
-                    moves.push(Move::new(4, 6, None));
+                    moves.push(Move::new_with_flag(4, 6, MoveFlag::KingsideCastle));
                 }
             }
             if board.castling_rights.white_queenside {
@@ -449,7 +490,7 @@ impl MoveGen {
                     !board.is_square_attacked(4, false, self) &&
                     !board.is_square_attacked(3, false, self) &&
                     !board.is_square_attacked(2, false, self) {
-                    moves.push(Move::new(4, 2, None));
+                    moves.push(Move::new_with_flag(4, 2, MoveFlag::QueensideCastle));
                 }
             }
             for from_sq_ind in bits(&board.pieces[WHITE][KING]) {
@@ -470,7 +511,7 @@ impl MoveGen {
                     !board.is_square_attacked(60, true, self) &&
                     !board.is_square_attacked(61, true, self) &&
                     !board.is_square_attacked(62, true, self) {
-                    moves.push(Move::new(60, 62, None));
+                    moves.push(Move::new_with_flag(60, 62, MoveFlag::KingsideCastle));
                 }
             }
             if board.castling_rights.black_queenside {
@@ -480,7 +521,7 @@ impl MoveGen {
                     !board.is_square_attacked(60, true, self) &&
                     !board.is_square_attacked(59, true, self) &&
                     !board.is_square_attacked(58, true, self) {
-                    moves.push(Move::new(60, 58, None));
+                    moves.push(Move::new_with_flag(60, 58, MoveFlag::QueensideCastle));
                 }
             }
             for from_sq_ind in bits(&board.pieces[BLACK][KING]) {
@@ -567,9 +608,30 @@ impl MoveGen {
     pub fn gen_bishop_potential_captures(&self, board: &Board, from_sq_ind: usize) -> u64 {
         // Generate potential bishop captures from the given square.
         // Used to determine whether a king is in check.
+        self.bishop_attacks(from_sq_ind, board.pieces_occ[BLACK] | board.pieces_occ[WHITE])
+    }
+
+    pub fn gen_rook_potential_captures(&self, board: &Board, from_sq_ind: usize) -> u64 {
+        // Generate potential rook captures from the given square.
+        // Used to determine whether a king is in check.
+        self.rook_attacks(from_sq_ind, board.pieces_occ[BLACK] | board.pieces_occ[WHITE])
+    }
 
+    /// Looks up the squares a bishop attacks for an arbitrary occupancy.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_sq_ind` - The square index (0-63) of the bishop.
+    /// * `occupancy` - A bitboard of all occupied squares.
+    ///
+    /// # Returns
+    ///
+    /// A bitboard of only the first blocker in each direction, or the edge square if there is
+    /// none, which is enough to test for attacks on occupied squares. Use `bishop_reach` for
+    /// every attacked square.
+    pub fn bishop_attacks(&self, from_sq_ind: usize, occupancy: u64) -> u64 {
         // Mask blockers
-        let blockers: u64 = (board.pieces_occ[BLACK] | board.pieces_occ[WHITE]) & B_MASKS[from_sq_ind];
+        let blockers: u64 = occupancy & B_MASKS[from_sq_ind];
 
         // Generate the key using a multiplication and right shift
         let key: usize = ((blockers.wrapping_mul(self.b_magics[from_sq_ind])) >> (64 - B_BITS[from_sq_ind])) as usize;
@@ -578,12 +640,21 @@ impl MoveGen {
         self.b_move_bitboard[from_sq_ind][key]
     }
 
-    pub fn gen_rook_potential_captures(&self, board: &Board, from_sq_ind: usize) -> u64 {
-        // Generate potential rook captures from the given square.
-        // Used to determine whether a king is in check.
-
+    /// Looks up the squares a rook attacks for an arbitrary occupancy.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_sq_ind` - The square index (0-63) of the rook.
+    /// * `occupancy` - A bitboard of all occupied squares.
+    ///
+    /// # Returns
+    ///
+    /// A bitboard of only the first blocker in each direction, or the edge square if there is
+    /// none, which is enough to test for attacks on occupied squares. Use `rook_reach` for
+    /// every attacked square.
+    pub fn rook_attacks(&self, from_sq_ind: usize, occupancy: u64) -> u64 {
         // Mask blockers
-        let blockers: u64 = (board.pieces_occ[BLACK] | board.pieces_occ[WHITE]) & R_MASKS[from_sq_ind];
+        let blockers: u64 = occupancy & R_MASKS[from_sq_ind];
 
         // Generate the key using a multiplication and right shift
         let key: usize = ((blockers.wrapping_mul(self.r_magics[from_sq_ind])) >> (64 - R_BITS[from_sq_ind])) as usize;
@@ -592,6 +663,46 @@ impl MoveGen {
         self.r_move_bitboard[from_sq_ind][key]
     }
 
+    /// Looks up every square a bishop can move to or capture on for an arbitrary occupancy.
+    ///
+    /// Unlike `bishop_attacks`, whose table only keeps the blockers and the edge squares, this
+    /// includes the empty squares along each diagonal, so it is suited to counting mobility.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_sq_ind` - The square index (0-63) of the bishop.
+    /// * `occupancy` - A bitboard of all occupied squares.
+    ///
+    /// # Returns
+    ///
+    /// A bitboard of the reachable squares, including the first blocker in each direction.
+    pub fn bishop_reach(&self, from_sq_ind: usize, occupancy: u64) -> u64 {
+        let blockers: u64 = occupancy & B_MASKS[from_sq_ind];
+        let key: usize = ((blockers.wrapping_mul(self.b_magics[from_sq_ind])) >> (64 - B_BITS[from_sq_ind])) as usize;
+        let (captures, moves) = &self.b_moves[from_sq_ind][key];
+        captures.iter().chain(moves).fold(0, |reach, &sq| reach | (1u64 << sq))
+    }
+
+    /// Looks up every square a rook can move to or capture on for an arbitrary occupancy.
+    ///
+    /// Unlike `rook_attacks`, whose table only keeps the blockers and the edge squares, this
+    /// includes the empty squares along each line, so it is suited to counting mobility.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_sq_ind` - The square index (0-63) of the rook.
+    /// * `occupancy` - A bitboard of all occupied squares.
+    ///
+    /// # Returns
+    ///
+    /// A bitboard of the reachable squares, including the first blocker in each direction.
+    pub fn rook_reach(&self, from_sq_ind: usize, occupancy: u64) -> u64 {
+        let blockers: u64 = occupancy & R_MASKS[from_sq_ind];
+        let key: usize = ((blockers.wrapping_mul(self.r_magics[from_sq_ind])) >> (64 - R_BITS[from_sq_ind])) as usize;
+        let (captures, moves) = &self.r_moves[from_sq_ind][key];
+        captures.iter().chain(moves).fold(0, |reach, &sq| reach | (1u64 << sq))
+    }
+
     /// Generates moves for a bishop on a specific square.
     ///
     /// This function generates moves for a bishop on the given square, including
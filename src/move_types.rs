@@ -4,13 +4,34 @@
 //! to represent and manipulate chess moves.
 
 use std::fmt;
+use crate::board::Board;
 use crate::board_utils::sq_ind_to_algebraic;
 use crate::piece_types::{KNIGHT, BISHOP, ROOK, QUEEN};
 
+/// Describes how a move is applied to the board beyond moving a single piece.
+///
+/// The flag is set by the move generator, so that making a move never has to
+/// re-derive whether it was castling or en passant from the squares involved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MoveFlag {
+    /// A regular move or capture.
+    Normal,
+    /// A pawn advancing two squares, which sets the en passant square.
+    DoublePawnPush,
+    /// An en passant capture; the captured pawn is not on the destination square.
+    EnPassant,
+    /// King-side castling; the rook moves along with the king.
+    KingsideCastle,
+    /// Queen-side castling; the rook moves along with the king.
+    QueensideCastle,
+    /// A pawn promotion; the new piece is stored in `Move::promotion`.
+    Promotion,
+}
+
 /// Represents a chess move.
 ///
 /// This struct contains information about the source square, destination square,
-/// and any promotion that occurs as a result of the move.
+/// any promotion that occurs as a result of the move, and a flag for special moves.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Move {
     /// The index of the square the piece is moving from (0-63).
@@ -19,7 +40,9 @@ pub struct Move {
     pub to: usize,
     /// The type of piece to promote to, if this move results in a promotion.
     /// `None` if the move does not result in a promotion.
-    pub promotion: Option<usize>
+    pub promotion: Option<usize>,
+    /// The kind of move (normal, double pawn push, en passant, castling or promotion).
+    pub flag: MoveFlag
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -52,13 +75,36 @@ impl Move {
     ///
     /// # Returns
     ///
-    /// A new `Move` instance with the specified parameters.
+    /// A new `Move` instance with the specified parameters. The flag is `Promotion` if a
+    /// promotion piece is given and `Normal` otherwise.
     pub fn new(from: usize, to: usize, promotion: Option<usize>) -> Move {
         // New move
+        let flag = if promotion.is_some() { MoveFlag::Promotion } else { MoveFlag::Normal };
+        Move {
+            from,
+            to,
+            promotion,
+            flag
+        }
+    }
+
+    /// Creates a new non-promotion `Move` instance with an explicit flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The index of the source square (0-63).
+    /// * `to` - The index of the destination square (0-63).
+    /// * `flag` - The kind of move, e.g. `MoveFlag::EnPassant` or `MoveFlag::KingsideCastle`.
+    ///
+    /// # Returns
+    ///
+    /// A new `Move` instance with the specified parameters.
+    pub fn new_with_flag(from: usize, to: usize, flag: MoveFlag) -> Move {
         Move {
             from,
             to,
-            promotion
+            promotion: None,
+            flag
         }
     }
 
@@ -70,7 +116,17 @@ impl Move {
     ///
     /// # Returns
     ///
-    /// An `Option<Move>` which is `Some(Move)` if the UCI string is valid, or `None` if it's invalid.
+    /// An `Option<Move>` which is `Some(Move)` if the UCI string is valid, or `None` if it's invalid:
+    /// not four or five characters, a square off the board, the same square twice, or a promotion
+    /// suffix that is not `n`, `b`, `r` or `q` or is not on a pawn step onto the last rank.
+    ///
+    /// # Note
+    ///
+    /// The string alone does not say whether a move is castling, en passant or a double pawn push,
+    /// so the returned move is flagged `Normal` (or `Promotion`). Moves compare equal only if their
+    /// flags match too, so `Move::from_uci("e1g1")` is not equal to the castling move generated for
+    /// the same squares. Use `Board::move_from_uci` to parse a move that is to be made on a
+    /// particular board, or compared with generated moves.
     ///
     /// # Examples
     ///
@@ -81,14 +137,15 @@ impl Move {
     /// assert!(Move::from_uci("invalid").is_none());
     /// ```
     pub fn from_uci(uci: &str) -> Option<Move> {
-        if uci.len() < 4 || uci.len() > 5 {
+        let bytes = uci.as_bytes();
+        if !uci.is_ascii() || bytes.len() < 4 || bytes.len() > 5 {
             return None;
         }
 
-        let from_file = (uci.chars().nth(0)? as u8).wrapping_sub(b'a');
-        let from_rank = (uci.chars().nth(1)? as u8).wrapping_sub(b'1');
-        let to_file = (uci.chars().nth(2)? as u8).wrapping_sub(b'a');
-        let to_rank = (uci.chars().nth(3)? as u8).wrapping_sub(b'1');
+        let from_file = bytes[0].wrapping_sub(b'a');
+        let from_rank = bytes[1].wrapping_sub(b'1');
+        let to_file = bytes[2].wrapping_sub(b'a');
+        let to_rank = bytes[3].wrapping_sub(b'1');
 
         if from_file > 7 || from_rank > 7 || to_file > 7 || to_rank > 7 {
             return None;
@@ -96,24 +153,28 @@ impl Move {
 
         let from: usize = (from_rank * 8 + from_file) as usize;
         let to: usize = (to_rank * 8 + to_file) as usize;
+        if from == to {
+            return None;
+        }
 
-        let promotion = if uci.len() == 5 {
-            // Make sure the promotion is on the last rank
-            if to_rank != 7 && to_rank != 0 {
+        let promotion = if bytes.len() == 5 {
+            // Make sure this is a pawn step onto the last rank
+            let onto_last_rank = (from_rank == 6 && to_rank == 7) || (from_rank == 1 && to_rank == 0);
+            if !onto_last_rank || from_file.abs_diff(to_file) > 1 {
                 return None;
             }
-            match uci.chars().nth(4)? {
-                'n' => Some(KNIGHT),
-                'b' => Some(BISHOP),
-                'r' => Some(ROOK),
-                'q' => Some(QUEEN),
+            match bytes[4] {
+                b'n' => Some(KNIGHT),
+                b'b' => Some(BISHOP),
+                b'r' => Some(ROOK),
+                b'q' => Some(QUEEN),
                 _ => return None,
             }
         } else {
             None
         };
 
-        Some(Move { from, to, promotion })
+        Some(Move::new(from, to, promotion))
     }
 
     /// Creates a null move.
@@ -124,23 +185,69 @@ impl Move {
     ///
     /// # Returns
     ///
-    /// A `Move` instance representing a null move, with `from` and `to` set to 0
-    /// and `promotion` set to `None`.
+    /// A `Move` instance representing a null move, with `from` and `to` set to 0,
+    /// `promotion` set to `None` and a `Normal` flag.
     pub fn null() -> Move {
         // Null move
         Move {
             from: 0,
             to: 0,
-            promotion: None
+            promotion: None,
+            flag: MoveFlag::Normal
         }
     }
 
+    /// Returns true if the move captures a piece in the given position.
+    ///
+    /// An en passant capture counts as a capture even though its destination square is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The position the move is made from.
+    pub fn is_capture(&self, board: &Board) -> bool {
+        self.flag == MoveFlag::EnPassant || board.get_piece(self.to).is_some()
+    }
+
+    /// Returns true if the move is neither a capture nor a promotion in the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The position the move is made from.
+    pub fn is_quiet(&self, board: &Board) -> bool {
+        !self.is_promotion() && !self.is_capture(board)
+    }
+
+    /// Returns true if the move promotes a pawn.
+    pub fn is_promotion(&self) -> bool {
+        self.promotion.is_some()
+    }
+
+    /// Returns true if the move is king-side or queen-side castling.
+    pub fn is_castle(&self) -> bool {
+        matches!(self.flag, MoveFlag::KingsideCastle | MoveFlag::QueensideCastle)
+    }
+
+    /// Writes the move in UCI notation, e.g. "e2e4" or "e7e8q", and "0000" for the null move.
+    ///
+    /// The promotion piece is written as a lowercase letter for either color, so that
+    /// `Move::from_uci` reads the same move back.
+    pub fn to_uci(&self) -> String {
+        if *self == Move::null() {
+            return String::from("0000");
+        }
+        let promotion = match self.promotion {
+            Some(piece) => ['p', 'n', 'b', 'r', 'q', 'k'][piece].to_string(),
+            None => String::new(),
+        };
+        format!("{}{}{}", sq_ind_to_algebraic(self.from), sq_ind_to_algebraic(self.to), promotion)
+    }
+
     /// Change the way a move is printed so that it uses algebraic notation
     pub fn print_algebraic(&self) -> String {
         let from = sq_ind_to_algebraic(self.from);
         let to = sq_ind_to_algebraic(self.to);
         let mut promotion = String::from("");
-        if self.promotion.is_some() {
+        if self.is_promotion() {
             promotion = String::from("=");
             match self.promotion.unwrap() {
                 n if n == KNIGHT => promotion.push('N'),
@@ -166,12 +273,68 @@ mod tests {
 
     #[test]
     fn test_move_from_uci() {
-        assert_eq!(Move::from_uci("e2e4"), Some(Move { from: 12, to: 28, promotion: None }));
-        assert_eq!(Move::from_uci("a7a8q"), Some(Move { from: 48, to: 56, promotion: Some(QUEEN) }));
-        assert_eq!(Move::from_uci("h2h1n"), Some(Move { from: 15, to: 7, promotion: Some(KNIGHT) }));
-        assert_eq!(Move::from_uci("e1g1"), Some(Move { from: 4, to: 6, promotion: None })); // Castling
+        assert_eq!(Move::from_uci("e2e4"), Some(Move { from: 12, to: 28, promotion: None, flag: MoveFlag::Normal }));
+        assert_eq!(Move::from_uci("a7a8q"), Some(Move { from: 48, to: 56, promotion: Some(QUEEN), flag: MoveFlag::Promotion }));
+        assert_eq!(Move::from_uci("h2h1n"), Some(Move { from: 15, to: 7, promotion: Some(KNIGHT), flag: MoveFlag::Promotion }));
+        assert_eq!(Move::from_uci("e1g1"), Some(Move { from: 4, to: 6, promotion: None, flag: MoveFlag::Normal })); // Castling needs board context
         assert_eq!(Move::from_uci("invalid"), None);
         assert_eq!(Move::from_uci("e2e9"), None); // Invalid square
         assert_eq!(Move::from_uci("e2e4q"), None); // Invalid promotion (not on last rank)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_move_from_uci_rejects_malformed_input() {
+        // Wrong length
+        for uci in ["", "e2", "e2e", "e2e4qq"] {
+            assert_eq!(Move::from_uci(uci), None, "{}", uci);
+        }
+        // Squares off the board
+        for uci in ["z9z9", "i2i4", "e0e1", "E2E4", "e2-4"] {
+            assert_eq!(Move::from_uci(uci), None, "{}", uci);
+        }
+        // Not a move, or not UCI
+        assert_eq!(Move::from_uci("e2e2"), None);
+        assert_eq!(Move::from_uci("0000"), None);
+        assert_eq!(Move::from_uci("é2e4"), None);
+        // Invalid promotion suffixes
+        for uci in ["e7e8k", "e7e8p", "e7e8Q", "e7e8x", "e3e1q", "e6e8q", "a7c8q", "e8e7q"] {
+            assert_eq!(Move::from_uci(uci), None, "{}", uci);
+        }
+        // Valid promotions, including captures and black promotions
+        assert_eq!(Move::from_uci("e7e8q"), Some(Move::new(52, 60, Some(QUEEN))));
+        assert_eq!(Move::from_uci("b7a8r"), Some(Move::new(49, 56, Some(ROOK))));
+        assert_eq!(Move::from_uci("d2c1b"), Some(Move::new(11, 2, Some(BISHOP))));
+    }
+
+    #[test]
+    fn test_move_type_predicates() {
+        // En passant: the destination square is empty before the move
+        let board = Board::new_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let en_passant = board.move_from_uci("e5d6").unwrap();
+        assert_eq!(en_passant.flag, MoveFlag::EnPassant);
+        assert!(board.get_piece(en_passant.to).is_none());
+        assert!(en_passant.is_capture(&board));
+        assert!(!en_passant.is_quiet(&board));
+        assert!(!en_passant.is_promotion());
+        let push = board.move_from_uci("e5e6").unwrap();
+        assert!(!push.is_capture(&board));
+        assert!(push.is_quiet(&board));
+
+        // Promotions, with and without a capture
+        let board = Board::new_from_fen("1r2k3/P7/8/8/8/8/8/4K2R w K - 0 1");
+        let promotion = board.move_from_uci("a7a8n").unwrap();
+        assert!(promotion.is_promotion());
+        assert!(!promotion.is_capture(&board));
+        assert!(!promotion.is_quiet(&board));
+        let capture_promotion = board.move_from_uci("a7b8q").unwrap();
+        assert!(capture_promotion.is_promotion());
+        assert!(capture_promotion.is_capture(&board));
+
+        // Castling is a quiet move
+        let castle = board.move_from_uci("e1g1").unwrap();
+        assert!(castle.is_castle());
+        assert!(castle.is_quiet(&board));
+        assert!(!board.move_from_uci("h1h8").unwrap().is_castle());
+        assert!(board.move_from_uci("h1h8").unwrap().is_quiet(&board));
+    }
+}
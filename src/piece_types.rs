@@ -8,4 +8,4 @@ pub const KING: usize = 5;
 
 /// Represents the color of a chess piece.
 pub const WHITE: usize = 0;
-pub const BLACK: usize = 1;
\ No newline at end of file
+pub const BLACK: usize = 1;
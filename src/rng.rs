@@ -0,0 +1,44 @@
+//! Seedable random number generation
+//!
+//! The engine's random numbers come from the SplitMix64 generator implemented here rather than
+//! from `rand`, whose generators may change their output between versions. Since the Zobrist keys
+//! are drawn from it, every position hash is fixed by this file alone.
+
+/// The SplitMix64 pseudo-random number generator
+///
+/// Small and fast, and good enough for Zobrist keys, magic number candidates and sampling. Each
+/// seed gives a different, fully reproducible stream.
+#[derive(Clone, Debug)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a generator from a seed
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed; any value is fine
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    /// Returns the next pseudo-random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo-random index in `0..n`
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of indices to choose from, which must be positive
+    pub fn next_index(&mut self, n: usize) -> usize {
+        assert!(n > 0, "Cannot choose an index from an empty range");
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
@@ -2,13 +2,565 @@
 //!
 //! This module implements the negamax search algorithm for chess position evaluation.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
+use crate::bits::bits;
+use crate::board::Board;
+use crate::piece_types::{PAWN, KING, WHITE, BLACK};
+use crate::eval_constants::PIECE_VALUES;
 use crate::boardstack::BoardStack;
-use crate::move_types::Move;
+use crate::move_types::{Move, MoveFlag};
 use crate::move_generation::MoveGen;
-use crate::eval::PestoEval;
+use crate::eval::Evaluator;
 use crate::utils::print_move;
 use crate::transposition::TranspositionTable;
+use lazy_static::lazy_static;
+
+/// Futility pruning margins (in centipawns), indexed by remaining depth
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 500];
+
+/// Razoring margin (in centipawns) at frontier nodes
+const RAZORING_MARGIN: i32 = 300;
+
+/// Reverse futility pruning margin (in centipawns) per ply of remaining depth
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+
+/// Maximum remaining depth at which reverse futility pruning is applied
+const REVERSE_FUTILITY_MAX_DEPTH: i32 = 3;
+
+/// Minimum remaining depth at which late move reductions are applied
+const LMR_MIN_DEPTH: i32 = 3;
+
+/// Number of moves searched at full depth before late move reductions start
+const LMR_MIN_MOVES: i32 = 3;
+
+/// Base reduction of the late move reduction table, in plies
+pub const LMR_BASE: f64 = 0.75;
+
+/// Divisor of the late move reduction table; larger values reduce less
+pub const LMR_DIVISOR: f64 = 2.25;
+
+/// Hard cap on the depth of quiescence search, whatever maximum depth the caller asks for
+const Q_SEARCH_MAX_DEPTH: i32 = 32;
+
+/// Default maximum depth of quiescence search for a `SearchContext`
+pub const DEFAULT_Q_SEARCH_DEPTH: i32 = 4;
+
+/// Number of nodes between checks of the time limit
+const TIME_CHECK_INTERVAL: u64 = 512;
+
+/// The score for delivering checkmate at the root; a mate `n` plies from the root scores `MATE_SCORE - n`
+pub const MATE_SCORE: i32 = 1000000;
+
+/// Scores beyond this magnitude are treated as mate scores, which are never pruned
+pub const MATE_THRESHOLD: i32 = 900000;
+
+lazy_static! {
+    /// The late move reduction table used by the search
+    static ref LMR_TABLE: ReductionTable = ReductionTable::new(LMR_BASE, LMR_DIVISOR);
+}
+
+/// Precomputed late move reductions, indexed by remaining depth and move number
+///
+/// The reduction grows with the logarithm of both the depth and the move number:
+/// `base + ln(depth) * ln(move_number) / divisor`, rounded down.
+pub struct ReductionTable {
+    reductions: [[i32; 64]; 64],
+}
+
+impl ReductionTable {
+    /// Builds a reduction table.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The reduction added to every entry, in plies
+    /// * `divisor` - The divisor of the logarithmic term; larger values reduce less
+    ///
+    /// # Returns
+    ///
+    /// The reduction table.
+    pub fn new(base: f64, divisor: f64) -> Self {
+        let mut reductions = [[0; 64]; 64];
+        for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+            for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+                *reduction = (base + (depth as f64).ln() * (move_number as f64).ln() / divisor) as i32;
+            }
+        }
+        ReductionTable { reductions }
+    }
+
+    /// Looks up the reduction for a move.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The remaining depth of the node
+    /// * `move_number` - The number of the move in the move ordering, starting from 1
+    ///
+    /// # Returns
+    ///
+    /// The reduction in plies, before any adjustment for the type of node.
+    pub fn reduction(&self, depth: i32, move_number: i32) -> i32 {
+        self.reductions[depth.clamp(0, 63) as usize][move_number.clamp(0, 63) as usize]
+    }
+}
+
+/// Formats a search score for the UCI `info` command.
+///
+/// # Arguments
+///
+/// * `score` - The score from the point of view of the side to move at the root
+///
+/// # Returns
+///
+/// `mate N` for mate scores, where N is the number of moves to mate (negative if the side to
+/// move is getting mated), otherwise `cp` followed by the score in centipawns.
+pub fn format_uci_score(score: i32) -> String {
+    if score.abs() > MATE_THRESHOLD {
+        let plies = MATE_SCORE - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Reads the principal variation from the transposition table.
+///
+/// Starting from the current position, follows the best move stored for each position until
+/// a position is missing from the table, has no best move, or the line reaches `max_len`
+/// moves. The board is returned to the current position afterwards.
+///
+/// # Arguments
+///
+/// * `board` - A mutable reference to the current board state
+/// * `move_gen` - A reference to the move generator
+/// * `tt` - The transposition table filled by a search from this position
+/// * `max_len` - The maximum number of moves in the line
+///
+/// # Returns
+///
+/// The moves of the principal variation, starting with the best move in the current position.
+pub fn principal_variation(board: &mut BoardStack, move_gen: &MoveGen, tt: &TranspositionTable, max_len: usize) -> Vec<Move> {
+    let mut pv = Vec::new();
+    while pv.len() < max_len {
+        let m = match tt.probe(board.current_state(), i32::MIN) {
+            Some(entry) if entry.best_move != Move::null() => entry.best_move,
+            _ => break,
+        };
+        board.make_move(m);
+        if !board.current_state().is_legal(move_gen) {
+            board.undo_move();
+            break;
+        }
+        pv.push(m);
+    }
+    board.undo_moves(pv.len());
+    pv
+}
+
+/// The progress of a search after a completed depth, as reported to the search's info sink
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchInfo {
+    /// The depth just completed
+    pub depth: i32,
+    /// The greatest depth reached by any line, including quiescence search
+    pub seldepth: usize,
+    /// The score from the point of view of the side to move at the root
+    pub score: i32,
+    /// The number of nodes searched so far
+    pub nodes: u64,
+    /// The number of nodes searched per second
+    pub nps: u64,
+    /// The permille of the transposition table in use
+    pub hashfull: usize,
+    /// The time since the search started
+    pub time: Duration,
+    /// The principal variation, starting with the best move
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    /// Formats the progress as a UCI `info` line
+    pub fn to_uci(&self) -> String {
+        let pv: Vec<String> = self.pv.iter().map(|m| m.to_uci()).collect();
+        format!("info depth {} seldepth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+                self.depth, self.seldepth, format_uci_score(self.score), self.nodes, self.nps,
+                self.hashfull, self.time.as_millis(), pv.join(" "))
+    }
+}
+
+/// Static exchange evaluation (SEE) of a move
+///
+/// Plays out the sequence of captures on the destination square, with each side recapturing with
+/// its least valuable attacker and stopping as soon as recapturing would lose material. Pieces
+/// behind a capturing slider join the exchange once it has moved. For en passant, the captured
+/// pawn is removed from its own square rather than the destination square.
+///
+/// # Arguments
+///
+/// * `board` - The position before the move
+/// * `move_gen` - A reference to the move generator
+/// * `mv` - A legal move in the position, which need not be a capture
+///
+/// # Returns
+///
+/// The expected material gain of the move in centipawns (using `PIECE_VALUES`), from the point
+/// of view of the side making it.
+pub fn see(board: &Board, move_gen: &MoveGen, mv: Move) -> i32 {
+    see_with_values(board, move_gen, mv, &PIECE_VALUES)
+}
+
+/// Static exchange evaluation (SEE) of a move, with the given piece values
+///
+/// The same as `see`, for tuning the piece values. Passing anything other than `PIECE_VALUES`
+/// makes SEE disagree with the material counted elsewhere, e.g. by `MaterialEval`.
+///
+/// # Arguments
+///
+/// * `board` - The position before the move
+/// * `move_gen` - A reference to the move generator
+/// * `mv` - A legal move in the position, which need not be a capture
+/// * `piece_values` - The value of each piece type in centipawns, indexed by piece type
+///
+/// # Returns
+///
+/// The expected material gain of the move in centipawns, from the point of view of the side
+/// making it.
+pub fn see_with_values(board: &Board, move_gen: &MoveGen, mv: Move, piece_values: &[i32; 6]) -> i32 {
+    let Some((us, moving_piece)) = board.get_piece(mv.from) else {
+        return 0;
+    };
+    let mut scratch = board.clone();
+
+    // Material won by the move itself
+    let mut gain = [0; 32];
+    if mv.flag == MoveFlag::EnPassant {
+        scratch.clear_square(if us == WHITE { mv.to - 8 } else { mv.to + 8 });
+        gain[0] = piece_values[PAWN];
+    } else if let Some((_, captured)) = board.get_piece(mv.to) {
+        gain[0] = piece_values[captured];
+    }
+    let mut on_square = moving_piece;
+    if let Some(promotion) = mv.promotion {
+        on_square = promotion;
+        gain[0] += piece_values[promotion] - piece_values[PAWN];
+    }
+    scratch.clear_square(mv.from);
+    scratch.set_square(mv.to, us, on_square);
+
+    // Alternate captures with the least valuable attacker
+    let mut side = 1 - us;
+    let mut depth = 0;
+    while depth + 1 < gain.len() {
+        let attackers = scratch.attackers_to(mv.to, side == WHITE, move_gen);
+        let Some(piece) = (PAWN..=KING).find(|&piece| attackers & scratch.pieces[side][piece] != 0) else {
+            break;
+        };
+        let from = (attackers & scratch.pieces[side][piece]).trailing_zeros() as usize;
+        // The king may only capture an undefended piece
+        if piece == KING && scratch.attackers_to(mv.to, side != WHITE, move_gen) != 0 {
+            break;
+        }
+        depth += 1;
+        gain[depth] = piece_values[on_square] - gain[depth - 1];
+        scratch.clear_square(from);
+        scratch.set_square(mv.to, side, piece);
+        on_square = piece;
+        side = 1 - side;
+    }
+
+    // Each side may stop capturing when continuing would lose material
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+    gain[0]
+}
+
+/// Which moves quiescence search considers in quiet positions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuiescenceMode {
+    /// Only captures and promotions
+    CapturesOnly,
+    /// Captures and promotions, plus quiet checking moves at the first ply
+    CapturesAndChecks,
+}
+
+/// Receives the progress of a search, e.g. to print it or show it in a GUI
+pub type InfoSink = Box<dyn Fn(&SearchInfo)>;
+
+/// The limits of a search and the flag that stops it, shared by the whole tree
+///
+/// Every node of the tree counts itself here, and the clock is read every `TIME_CHECK_INTERVAL`
+/// nodes, so a limit is noticed promptly however deep the current subtree is. The search is
+/// stopped by setting the stop flag, either when a limit is reached or from another thread
+/// (e.g. on the UCI `stop` command). Every node checks the flag, and once it is set the whole
+/// tree unwinds without storing any results.
+pub struct SearchLimits {
+    /// The time at which the search started
+    start_time: Instant,
+    /// The time limit for the search, if any
+    time_limit: Option<Duration>,
+    /// The maximum number of nodes to search, if any
+    node_limit: Option<u64>,
+    /// The number of nodes searched so far
+    nodes: AtomicU64,
+    /// Set to stop the search
+    stop: Arc<AtomicBool>,
+    /// The moves the root may choose from, or `None` to search all moves
+    root_moves: Option<Vec<Move>>,
+    /// Receives the progress of the search, or `None` to report nothing
+    info_sink: Option<InfoSink>,
+}
+
+impl SearchLimits {
+    /// Creates the limits for a search starting now
+    ///
+    /// # Arguments
+    ///
+    /// * `time_limit` - The time limit for the search, if any
+    /// * `node_limit` - The maximum number of nodes to search, if any
+    pub fn new(time_limit: Option<Duration>, node_limit: Option<u64>) -> Self {
+        SearchLimits::with_stop_flag(time_limit, node_limit, Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Creates the limits for a search starting now, which can also be stopped through a flag
+    /// shared with another thread
+    ///
+    /// # Arguments
+    ///
+    /// * `time_limit` - The time limit for the search, if any
+    /// * `node_limit` - The maximum number of nodes to search, if any
+    /// * `stop` - The stop flag, which the caller should clear before the search
+    pub fn with_stop_flag(time_limit: Option<Duration>, node_limit: Option<u64>, stop: Arc<AtomicBool>) -> Self {
+        SearchLimits {
+            start_time: Instant::now(),
+            time_limit,
+            node_limit,
+            nodes: AtomicU64::new(0),
+            stop,
+            root_moves: None,
+            info_sink: None,
+        }
+    }
+
+    /// Restricts the search to the given root moves, as with UCI `go searchmoves`
+    ///
+    /// # Arguments
+    ///
+    /// * `root_moves` - The legal moves to choose from; an empty list searches all moves
+    pub fn with_root_moves(mut self, root_moves: Vec<Move>) -> Self {
+        self.root_moves = if root_moves.is_empty() { None } else { Some(root_moves) };
+        self
+    }
+
+    /// Sends the progress of the search to the given sink after each completed depth, e.g. to
+    /// print UCI `info` lines. Without a sink the search reports nothing
+    ///
+    /// # Arguments
+    ///
+    /// * `info_sink` - Called with the progress after each completed depth
+    pub fn with_info_sink(mut self, info_sink: InfoSink) -> Self {
+        self.info_sink = Some(info_sink);
+        self
+    }
+
+    /// Reports the progress of the search to the info sink, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - The progress after a completed depth
+    pub fn report(&self, info: &SearchInfo) {
+        if let Some(info_sink) = &self.info_sink {
+            info_sink(info);
+        }
+    }
+
+    /// Whether the search has an info sink to report its progress to
+    pub fn has_info_sink(&self) -> bool {
+        self.info_sink.is_some()
+    }
+
+    /// The moves the root of the search may choose from, if restricted
+    pub fn root_moves(&self) -> Option<&[Move]> {
+        self.root_moves.as_deref()
+    }
+
+    /// Counts searched nodes, and stops the search if this reaches a limit
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of nodes searched
+    pub fn add_nodes(&self, count: i32) {
+        let count = count as u64;
+        let before = self.nodes.fetch_add(count, Ordering::Relaxed);
+        let after = before + count;
+        if let Some(node_limit) = self.node_limit {
+            if after >= node_limit {
+                self.stop();
+            }
+        }
+        // Read the clock when the counter passes a multiple of the interval
+        if before / TIME_CHECK_INTERVAL != after / TIME_CHECK_INTERVAL {
+            self.check_time();
+        }
+    }
+
+    /// Stops the search if the time limit has been reached
+    pub fn check_time(&self) {
+        if let Some(time_limit) = self.time_limit {
+            if self.start_time.elapsed() > time_limit {
+                self.stop();
+            }
+        }
+    }
+
+    /// Stops the search
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the search has been stopped
+    pub fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// The time limit for the search, if any
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit
+    }
+
+    /// The time since the search started
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+}
+
+/// The number of moves a clock's remaining time is divided between when the time control does
+/// not say how many moves are left
+const DEFAULT_MOVES_TO_GO: u32 = 20;
+
+/// Allocates the time for one move from the time left on the clock
+///
+/// Formula: time left per move until the time control is reached (or 5% of the time left if the
+/// number of moves is unknown) + 50% of the increment
+///
+/// # Arguments
+///
+/// * `time_left` - The time left on the side to move's clock
+/// * `increment` - The time added to the clock after each move
+/// * `moves_to_go` - The number of moves until the next time control, if known
+///
+/// # Returns
+///
+/// The time to search the move for
+pub fn allocate_time(time_left: Duration, increment: Duration, moves_to_go: Option<u32>) -> Duration {
+    let moves_left = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) as f32;
+    let base_time = time_left.as_secs_f32() / moves_left;
+    let bonus_time = increment.as_secs_f32();
+    Duration::from_secs_f32(base_time + bonus_time * 0.5)
+}
+
+/// The settings shared by every node of an alpha-beta search
+///
+/// Holds everything the search needs besides the board, the transposition table and the
+/// depth and window of the node being searched.
+pub struct SearchContext<'a, E: Evaluator> {
+    /// The move generator
+    pub move_gen: &'a MoveGen,
+    /// The position evaluator
+    pub evaluator: &'a E,
+    /// The maximum depth for the quiescence search
+    pub q_search_max_depth: i32,
+    /// Centipawn penalty applied to drawn positions from the point of view of the side to move at the root
+    pub contempt: i32,
+    /// The limits of the search, checked throughout the tree
+    pub limits: &'a SearchLimits,
+    /// Whether to print verbose output
+    pub verbose: bool,
+    /// The forward pruning and reductions the search may use
+    pub pruning: Pruning,
+}
+
+impl<'a, E: Evaluator> SearchContext<'a, E> {
+    /// Creates the settings for a search with a quiescence search depth of
+    /// `DEFAULT_Q_SEARCH_DEPTH`, no contempt and no verbose output
+    ///
+    /// # Arguments
+    ///
+    /// * `move_gen` - A reference to the move generator
+    /// * `evaluator` - A reference to the position evaluator
+    /// * `limits` - The limits of the search, checked throughout the tree
+    pub fn new(move_gen: &'a MoveGen, evaluator: &'a E, limits: &'a SearchLimits) -> Self {
+        SearchContext {
+            move_gen,
+            evaluator,
+            q_search_max_depth: DEFAULT_Q_SEARCH_DEPTH,
+            contempt: 0,
+            limits,
+            verbose: false,
+            pruning: Pruning::ALL,
+        }
+    }
+
+    /// Sets the maximum depth for the quiescence search; 0 uses the static evaluation at the leaves
+    pub fn with_q_search_depth(mut self, q_search_max_depth: i32) -> Self {
+        self.q_search_max_depth = q_search_max_depth;
+        self
+    }
+
+    /// Sets the centipawn penalty applied to drawn positions from the point of view of the side to move at the root
+    pub fn with_contempt(mut self, contempt: i32) -> Self {
+        self.contempt = contempt;
+        self
+    }
+
+    /// Sets whether to print verbose output
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Sets the forward pruning and reductions the search may use, e.g. to measure what one of them saves
+    pub fn with_pruning(mut self, pruning: Pruning) -> Self {
+        self.pruning = pruning;
+        self
+    }
+}
+
+/// The forward pruning and reductions an alpha-beta search may use
+///
+/// All of them are enabled in normal play. Turning one off shows how many nodes it saves, and
+/// whether it changes the result of a search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pruning {
+    /// Reverse futility pruning of nodes whose static eval is far above beta
+    pub reverse_futility: bool,
+    /// Razoring of frontier nodes whose static eval is far below alpha
+    pub razoring: bool,
+    /// Futility pruning of quiet moves at nodes whose static eval is far below alpha
+    pub futility: bool,
+    /// Late move reductions of quiet moves ordered late
+    pub late_move_reductions: bool,
+}
+
+impl Pruning {
+    /// Every kind of pruning and reduction
+    pub const ALL: Pruning = Pruning { reverse_futility: true, razoring: true, futility: true, late_move_reductions: true };
+
+    /// No pruning or reductions, so the search is exhaustive apart from alpha-beta cutoffs
+    pub const NONE: Pruning = Pruning { reverse_futility: false, razoring: false, futility: false, late_move_reductions: false };
+}
+
+/// Which moves the attacking side may play in mate search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MateSearchMode {
+    /// Only checking moves, which is fast but misses mates that need a quiet move
+    ChecksOnly,
+    /// All moves, which also finds mates that start with a quiet or waiting move
+    Full,
+}
 
 /// Perform negamax search from the given position
 ///
@@ -16,7 +568,7 @@ use crate::transposition::TranspositionTable;
 ///
 /// * `board` - A mutable reference to the current board state
 /// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
+/// * `evaluator` - A reference to the position evaluator
 /// * `depth` - The depth to search to
 ///
 /// # Returns
@@ -25,13 +577,13 @@ use crate::transposition::TranspositionTable;
 /// * The evaluation (in centipawns) of the best move
 /// * The best move to play from the current position
 /// * The number of nodes searched
-pub fn negamax_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, depth: i32) -> (i32, Move, i32) {
+pub fn negamax_search<E: Evaluator>(board: &mut BoardStack, move_gen: &MoveGen, evaluator: &E, depth: i32) -> (i32, Move, i32) {
     let mut best_eval: i32 = -1000000;
     let mut best_move: Move = Move::null();
     let mut n: i32 = 0;
     
     // Generate and combine captures and regular moves
-    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), pesto);
+    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), evaluator);
     captures.extend(moves);
     
     // Iterate through all moves
@@ -41,7 +593,7 @@ pub fn negamax_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoE
             board.undo_move();
             continue;
         }
-        let (mut eval, nodes) = negamax(board, move_gen, pesto, depth - 1);
+        let (mut eval, nodes) = negamax(board, move_gen, evaluator, depth - 1);
         eval = -eval;
         n += nodes;
         
@@ -63,7 +615,7 @@ pub fn negamax_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoE
 ///
 /// * `board` - A mutable reference to the current board state
 /// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
+/// * `evaluator` - A reference to the position evaluator
 /// * `depth` - The current depth in the search tree
 ///
 /// # Returns
@@ -71,17 +623,17 @@ pub fn negamax_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoE
 /// A tuple containing:
 /// * The evaluation (in centipawns) of the best move
 /// * The number of nodes searched
-fn negamax(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, depth: i32) -> (i32, i32) {
+fn negamax<E: Evaluator>(board: &mut BoardStack, move_gen: &MoveGen, evaluator: &E, depth: i32) -> (i32, i32) {
     if depth == 0 {
         // Leaf node: return the board evaluation
-        return (pesto.eval(&board.current_state()), 1);
+        return (evaluator.eval(&board.current_state(), move_gen), 1);
     }
     
     let mut best_eval: i32 = -1000000;
     let mut n: i32 = 0;
     
     // Generate and combine captures and regular moves
-    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), pesto);
+    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), evaluator);
     captures.extend(moves);
     
     // Iterate through all moves
@@ -91,7 +643,7 @@ fn negamax(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, depth:
             board.undo_move();
             continue;
         }
-        let (mut eval, nodes) = negamax(board, move_gen, pesto, depth - 1);
+        let (mut eval, nodes) = negamax(board, move_gen, evaluator, depth - 1);
         eval = -eval;
         n += nodes;
         
@@ -113,15 +665,11 @@ fn negamax(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, depth:
 /// # Arguments
 ///
 /// * `board` - A mutable reference to the current board state
-/// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
+/// * `tt` - The transposition table
+/// * `ctx` - The move generator, evaluator, quiescence depth, contempt and limits of the search
 /// * `depth` - The depth to search to
 /// * `alpha_init` - The initial alpha value for alpha-beta pruning
 /// * `beta_init` - The initial beta value for alpha-beta pruning
-/// * `q_search_max_depth` - The maximum depth for the quiescence search
-/// * `verbose` - A flag indicating whether to print verbose output
-/// * `start_time` - Current time if time limit is enabled
-/// * `time_limit` - Time limit for the search if time limit is enabled
 ///
 /// # Returns
 ///
@@ -129,8 +677,12 @@ fn negamax(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, depth:
 /// * The evaluation (in centipawns) of the final position
 /// * The best move to play from the current position
 /// * The number of nodes searched
-/// * Whether the search was terminated
-pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, tt: &mut TranspositionTable, depth: i32, alpha_init: i32, beta_init: i32, q_search_max_depth: i32, verbose: bool, start_time: Option<Instant>, time_limit: Option<Duration>) -> (i32, Move, i32, bool) {
+///
+/// If the search was stopped, `limits.stopped()` is set and the result is incomplete.
+pub fn alpha_beta_search<E: Evaluator>(board: &mut BoardStack, tt: &mut TranspositionTable, ctx: &SearchContext<E>, depth: i32, alpha_init: i32, beta_init: i32) -> (i32, Move, i32) {
+    // Mate scores and the selective depth are measured from here
+    board.set_search_root();
+
     // Initialize best move and alpha value
     let mut best_move: Move = Move::null();
     let mut alpha: i32 = alpha_init;
@@ -139,34 +691,39 @@ pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &Pes
     let mut eval: i32 = 0;
 
     // Check for checkmate and stalemate
-    if verbose {
+    if ctx.verbose {
         println!("Checking for checkmate and stalemate");
     }
-    let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(move_gen);
-    if verbose {
+    let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(ctx.move_gen);
+    if ctx.verbose {
         println!("Checkmate and stalemate checked");
         println!("Checkmate: {} Stalemate: {}", checkmate, stalemate);
     }
 
     // Handle checkmate and stalemate cases
     if checkmate {
-        if verbose {
+        if ctx.verbose {
             println!("AB search: Checkmate!");
         }
-        return (1000000, best_move, 1, true);
+        return (-MATE_SCORE, best_move, 1);
     } else if stalemate {
-        if verbose {
+        if ctx.verbose {
             println!("AB search: Stalemate!");
         }
-        return (0, best_move, 1, true);
+        return (-ctx.contempt, best_move, 1);
     }
 
     // Generate and combine captures and regular moves
-    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), pesto);
+    let (mut captures, moves) = ctx.move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), ctx.evaluator);
     captures.extend(moves);
 
+    // Only consider the allowed root moves, if restricted
+    if let Some(root_moves) = ctx.limits.root_moves() {
+        captures.retain(|m| root_moves.contains(m));
+    }
+
     // Print the list of captures
-    if verbose {
+    if ctx.verbose {
         println!("Before probing transition table:");
         for m in &captures {
             println!("{}", print_move(&m));
@@ -178,7 +735,7 @@ pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &Pes
     let mut found_best_move = false;
     if let Some(entry) = tt.probe(board.current_state(), 1) {
         if let Some(tt_best_move) = captures.iter().find(|&m| *m == entry.best_move) {
-            if verbose {
+            if ctx.verbose {
                 found_best_move = true;
                 println!("Found best move from transposition table: {}", print_move(&tt_best_move));
             }
@@ -198,39 +755,35 @@ pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &Pes
     }
 
     for m in captures {
-        if verbose {
+        if ctx.verbose {
             println!("Considering move {} at root of search tree", print_move(&m));
         }
         board.make_move(m);
-        if !board.current_state().is_legal(move_gen) {
+        if !board.current_state().is_legal(ctx.move_gen) {
             board.undo_move();
             continue;
         }
-        let (search_eval, nodes) = alpha_beta(board, move_gen, pesto, tt, depth - 1, -beta, -alpha, q_search_max_depth, verbose);
+        let (search_eval, nodes) = alpha_beta(board, tt, ctx, depth - 1, -beta, -alpha, ctx.contempt);
         eval = -search_eval;
         n += nodes;
+
+        // Stop on reaching a limit, discarding the unfinished result of this move
+        if ctx.limits.stopped() {
+            board.undo_move();
+            if ctx.verbose {
+                println!("Search stopped.");
+            }
+            return (alpha, best_move, n);
+        }
+
         if eval > alpha {
             alpha = eval;
             best_move = m;
         }
 
-        if verbose {
+        if ctx.verbose {
             println!("Just checked move {}, current best move is {}", &m.print_algebraic(), &best_move.print_algebraic());
-            if let Some(start_time) = start_time {
-                println!("Current time: {:?}, time limit: {:?}", start_time.elapsed(), time_limit);
-            }
-        }
-
-        // Check time limit
-        if let Some(start_time) = start_time {
-            if let Some(time_limit) = time_limit {
-                if start_time.elapsed() > time_limit {
-                    if verbose {
-                        println!("Time limit reached. Stopping search.");
-                    }
-                    return (alpha, best_move, nodes, true);
-                }
-            }
+            println!("Current time: {:?}", ctx.limits.elapsed());
         }
 
         // Undo the move
@@ -242,14 +795,95 @@ pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &Pes
         }
     }
 
-    if verbose {
+    if ctx.verbose {
         println!("Alpha beta search at depth {} searched {} nodes. Best eval and move are {} {}", depth, n, alpha, print_move(&best_move));
     }
 
     // Store the result in the transposition table
     tt.store(board.current_state(), depth, eval, best_move);
 
-    (alpha, best_move, n, false)
+    (alpha, best_move, n)
+}
+
+/// The result of searching one root move, with the opponent's best reply to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RootMoveAnalysis {
+    /// The root move
+    pub mv: Move,
+    /// The score of the move from the point of view of the side to move at the root
+    pub score: i32,
+    /// The opponent's best reply, which refutes the move when its score is poor
+    pub reply: Option<Move>,
+}
+
+/// Searches every legal root move separately and reports the opponent's best reply to each
+///
+/// Unlike `alpha_beta_search`, each move is searched with a full window, so the scores are exact
+/// and can be compared, and the reply that refutes a poor move is known. This is slower than a
+/// normal search and meant for analysis.
+///
+/// # Arguments
+///
+/// * `board` - A mutable reference to the current board state
+/// * `tt` - The transposition table, from which the replies are read
+/// * `ctx` - The move generator, evaluator, quiescence depth, contempt and limits of the search
+/// * `depth` - The depth to search each root move to; replies are only found from depth 2
+///
+/// # Returns
+///
+/// The legal root moves with their scores and best replies, best move first.
+pub fn analyze_root_moves<E: Evaluator>(board: &mut BoardStack, tt: &mut TranspositionTable, ctx: &SearchContext<E>, depth: i32) -> Vec<RootMoveAnalysis> {
+    board.set_search_root();
+    let (mut moves, quiets) = ctx.move_gen.gen_pseudo_legal_moves_with_evals(board.current_state(), ctx.evaluator);
+    moves.extend(quiets);
+
+    let mut analysis = Vec::new();
+    for m in moves {
+        board.make_move(m);
+        if !board.current_state().is_legal(ctx.move_gen) {
+            board.undo_move();
+            continue;
+        }
+        let (score, _) = alpha_beta(board, tt, ctx, depth - 1, -MATE_SCORE, MATE_SCORE, ctx.contempt);
+        let reply = tt.probe(board.current_state(), i32::MIN)
+            .map(|entry| entry.best_move)
+            .filter(|&reply| reply != Move::null() && board.current_state().apply_move_to_board(reply).is_legal(ctx.move_gen));
+        board.undo_move();
+        analysis.push(RootMoveAnalysis { mv: m, score: -score, reply });
+    }
+    analysis.sort_by_key(|root_move| -root_move.score);
+    analysis
+}
+
+/// Checks whether the side to move is stalemated: not in check, but without a legal move
+///
+/// A king with a safe square to step to always has a legal move, which settles most positions
+/// without generating any moves and keeps the test cheap enough to run at every node.
+///
+/// # Arguments
+///
+/// * `board` - The position to check
+/// * `move_gen` - A reference to the move generator
+/// * `in_check` - Whether the side to move is in check
+///
+/// # Returns
+///
+/// `true` if the position is stalemate
+fn is_stalemate(board: &Board, move_gen: &MoveGen, in_check: bool) -> bool {
+    if in_check {
+        return false;
+    }
+    let side = if board.w_to_move { WHITE } else { BLACK };
+    let king = board.pieces[side][KING];
+    if king != 0 {
+        // The king is not in check, so no slider looks through its square, and a square that is
+        // not attacked now is still safe once the king has moved
+        let free = move_gen.k_move_bitboard[king.trailing_zeros() as usize] & !board.pieces_occ[side];
+        if bits(&free).any(|sq| !board.is_square_attacked(sq, !board.w_to_move, move_gen)) {
+            return false;
+        }
+    }
+    board.is_checkmate_or_stalemate(move_gen).1
 }
 
 /// Recursive helper function for alpha-beta search
@@ -260,13 +894,14 @@ pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &Pes
 /// # Arguments
 ///
 /// * `board` - A mutable reference to the current board state
-/// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
+/// * `tt` - The transposition table
+/// * `ctx` - The move generator, evaluator, quiescence depth and limits of the search. Once a
+///   limit is reached the search stops, and the returned evaluation is meaningless and must be discarded
 /// * `depth` - The current depth in the search tree
 /// * `alpha` - The current alpha value for alpha-beta pruning
 /// * `beta` - The current beta value for alpha-beta pruning
-/// * `q_search_max_depth` - The maximum depth for the quiescence search
-/// * `verbose` - A flag indicating whether to print verbose output
+/// * `draw_score` - The score of a drawn position (repetition, fifty-move rule or stalemate) from the
+///   point of view of the side to move; this is how contempt is propagated through the tree
 ///
 /// # Returns
 ///
@@ -274,23 +909,59 @@ pub fn alpha_beta_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &Pes
 /// * The evaluation (in centipawns) of the final position
 /// * The best move to play from the current position
 /// * The number of nodes searched
-pub fn alpha_beta(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, tt: &mut TranspositionTable, depth: i32, mut alpha: i32, beta: i32, q_search_max_depth: i32, verbose: bool) -> (i32, i32) {
+pub fn alpha_beta<E: Evaluator>(board: &mut BoardStack, tt: &mut TranspositionTable, ctx: &SearchContext<E>, depth: i32, mut alpha: i32, mut beta: i32, draw_score: i32) -> (i32, i32) {
     // Private recursive function used for alpha-beta search
     // External functions should call alpha_beta_search instead
     // Returns the eval (in centipawns) of the final position
     // Also returns number of nodes searched
-    if verbose {
+    if ctx.verbose {
         println!("Alpha beta search at depth {} with alpha {} and beta {}", depth, alpha, beta);
     }
+
+    // Stopped elsewhere in the tree: the result will be discarded
+    if ctx.limits.stopped() {
+        return (alpha, 0);
+    }
+
+    // Draw by threefold repetition or the fifty-move rule. Checkmate on the move that reaches
+    // the fifty-move limit still wins, so that case is left to the search below
+    if board.is_draw_by_repetition()
+        || (board.current_state().halfmove_clock >= 100 && !board.current_state().is_checkmate_or_stalemate(ctx.move_gen).0) {
+        ctx.limits.add_nodes(1);
+        return (draw_score, 1);
+    }
+
+    // Mate distance pruning: being mated here, or mating on the next move, bounds the score,
+    // so there is nothing to search if a shorter mate has already been found
+    let ply = board.ply();
+    alpha = alpha.max(-MATE_SCORE + ply);
+    beta = beta.min(MATE_SCORE - ply - 1);
+    if alpha >= beta {
+        ctx.limits.add_nodes(1);
+        return (alpha, 1);
+    }
+
+    // Stalemate would otherwise be scored statically by the quiescence search or the pruning
+    // below, making a stalemating move look like a winning one
+    let in_check = board.current_state().is_check(ctx.move_gen);
+    if is_stalemate(board.current_state(), ctx.move_gen, in_check) {
+        ctx.limits.add_nodes(1);
+        return (draw_score, 1);
+    }
+
     if depth == 0 {
         // Leaf node
-        let (eval, nodes) = q_search(board, move_gen, pesto, alpha, beta, q_search_max_depth, verbose);
-        if verbose {
+        let (eval, nodes) = q_search(board, ctx, alpha, beta, ctx.q_search_max_depth, None, QuiescenceMode::CapturesOnly);
+        if ctx.verbose {
             println!("Outcome of Q search: {} {}", eval, nodes);
         }
+        ctx.limits.add_nodes(nodes);
         return (eval, nodes);
     }
 
+    // Count this node now, so that the ctx.limits are checked on the way down the tree
+    ctx.limits.add_nodes(1);
+
     // Best move
     let mut eval: i32 = 0;
     let mut best_move: Move = Move::null();
@@ -298,7 +969,45 @@ pub fn alpha_beta(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval,
     // Non-leaf node
     let mut n: i32 = 1;
 
-    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), pesto);
+    // Forward pruning is only safe when not in check, away from mate scores and off the
+    // principal variation, whose exact score the pruning margins would otherwise distort
+    let pv_node = beta - alpha > 1;
+    let can_prune = !in_check && !pv_node && depth <= REVERSE_FUTILITY_MAX_DEPTH && alpha.abs() < MATE_THRESHOLD && beta.abs() < MATE_THRESHOLD;
+
+    // Late move reductions rely on the quiescence search to catch the tactics they miss, like razoring
+    let reduce_late_moves = ctx.pruning.late_move_reductions && !in_check && depth >= LMR_MIN_DEPTH && ctx.q_search_max_depth > 0;
+    let static_eval = if can_prune || reduce_late_moves { ctx.evaluator.eval(board.current_state(), ctx.move_gen) } else { 0 };
+    if can_prune || reduce_late_moves {
+        board.set_static_eval(static_eval);
+    }
+
+    // A position whose static eval has not risen since the side to move last moved is reduced more;
+    // without an earlier eval to compare with, it is assumed to be improving
+    let improving = board.static_eval_before(2).is_none_or(|before| static_eval > before);
+
+    // Reverse futility pruning: the static eval is so far above beta that no move is expected to drop below it.
+    // Returning the static eval would give a principal variation node an inexact score, so `can_prune`
+    // excludes those
+    if can_prune && ctx.pruning.reverse_futility && static_eval - REVERSE_FUTILITY_MARGIN * depth >= beta {
+        return (static_eval, n);
+    }
+
+    // Razoring: at frontier nodes far below alpha, resolve the position with quiescence search instead
+    // (only when quiescence search is enabled, otherwise this would ignore all tactics)
+    if can_prune && ctx.pruning.razoring && depth == 1 && ctx.q_search_max_depth > 0 && static_eval + RAZORING_MARGIN <= alpha {
+        let (q_eval, q_nodes) = q_search(board, ctx, alpha, beta, ctx.q_search_max_depth, Some(static_eval), QuiescenceMode::CapturesOnly);
+        // The quiescence search already counted this node
+        ctx.limits.add_nodes(q_nodes - 1);
+        if q_eval <= alpha {
+            return (q_eval, q_nodes);
+        }
+        n += q_nodes - 1;
+    }
+
+    // Futility pruning: quiet moves are unlikely to raise a hopeless static eval above alpha
+    let futile = can_prune && ctx.pruning.futility && depth <= 2 && static_eval + FUTILITY_MARGINS[depth as usize] <= alpha;
+
+    let (mut captures, moves) = ctx.move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), ctx.evaluator);
     captures.extend(moves);
 
     // Improve alpha-beta pruning by searching the best move from the transposition table first
@@ -313,31 +1022,75 @@ pub fn alpha_beta(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval,
         }
     }
 
+    let mut legal_moves: i32 = 0;
     for m in captures {
-        if verbose {
+        if ctx.verbose {
             println!("Considering move {}", print_move(&m));
         }
+        let is_quiet = m.is_quiet(board.current_state());
         board.make_move(m);
-        if !board.current_state().is_legal(move_gen) {
+        if !board.current_state().is_legal(ctx.move_gen) {
             board.undo_move();
             continue;
         }
-        let (search_eval, nodes) = alpha_beta(board, move_gen, pesto, tt, depth - 1, -beta, -alpha, q_search_max_depth, verbose);
-        eval = -search_eval;
-        n += nodes;
+        legal_moves += 1;
+
+        // Skip futile quiet moves, unless they give check
+        if futile && is_quiet && !board.current_state().is_check(ctx.move_gen) {
+            board.undo_move();
+            continue;
+        }
+
+        // Late move reductions: quiet moves ordered late are first searched to a reduced depth
+        // with a null window, and only searched fully if they unexpectedly raise alpha
+        let mut reduction = 0;
+        if reduce_late_moves && is_quiet && legal_moves > LMR_MIN_MOVES && !board.current_state().is_check(ctx.move_gen) {
+            reduction = LMR_TABLE.reduction(depth, legal_moves);
+            if pv_node {
+                reduction -= 1;
+            }
+            if !improving {
+                reduction += 1;
+            }
+            reduction = reduction.clamp(0, depth - 2);
+        }
+        if reduction > 0 {
+            let (search_eval, nodes) = alpha_beta(board, tt, ctx, depth - 1 - reduction, -alpha - 1, -alpha, -draw_score);
+            eval = -search_eval;
+            n += nodes;
+        }
+        if reduction == 0 || (eval > alpha && !ctx.limits.stopped()) {
+            let (search_eval, nodes) = alpha_beta(board, tt, ctx, depth - 1, -beta, -alpha, -draw_score);
+            eval = -search_eval;
+            n += nodes;
+        }
+        board.undo_move();
+
+        // Out of time or nodes: unwind without storing the incomplete result
+        if ctx.limits.stopped() {
+            return (alpha, n);
+        }
+
         if eval > alpha {
             alpha = eval;
             best_move = m;
         }
-        board.undo_move();
         if alpha >= beta {
-            if verbose {
+            if ctx.verbose {
                 println!("Inner Alpha beta search at depth {} searched {} nodes. Best eval and move are {} {}", depth, n, alpha, print_move(&m));
             }
             break;
         }
     }
 
+    // No legal moves: checkmate or stalemate
+    if legal_moves == 0 {
+        if in_check {
+            return (-MATE_SCORE + ply, n);
+        }
+        return (draw_score, n);
+    }
+
     // Store the result in the transposition table, but no need to return it
     tt.store(board.current_state(), depth, eval, best_move);
 
@@ -348,17 +1101,17 @@ pub fn alpha_beta(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval,
 ///
 /// This function performs an iterative deepening search, where the search depth is gradually increased
 /// until the maximum depth is reached. At each iteration, the alpha-beta search algorithm is used to
-/// search for the best move.
+/// search for the best move. Each completed depth is reported to the limits' info sink, if one is set.
 ///
 /// # Arguments
 ///
 /// * `board` - A mutable reference to the current board state
-/// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
+/// * `tt` - The transposition table, which may be kept between the searches of a game
+/// * `ctx` - The move generator, evaluator, quiescence depth, contempt and limits of the search.
+///   The limits hold the time and node limits and the flag to stop the search. The search
+///   always stops after `max_depth`, and otherwise returns the result of the last completed depth.
+///   A single legal move is returned after searching it to depth 1
 /// * `max_depth` - The maximum depth to search to
-/// * `q_search_max_depth` - The maximum depth for the quiescence search
-/// * `time_limit` - An optional duration for the search time limit
-/// * `verbose` - A flag indicating whether to print verbose output
 ///
 /// # Returns
 ///
@@ -367,26 +1120,32 @@ pub fn alpha_beta(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval,
 /// * The evaluation (in centipawns) of the final position
 /// * The best move to play from the current position
 /// * The number of nodes searched
-pub fn iterative_deepening_ab_search(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, max_depth: i32, q_search_max_depth: i32, time_limit: Option<Duration>, verbose: bool) -> (i32, i32, Move, i32) {
+pub fn iterative_deepening_ab_search<E: Evaluator>(board: &mut BoardStack, tt: &mut TranspositionTable, ctx: &SearchContext<E>, max_depth: i32) -> (i32, i32, Move, i32) {
+
+    tt.new_search();
+
+    // With only one legal move there is nothing to decide, so the search plays it at once,
+    // searching just deep enough to report a score
+    if let Some(only_move) = only_root_move(board.current_state(), ctx.move_gen, ctx.limits) {
+        let (eval, _, nodes) = alpha_beta_search(board, tt, ctx, 1, -1000000, 1000000);
+        if ctx.limits.stopped() {
+            return (0, 0, only_move, nodes);
+        }
+        tt.store(board.current_state(), 1, eval, only_move);
+        report_progress(board, ctx.move_gen, tt, ctx.limits, 1, eval, nodes);
+        return (1, eval, only_move, nodes);
+    }
 
-    let mut tt = TranspositionTable::new();
     let mut eval: i32 = 0;
     let mut best_move: Move = Move::null();
     let mut nodes: i32 = 0;
     let mut last_fully_searched_depth: i32 = 0;
 
-    let start_time = Instant::now();
-
-    // Check the transposition table to see if this node has already been searched at the target depth
-    if let Some(entry) = tt.probe(&board.current_state(), max_depth) {
-        return (entry.depth, entry.score, entry.best_move, nodes);
-    }
-
     // Iterate over increasing depths
     let mut depth = 1;
     while depth <= max_depth {
 
-        if verbose {
+        if ctx.verbose {
             println!("Starting search at depth {}", depth);
         }
         // Skip odd depths in iterative deepening (other than max_depth if it is odd)
@@ -396,106 +1155,205 @@ pub fn iterative_deepening_ab_search(board: &mut BoardStack, move_gen: &MoveGen,
         }
 
         // Perform alpha-beta search
-        let (new_eval, new_best_move, new_nodes, terminated) = alpha_beta_search(board, move_gen, pesto, &mut tt, depth, -1000000, 1000000, q_search_max_depth, verbose, Some(start_time), time_limit);
+        let (new_eval, new_best_move, new_nodes) = alpha_beta_search(board, tt, ctx, depth, -1000000, 1000000);
 
-        if !terminated {
-            eval = new_eval;
-            best_move = new_best_move;
-            nodes += new_nodes;
+        // Nodes of an unfinished iteration still count, but its result is discarded
+        nodes += new_nodes;
+        if ctx.limits.stopped() {
+            if ctx.verbose {
+                println!("Search stopped at depth {}", depth);
+            }
+            break;
         }
+        eval = new_eval;
+        best_move = new_best_move;
 
-        if verbose {
+        if ctx.verbose {
             println!("At depth {}, searched {} nodes. best eval and move are {} {}", depth, nodes, eval, print_move(&best_move));
         }
 
-        // If there is a time limit, check to see if we have exceeded it
-        if terminated {
+        // No legal moves, so there is nothing to deepen
+        if best_move == Move::null() {
             break;
         }
 
-        if let Some(time_limit) = time_limit {
-            if start_time.elapsed() > time_limit {
-                if verbose {
-                    println!("Time limit reached. Stopping search.");
-                }
-                break;
-            }
-        }
-
         // Store the result in the transposition table
-        tt.store(&board.current_state(), depth, eval, best_move);
+        tt.store(board.current_state(), depth, eval, best_move);
         last_fully_searched_depth = depth;
+        report_progress(board, ctx.move_gen, tt, ctx.limits, depth, eval, nodes);
+
+        // Out of time: don't start another depth
+        ctx.limits.check_time();
+        if ctx.limits.stopped() {
+            break;
+        }
 
         depth += 1;
     }
     (last_fully_searched_depth, eval, best_move, nodes)
 }
 
+/// Reports the progress after a completed depth to the search's info sink, if it has one
+fn report_progress(board: &mut BoardStack, move_gen: &MoveGen, tt: &TranspositionTable, limits: &SearchLimits, depth: i32, eval: i32, nodes: i32) {
+    if !limits.has_info_sink() {
+        return;
+    }
+    let time = limits.elapsed();
+    let nodes = nodes as u64;
+    limits.report(&SearchInfo {
+        depth,
+        seldepth: board.seldepth(),
+        score: eval,
+        nodes,
+        nps: nodes * 1000 / (time.as_millis() as u64).max(1),
+        hashfull: tt.hashfull(),
+        time,
+        pv: principal_variation(board, move_gen, tt, depth.max(1) as usize),
+    });
+}
+
+/// Returns the legal move the root of a search may choose from, if there is exactly one
+fn only_root_move(board: &Board, move_gen: &MoveGen, limits: &SearchLimits) -> Option<Move> {
+    let (captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+    let mut legal_moves = captures.into_iter().chain(moves)
+        .filter(|m| limits.root_moves().is_none_or(|root_moves| root_moves.contains(m)))
+        .filter(|m| board.apply_move_to_board(*m).is_legal(move_gen));
+    match (legal_moves.next(), legal_moves.next()) {
+        (Some(only_move), None) => Some(only_move),
+        _ => None,
+    }
+}
+
+/// The half-width (in centipawns) of the first aspiration window at each depth
+pub const ASPIRATION_INITIAL_DELTA: i32 = 25;
+
+/// The factor by which an aspiration window's failed side is widened before a re-search
+pub const ASPIRATION_GROWTH: f64 = 2.0;
+
+/// The number of re-searches at one depth before falling back to a full window
+pub const ASPIRATION_MAX_RESEARCHES: u32 = 4;
+
+/// The widest window a search uses, which no score falls outside of
+const FULL_WINDOW: (i32, i32) = (-1000000, 1000000);
+
+/// How aspiration windows are widened when a search falls outside them
+///
+/// Each depth starts with a window of `initial_delta` on either side of the previous depth's
+/// score. A search that fails low or high widens that side of the window by `growth` and
+/// searches again. After `max_researches` re-searches at one depth, if any, the next search uses
+/// a full window, so a position whose score swings wildly between depths cannot keep the search
+/// re-searching. A side that has grown past the full window is cut off there, so even without a
+/// limit the search always settles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AspirationWindow {
+    /// The half-width of the first window at each depth, in centipawns
+    pub initial_delta: i32,
+    /// The factor by which a failed side of the window is widened; must be greater than 1
+    pub growth: f64,
+    /// The number of re-searches at one depth before using a full window, or no limit if `None`
+    pub max_researches: Option<u32>,
+}
+
+impl AspirationWindow {
+    /// Creates a widening schedule
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_delta` - The half-width of the first window at each depth, in centipawns
+    /// * `growth` - The factor by which a failed side of the window is widened; must be greater than 1
+    /// * `max_researches` - The number of re-searches at one depth before using a full window, or no limit if `None`
+    pub fn new(initial_delta: i32, growth: f64, max_researches: Option<u32>) -> Self {
+        assert!(initial_delta > 0, "the aspiration window must have a positive width");
+        assert!(growth > 1.0, "the aspiration window must grow on each re-search");
+        AspirationWindow { initial_delta, growth, max_researches }
+    }
+
+    /// The half-width of a side of the window after it has failed the given number of times
+    fn delta(&self, fails: u32) -> i32 {
+        (self.initial_delta as f64 * self.growth.powi(fails as i32)).min(FULL_WINDOW.1 as f64) as i32
+    }
+}
+
+impl Default for AspirationWindow {
+    fn default() -> Self {
+        AspirationWindow::new(ASPIRATION_INITIAL_DELTA, ASPIRATION_GROWTH, Some(ASPIRATION_MAX_RESEARCHES))
+    }
+}
+
 /// Perform aspiration window alpha-beta search from the given position
 ///
 /// This function performs an aspiration window search, where the search is focused on a specific
 /// window of possible scores. The window is initially set to a narrow range, and if the search
-/// finds a move that falls outside this range, the window is expanded and the search is repeated.
+/// finds a move that falls outside this range, the window is expanded and the search is repeated,
+/// following the widening schedule in `window`.
 ///
 /// # Arguments
 ///
 /// * `board` - A mutable reference to the current board state
-/// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
+/// * `tt` - The transposition table
+/// * `ctx` - The move generator, evaluator, quiescence depth, contempt and limits of the search. The search
+///   always stops after `max_depth`, and otherwise returns the result of the last completed
+///   depth; a depth that has to be re-searched is not complete until a search falls inside its window
 /// * `max_depth` - The maximum depth to search to
-/// * `q_search_max_depth` - The maximum depth for the quiescence search
-/// * `verbose` - A flag indicating whether to print verbose output
+/// * `window` - How the window is widened on re-searches, e.g. `AspirationWindow::default()`
 ///
 /// # Returns
 ///
 /// A tuple containing:
+/// * The last depth completed, in plies
 /// * The evaluation (in centipawns) of the final position
 /// * The best move to play from the current position
 /// * The number of nodes searched
-pub fn aspiration_window_ab_search(board: &mut BoardStack, move_gen: &MoveGen, tt: &mut TranspositionTable, pesto: &PestoEval, max_depth: i32, q_search_max_depth: i32, verbose: bool) -> (i32, Move, i32) {
+pub fn aspiration_window_ab_search<E: Evaluator>(board: &mut BoardStack, tt: &mut TranspositionTable, ctx: &SearchContext<E>, max_depth: i32, window: &AspirationWindow) -> (i32, i32, Move, i32) {
     // Perform aspiration window alpha-beta search from the given position
     // Also uses iterative deepening: After searching at a given depth, starts a new search at that depth + 1, but looks at most promising variation first
     // This is really helpful for alpha-beta pruning
-    let lower_bound_param: i32 = -25;
-    let upper_bound_param: i32 = 25;
-
     let mut target_eval: i32 = board.current_state().eval;
     let mut best_move: Move = Move::null();
-    let mut nodes: i32;
+    let mut last_fully_searched_depth: i32 = 0;
 
     // First perform a quiescence search at a depth of 0
-    let mut lower_bound: i32 = -1000000;
-    let mut upper_bound: i32 = 1000000;
-    let (mut eval, mut n) = q_search(board, move_gen, pesto, lower_bound, upper_bound, q_search_max_depth, verbose);
+    let (mut eval, mut n) = q_search(board, ctx, FULL_WINDOW.0, FULL_WINDOW.1, ctx.q_search_max_depth, None, QuiescenceMode::CapturesOnly);
 
     // Now perform an iterative deepening search with aspiration windows
     for d in 1..= max_depth {
         let depth = 2 * d; // Only even depths, due to the even/odd effect
-        let mut lower_window_scale: i32 = 1;
-        let mut upper_window_scale: i32 = 1;
+        let mut lower_fails: u32 = 0;
+        let mut upper_fails: u32 = 0;
         loop {
-            lower_bound = target_eval + lower_bound_param * lower_window_scale;
-            upper_bound = target_eval + upper_bound_param * upper_window_scale;
-            if verbose {
+            let researches = lower_fails + upper_fails;
+            let (lower_bound, upper_bound) = if window.max_researches.is_some_and(|max| researches >= max) {
+                FULL_WINDOW
+            } else {
+                ((target_eval - window.delta(lower_fails)).max(FULL_WINDOW.0), (target_eval + window.delta(upper_fails)).min(FULL_WINDOW.1))
+            };
+            if ctx.verbose {
                 println!("Aspiration window search with window {} {}", lower_bound, upper_bound);
             }
-            (eval, best_move, nodes, _) = alpha_beta_search(board, move_gen, pesto, tt, depth, lower_bound, upper_bound, q_search_max_depth, verbose, None, None);
+            let (new_eval, new_best_move, nodes) = alpha_beta_search(board, tt, ctx, depth, lower_bound, upper_bound);
             n += nodes;
-            if verbose {
+
+            // Out of time or nodes: keep the result of the last completed depth
+            if ctx.limits.stopped() {
+                return (last_fully_searched_depth, eval, best_move, n);
+            }
+            (eval, best_move) = (new_eval, new_best_move);
+            if ctx.verbose {
                 println!("At depth {}, searched {} nodes. best eval and move are {} {}", depth, n, eval, print_move(&best_move));
             }
-            if eval == lower_bound {
-                if verbose {
+            // A full window side cannot fail, so the search settles once both sides reach it
+            if eval <= lower_bound && lower_bound > FULL_WINDOW.0 {
+                if ctx.verbose {
                     println!("\nLower bound hit; retrying with larger window");
                 }
-                lower_window_scale *= 2;
-            } else if eval == upper_bound {
-                if verbose {
+                lower_fails += 1;
+            } else if eval >= upper_bound && upper_bound < FULL_WINDOW.1 {
+                if ctx.verbose {
                     println!("\nUpper bound hit; retrying with larger window");
                 }
-                upper_window_scale *= 2;
+                upper_fails += 1;
             } else {
-                if verbose {
+                if ctx.verbose {
                     println!("\nAspiration window search successful!");
                     println!("Best move: {}", print_move(&best_move));
                     println!("Eval: {}\n", eval);
@@ -504,83 +1362,108 @@ pub fn aspiration_window_ab_search(board: &mut BoardStack, move_gen: &MoveGen, t
                 break;
             }
         }
+        last_fully_searched_depth = depth;
+
+        // Out of time: don't start another depth
+        ctx.limits.check_time();
+        if ctx.limits.stopped() {
+            break;
+        }
     }
-    (eval, best_move, n)
+    (last_fully_searched_depth, eval, best_move, n)
 }
 
 /// Performs a quiescence search to evaluate tactical sequences and avoid the horizon effect.
 ///
 /// This function uses the negamax framework and searches captures and promotions until a quiet
 /// position is reached or the maximum depth is hit. It implements stand-pat evaluation and
-/// various pruning techniques to improve efficiency.
+/// various pruning techniques to improve efficiency. When the side to move is in check, stand-pat
+/// is not allowed: all evasions are searched, and a position without any is scored as checkmate.
+///
+/// Evasions and quiet checks are not extensions: every ply uses up depth like a capture, and
+/// quiet checks are only tried at the first ply. Together with the `Q_SEARCH_MAX_DEPTH` cap this
+/// keeps the search finite even in positions with perpetual check.
 ///
 /// # Arguments
 ///
 /// * `board` - A mutable reference to the current board state.
-/// * `move_gen` - A reference to the move generator.
-/// * `pesto` - A reference to the position evaluator.
+/// * `ctx` - The move generator and evaluator, and whether to print verbose output.
 /// * `alpha` - The lower bound of the search window.
 /// * `beta` - The upper bound of the search window.
-/// * `max_depth` - The (remaining) maximum depth for quiescence search.
-/// * `verbose` - A boolean flag for verbose output.
+/// * `max_depth` - The (remaining) maximum depth for quiescence search, capped at `Q_SEARCH_MAX_DEPTH`.
+/// * `static_eval` - The static evaluation of the position, if the caller has already computed it.
+/// * `mode` - Whether quiet checking moves are also searched at this ply.
 ///
 /// # Returns
 ///
 /// A tuple containing:
 /// - The score of the position after quiescence search (from the perspective of the side to move).
 /// - The number of nodes searched.
-fn q_search(
-    board: &mut BoardStack,
-    move_gen: &MoveGen,
-    pesto: &PestoEval,
-    mut alpha: i32,
-    beta: i32,
-    max_depth: i32,
-    verbose: bool
-) -> (i32, i32) {
+fn q_search<E: Evaluator>(board: &mut BoardStack, ctx: &SearchContext<E>, mut alpha: i32, beta: i32, max_depth: i32, static_eval: Option<i32>, mode: QuiescenceMode) -> (i32, i32) {
     let mut nodes = 1;
+    let max_depth = max_depth.min(Q_SEARCH_MAX_DEPTH);
 
-    // Stand-pat evaluation
-    let stand_pat = pesto.eval(&board.current_state());
+    // A side in check cannot stand pat, so all evasions must be searched instead
+    // (unless the maximum depth has been reached, in which case the static eval is used)
+    let in_check = max_depth > 0 && board.current_state().is_check(ctx.move_gen);
 
-    // Beta cutoff
-    if stand_pat >= beta {
-        return (beta, nodes);
-    }
+    let mut stand_pat = 0;
+    if !in_check {
+        // Stand-pat evaluation, reusing the caller's static eval if available
+        stand_pat = static_eval.unwrap_or_else(|| ctx.evaluator.eval(&board.current_state(), ctx.move_gen));
 
-    // Update alpha
-    if stand_pat > alpha {
-        alpha = stand_pat;
-    }
+        // Beta cutoff
+        if stand_pat >= beta {
+            return (beta, nodes);
+        }
 
-    // Check if we've reached max depth
-    if max_depth == 0 {
-        if verbose {
-            println!("Quiescence: Max depth reached! Eval: {}", stand_pat);
+        // Update alpha
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        // Check if we've reached max depth
+        if max_depth == 0 {
+            if ctx.verbose {
+                println!("Quiescence: Max depth reached! Eval: {}", stand_pat);
+            }
+            return (alpha, nodes);
         }
-        return (alpha, nodes);
     }
 
-    // Generate captures and promotions
-    let captures = move_gen.gen_pseudo_legal_captures(&board.current_state());
+    // Generate all evasions when in check, otherwise captures and promotions
+    let captures = if in_check {
+        let (mut captures, moves) = ctx.move_gen.gen_pseudo_legal_moves_with_evals(board.current_state(), ctx.evaluator);
+        captures.extend(moves);
+        captures
+    } else if mode == QuiescenceMode::CapturesAndChecks {
+        // Captures and promotions, then the quiet checks
+        let mut captures = ctx.move_gen.gen_pseudo_legal_captures(board.current_state());
+        captures.extend(ctx.move_gen.gen_checking_moves(board.current_state()).into_iter().filter(|m| m.is_quiet(board.current_state())));
+        captures
+    } else {
+        ctx.move_gen.gen_pseudo_legal_captures(board.current_state())
+    };
 
-    if captures.is_empty() {
-        if verbose {
+    if captures.is_empty() && !in_check {
+        if ctx.verbose {
             println!("Quiescence: No captures left! Eval: {}", stand_pat);
         }
         return (stand_pat, nodes);
     }
 
     // Search captures
+    let mut legal_moves: i32 = 0;
     for capture in captures {
         board.make_move(capture);
-        if !board.current_state().is_legal(move_gen) {
+        if !board.current_state().is_legal(ctx.move_gen) {
             board.undo_move();
             continue;
         }
+        legal_moves += 1;
 
         // Recursive call
-        let (mut score, n) = q_search(board, move_gen, pesto, -beta, -alpha, max_depth - 1, verbose);
+        let (mut score, n) = q_search(board, ctx, -beta, -alpha, max_depth - 1, None, QuiescenceMode::CapturesOnly);
         score = -score; // Negamax
         nodes += n;
 
@@ -598,149 +1481,76 @@ fn q_search(
         }
     }
 
+    // In check with no legal evasions: checkmate
+    if in_check && legal_moves == 0 {
+        if ctx.verbose {
+            println!("Quiescence: Checkmate!");
+        }
+        return (-MATE_SCORE + board.ply(), nodes);
+    }
+
     (alpha, nodes)
 }
 
-/// Perform a quiescence search with consistent side to move
+/// Performs a quiescence search from the given position.
 ///
-/// This function performs a quiescence search, which is a selective search of tactically
-/// active positions. It ensures that the evaluation is always from the perspective of the
-/// same side to move, allowing for consistent comparisons.
+/// This is the public entry point for the quiescence search used at the leaves of the
+/// alpha-beta search. It resolves captures (and optionally checks) so that the returned
+/// score is not distorted by pieces that are hanging, which makes it a more stable leaf
+/// value than the static evaluation.
 ///
 /// # Arguments
 ///
-/// * `board` - A mutable reference to the current board state
-/// * `move_gen` - A reference to the move generator
-/// * `pesto` - A reference to the Pesto evaluation function
-/// * `alpha` - The lower bound of the search window
-/// * `beta` - The upper bound of the search window
-/// * `eval_after_even_moves` - A flag indicating whether to evaluate after even or odd number of moves
-/// * `verbose` - A flag indicating whether to print verbose output
+/// * `board` - The position to search.
+/// * `move_gen` - A reference to the move generator.
+/// * `evaluator` - A reference to the position evaluator.
+/// * `alpha` - The lower bound of the search window.
+/// * `beta` - The upper bound of the search window.
+/// * `max_depth` - The maximum depth for quiescence search.
+/// * `mode` - Whether to search captures only, or captures and (at the first ply) checks.
 ///
 /// # Returns
 ///
 /// A tuple containing:
-/// * The evaluation (in centipawns) of the final position
-/// * The number of nodes searched
-///
-/// # Notes
-/// Interesting idea, but not used currently because it is too slow
-fn q_search_consistent_side_to_move_for_final_eval(board: &mut BoardStack, move_gen: &MoveGen, pesto: &PestoEval, mut alpha: i32, beta: i32, eval_after_even_moves: bool, verbose: bool) -> (i32, i32) {
-    let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(move_gen);
-    if checkmate {
-        if verbose {
-            println!("Qsearch: Quiescence: Checkmate!");
-        }
-        return (-1000000, 1);
-    } else if stalemate {
-        if verbose {
-            println!("Quiescence: Stalemate!");
-        }
-        return (0, 1);
-    }
-    // board.print();
-    if eval_after_even_moves {
-        // The problem here is that we are currently only comparing the eval at the end of the tactics, but
-        // sometimes the player to move might not want to play a capture, so we need to consider the stand pat eval too
-        // This side can either play a capture, or evaluate the position, whichever is better
-        let eval = pesto.eval(&board.current_state());
-        let captures = move_gen.gen_pseudo_legal_captures(&board.current_state());
-        if captures.is_empty() {
-            if verbose {
-                println!("Quiescence: No captures left! Eval: {}", eval);
-            }
-            (eval, 1)
-        } else {
-            if verbose {
-                println!("Stand pat eval: {}", eval);
-            }
-            if eval > alpha {
-                if eval >= beta {
-                    if verbose {
-                        println!("Quiescence: Stand pat eval is better than beta! Eval: {}", eval);
-                    }
-                    return (beta, 1); // Return beta here because player to move has reached a position that is better than beta, so opponent will never play this position
-                }
-                alpha = eval;
-            }
-            let mut n: i32 = 1;
-            for c in captures {
-                board.make_move(c);
-                if !board.current_state().is_legal(move_gen) {
-                    board.undo_move();
-                    continue;
-                }
-                let (mut score, nodes) = q_search_consistent_side_to_move_for_final_eval(board, move_gen, pesto, -beta, -alpha, !eval_after_even_moves, verbose);
-                score = -score;
-                if verbose {
-                    println!("Capture eval: {}", score);
-                }
-                n += nodes;
-                board.undo_move();
-                if score > alpha {
-                    alpha = score;
-                    if score >= beta {
-                        return (beta, n);
-                    }
-                }
-            }
-            (alpha, n)
-        }
-    } else {
-        // Other side simply plays best move
-        let (mut captures, moves) = move_gen.gen_pseudo_legal_moves_with_evals(&mut board.current_state(), pesto);
-        let mut n: i32 = 1;
-        captures.extend(moves);
-        for c in captures {
-            board.make_move(c);
-            if !board.current_state().is_legal(move_gen) {
-                board.undo_move();
-                continue;
-            }
-            let (mut score, nodes) = q_search_consistent_side_to_move_for_final_eval(board, move_gen, pesto, -beta, -alpha, !eval_after_even_moves, verbose);
-            score = -score;
-            if verbose {
-                println!("Other side eval: {}", score);
-            }
-            n += nodes;
-            if score > alpha {
-                alpha = score;
-            }
-            board.undo_move();
-            if alpha >= beta {
-                break;
-            }
-        }
-        (alpha, n)
-    }
+/// - The quiescent score of the position (from the perspective of the side to move).
+/// - The number of nodes searched.
+pub fn quiescence<E: Evaluator>(board: &Board, move_gen: &MoveGen, evaluator: &E, alpha: i32, beta: i32, max_depth: i32, mode: QuiescenceMode) -> (i32, i32) {
+    let mut board_stack = BoardStack::new_from_board(board.clone());
+    let limits = SearchLimits::new(None, None);
+    q_search(&mut board_stack, &SearchContext::new(move_gen, evaluator, &limits), alpha, beta, max_depth, None, mode)
 }
 
 /// Perform a mate search from the given position
 ///
-/// This function performs an iteratively deepening search for forced checkmates,
-/// where the side to move always gives check. It finds checkmates but does not
-/// find forced checkmates where the side to move does not give check, nor does
-/// it find forced stalemates or threefold repetitions.
+/// This function performs an iteratively deepening search for forced checkmates.
+/// In `ChecksOnly` mode the side to move always gives check, so it does not find
+/// forced checkmates where the side to move plays a quiet move; `Full` mode finds
+/// those too, at a much higher cost. It does not find forced stalemates or
+/// threefold repetitions.
 ///
 /// # Arguments
 ///
 /// * `board` - A reference to the current board state
 /// * `move_gen` - A reference to the move generator
 /// * `max_depth` - The maximum depth to search to
+/// * `mode` - Which moves the side to move may play
+/// * `limits` - The limits of the search. A depth that is stopped before it finishes finds no mate
 /// * `verbose` - A flag indicating whether to print verbose output
 ///
 /// # Returns
 ///
 /// A tuple containing:
-/// * The evaluation: 1000000 for checkmate, -1000000 for checkmate against, or 0 for neither
+/// * The evaluation: `MATE_SCORE - n` for a checkmate delivered `n` plies from now, or 0 if no mate was found
 /// * The best move to play from the current position
 /// * The number of nodes searched
-pub fn mate_search(board: &mut BoardStack, move_gen: &MoveGen, max_depth: i32, verbose: bool) -> (i32, Move, i32) {
+/// * The mating line, starting with the best move, if a checkmate was found
+pub fn mate_search(board: &mut BoardStack, move_gen: &MoveGen, max_depth: i32, mode: MateSearchMode, limits: &SearchLimits, verbose: bool) -> (i32, Move, i32, Vec<Move>) {
     let mut eval: i32 = 0;
     let mut best_move: Move = Move::null();
     let mut n: i32 = 0;
-    let mut alpha = -1000000;
-    let beta = 1000000;
+    let mut line: Vec<Move> = Vec::new();
+    let search = MateSearch { move_gen, mode, limits };
+    board.set_search_root();
 
     // Iterative deepening loop
     for d in 1..=max_depth {
@@ -749,9 +1559,14 @@ pub fn mate_search(board: &mut BoardStack, move_gen: &MoveGen, max_depth: i32, v
             println!("Performing mate search at depth {} ply", depth);
         }
 
-        // Generate and combine captures and regular moves
-        let (mut captures, moves) = move_gen.gen_pseudo_legal_moves(&mut board.current_state());
-        captures.extend(moves);
+        // Each depth is a fresh search, since a mate score from a shallower depth would have ended the search
+        let mut alpha = -MATE_SCORE;
+        let beta = MATE_SCORE;
+        let mut depth_best_move = Move::null();
+        let mut depth_line: Vec<Move> = Vec::new();
+
+        // Generate the checking moves, or all moves in full mode
+        let captures = mate_search_moves(board.current_state(), move_gen, mode == MateSearchMode::ChecksOnly);
 
         // Iterate through all moves
         for m in captures {
@@ -760,89 +1575,136 @@ pub fn mate_search(board: &mut BoardStack, move_gen: &MoveGen, max_depth: i32, v
                 board.undo_move();
                 continue;
             }
-            if !board.current_state().is_check(move_gen) {
-                board.undo_move();
-                continue;
-            }
-            let (score, nodes) = mate_search_recursive(board, move_gen, depth - 1, -beta, -alpha, false);
-            eval = -score;
+            let mut child_line = Vec::new();
+            let (score, nodes) = mate_search_recursive(board, &search, depth - 1, -beta, -alpha, false, &mut child_line);
+            let move_eval = -score;
             n += nodes;
-            if eval > alpha {
-                alpha = eval;
-            }
             board.undo_move();
-            if alpha >= beta {
-                best_move = m;
+            if move_eval > alpha {
+                alpha = move_eval;
+                depth_best_move = m;
+                depth_line = vec![m];
+                depth_line.extend(child_line);
+            }
+            // No shorter mate exists, or an earlier depth would have found it
+            if alpha > MATE_THRESHOLD {
                 break;
             }
         }
         if verbose{
-            println!("At depth {} ply, searched {} nodes. best eval {}", depth, n, eval);
+            println!("At depth {} ply, searched {} nodes. best eval {}", depth, n, alpha);
+        }
+        // Out of time or nodes: the unfinished depth proves nothing
+        if search.limits.stopped() {
+            break;
         }
         // If checkmate found, stop searching
-        if eval == 1000000 {
+        if alpha > MATE_THRESHOLD {
             if verbose{
                 println!("Mate search: Checkmate! No need to go deeper");
             }
+            eval = alpha;
+            best_move = depth_best_move;
+            line = depth_line;
             break;
         }
     }
-    (eval, best_move, n)
+    (eval, best_move, n, line)
+}
+
+/// The settings shared by every node of a mate search
+struct MateSearch<'a> {
+    /// The move generator
+    move_gen: &'a MoveGen,
+    /// Which moves the initial side may play
+    mode: MateSearchMode,
+    /// The limits of the search, checked at every node
+    limits: &'a SearchLimits,
+}
+
+/// Generates the moves to try in mate search: only checking moves, or all moves
+fn mate_search_moves(board: &Board, move_gen: &MoveGen, checks_only: bool) -> Vec<Move> {
+    if checks_only {
+        return move_gen.gen_checking_moves(board);
+    }
+    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+    captures.extend(moves);
+    captures
 }
 
 /// Recursive helper function for mate search
 ///
 /// This function performs a recursive mate search to the given depth, using alpha-beta pruning
-/// to optimize the search process. It only considers moves that give check.
+/// to optimize the search process. In `ChecksOnly` mode it only considers moves that give check
+/// for the initial side.
 ///
 /// # Arguments
 ///
 /// * `board` - A mutable reference to the current board state
-/// * `move_gen` - A reference to the move generator
+/// * `search` - The move generator, the moves the initial side may play and the limits of the search
 /// * `depth` - The current depth in the search tree
 /// * `alpha` - The current alpha value for alpha-beta pruning
 /// * `beta` - The current beta value for alpha-beta pruning
 /// * `side_to_move` - A boolean indicating which side is to move (true for the initial side)
+/// * `line` - Filled with the best line found from this position
 ///
 /// # Returns
 ///
 /// A tuple containing:
-/// * The evaluation: -1000000 for checkmate, 0 for no mate found
+/// * The evaluation: `-MATE_SCORE` plus the distance from the root for checkmate, 0 for no mate found
 /// * The number of nodes searched
-fn mate_search_recursive(board: &mut BoardStack, move_gen: &MoveGen, depth: i32, mut alpha: i32, beta: i32, side_to_move: bool) -> (i32, i32) {
+fn mate_search_recursive(board: &mut BoardStack, search: &MateSearch, depth: i32, mut alpha: i32, beta: i32, side_to_move: bool, line: &mut Vec<Move>) -> (i32, i32) {
+    let move_gen = search.move_gen;
     // Private recursive function used for mate search
     // External functions should call mate_search instead
     // Returns the eval (in centipawns) of the final position
     // Also returns number of nodes searched
+
+    // Stopped elsewhere in the tree: no mate is proven here
+    if search.limits.stopped() {
+        return (0, 0);
+    }
+    search.limits.add_nodes(1);
+
     if depth == 0 {
         // Leaf node
         // Check whether this is checkmate (could be either side)
-        let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(move_gen);
+        let (checkmate, _) = board.current_state().is_checkmate_or_stalemate(move_gen);
+        // Stalemate only happens in full mode, where the last move need not give check
         if checkmate {
-            return (-1000000, 1);
-        } else if stalemate {
-            panic!("Stalemate in mate search!");
+            return (-MATE_SCORE + board.ply(), 1);
         } else {
             return (0, 1);
         }
     }
+    // The fifty-move rule draws the game, unless the move that reached it gave checkmate
+    if board.current_state().halfmove_clock >= 100 {
+        let (checkmate, _) = board.current_state().is_checkmate_or_stalemate(move_gen);
+        return (if checkmate { -MATE_SCORE + board.ply() } else { 0 }, 1);
+    }
     // Non-leaf node
     let mut n: i32 = 1;
-    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves(&board.current_state());
-    captures.extend(moves);
+    let mut has_legal_move = false;
+    let checks_only = side_to_move && search.mode == MateSearchMode::ChecksOnly;
+    let captures = mate_search_moves(board.current_state(), move_gen, checks_only);
     for m in captures {
         board.make_move(m);
         if !board.current_state().is_legal(move_gen) {
             board.undo_move();
             continue;
         }
-        if side_to_move && !board.current_state().is_check(move_gen) {
-            board.undo_move();
-            continue;
-        }
-        let (mut eval, nodes) = mate_search_recursive(board, move_gen, depth - 1, -beta, -alpha, !side_to_move);
+        has_legal_move = true;
+        let mut child_line = Vec::new();
+        let (mut eval, nodes) = mate_search_recursive(board, search, depth - 1, -beta, -alpha, !side_to_move, &mut child_line);
         eval = -eval;
         n += nodes;
+        // Keep the first line when no move improves on alpha, so that a defender who is mated
+        // whatever they do still has a line to report
+        if eval > alpha || line.is_empty() {
+            line.clear();
+            line.push(m);
+            line.extend(child_line);
+        }
         if eval > alpha {
             alpha = eval;
         }
@@ -851,5 +1713,16 @@ fn mate_search_recursive(board: &mut BoardStack, move_gen: &MoveGen, depth: i32,
             break;
         }
     }
+    // Without a checking move, the side may still have quiet moves
+    if checks_only && !has_legal_move {
+        let (checkmate, stalemate) = board.current_state().is_checkmate_or_stalemate(move_gen);
+        has_legal_move = !checkmate && !stalemate;
+    }
+
+    // Checkmate or stalemate before the search depth was reached
+    if !has_legal_move {
+        let score = if board.current_state().is_check(move_gen) { -MATE_SCORE + board.ply() } else { 0 };
+        return (score, n);
+    }
     (alpha, n)
 }
\ No newline at end of file
@@ -0,0 +1,155 @@
+//! Tactical test positions with known best moves.
+//!
+//! The positions come from an embedded suite in EPD format, where the `bm` opcode gives the best
+//! move in SAN. Every position is checked when it is loaded: the FEN must describe a legal
+//! position and the best move must be a legal move in it, so a typo in the suite is reported
+//! rather than silently producing a position without a solution.
+
+use std::fmt;
+use crate::board::{Board, FenError};
+use crate::move_generation::MoveGen;
+use crate::move_types::Move;
+
+/// The default tactical suite in EPD format.
+///
+/// The composed positions at the top are short mates and one-move material wins. Their
+/// solutions are checked by the tests without the evaluation: the mates by move generation and
+/// an exhaustive mate search. The rest are positions from Fred Reinfeld's "Win at Chess" (1958)
+/// with the best moves published in the widely used WAC EPD suite. They are an outside
+/// reference to measure the engine against.
+pub const TACTICAL_SUITE: &str = r#"
+# Composed: mates in one
+r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - bm Qxf7#; id "scholars mate";
+6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8#; id "back rank";
+3r2k1/5ppp/8/8/8/8/5PPP/6K1 b - - bm Rd1#; id "back rank, black";
+6rk/6pp/7N/8/8/8/8/6K1 w - - bm Nf7#; id "smothered mate";
+# Composed: mate in three
+3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - bm Rh8+; id "doubled rooks";
+# Composed: material
+r3k2r/ppp2ppp/2n5/3q4/3P4/2N5/PPP2PPP/R2QK2R w KQkq - bm Nxd5; id "hanging queen";
+4k3/8/8/3q4/8/8/8/3QK3 w - - bm Qxd5; id "queen trade down";
+3qk3/8/8/8/3Q4/8/8/4K3 b - - bm Qxd4; id "queen trade down, black";
+r3k3/8/8/1N6/8/8/8/4K3 w - - bm Nc7+; id "knight fork";
+4k3/4qp2/8/8/8/8/4B3/4R1K1 w - - bm Bb5+; id "discovered attack";
+8/4P1k1/8/8/8/8/8/4K3 w - - bm e8=Q; id "promotion";
+# Win at Chess (Reinfeld, 1958)
+2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+8/7p/5k2/5p2/p1p2P2/Pr1pPK2/1P1R3P/8 b - - bm Rxb2; id "WAC.002";
+5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - bm Rg3; id "WAC.003";
+r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - bm Qxh7+; id "WAC.004";
+5k2/6pp/p1qN4/1p1p4/3P4/2PKP2Q/PP3r2/3R4 b - - bm Qc4+; id "WAC.005";
+7k/p7/1R5K/6r1/6p1/6P1/8/8 w - - bm Rb7; id "WAC.006";
+rnbqkb1r/pppp1ppp/8/4P3/6n1/7P/PPPNPPP1/R1BQKBNR b KQkq - bm Ne3; id "WAC.007";
+r4q1k/p2bR1rp/2p2Q1N/5p2/5p2/2P5/PP3PPP/R5K1 w - - bm Rf7; id "WAC.008";
+3q1rk1/p4pp1/2pb3p/3p4/6Pr/1PNQ4/P1PB1PP1/4RRK1 b - - bm Bh2+; id "WAC.009";
+r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1 w kq - bm Bxc6; id "WAC.011";
+4k1r1/2p3r1/1pR1p3/3pP2p/3P2qP/P4N2/1PQ4P/5R1K b - - bm Qxf3+; id "WAC.012";
+5rk1/pp4p1/2n1p2p/2Npq3/2p5/6P1/P3P1BP/R4Q1K w - - bm Qxf8+; id "WAC.013";
+r2rb1k1/pp1q1p1p/2n1p1p1/2bp4/5P2/PP1BPR1Q/1BPN2PP/R5K1 w - - bm Qxh7+; id "WAC.014";
+1R6/1brk2p1/4p2p/p1P1Pp2/P7/6P1/1P4P1/2R3K1 w - - bm Rxb7; id "WAC.015";
+r4rk1/ppp2ppp/2n5/2bqp3/8/P2PB3/1PP1NPPP/R2Q1RK1 w - - bm Nc3; id "WAC.016";
+1k5r/pppbn1pp/4q1r1/1P3p2/2NPp3/1QP5/P4PPP/R1B1R1K1 w - - bm Ne5; id "WAC.017";
+R7/P4k2/8/8/8/8/r7/6K1 w - - bm Rh8; id "WAC.018";
+r1b2rk1/ppbn1ppp/4p3/1QP4q/3P4/N4N2/5PPP/R1B2RK1 w - - bm c6; id "WAC.019";
+r2qkb1r/1ppb1ppp/p7/4p3/P1Q1P3/2P5/5PPP/R1B2KNR b kq - bm Bb5; id "WAC.020";
+"#;
+
+/// A position with a known best move.
+#[derive(Clone)]
+pub struct TacticalPosition {
+    /// The position.
+    pub board: Board,
+    /// The best move, which is legal in the position.
+    pub best_move: Move,
+    /// The name of the position from the `id` opcode, or an empty string.
+    pub id: String,
+}
+
+/// The reasons a line of a tactical suite can be rejected, with the line number (from 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TacticsError {
+    /// The position is malformed or illegal.
+    Fen(usize, FenError),
+    /// The line has no `bm` opcode.
+    MissingBestMove(usize),
+    /// The best move is not a legal move in the position.
+    IllegalBestMove(usize, String),
+}
+
+impl fmt::Display for TacticsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TacticsError::Fen(line, e) => write!(f, "line {}: {}", line, e),
+            TacticsError::MissingBestMove(line) => write!(f, "line {}: no best move", line),
+            TacticsError::IllegalBestMove(line, san) => write!(f, "line {}: best move '{}' is not legal", line, san),
+        }
+    }
+}
+
+impl std::error::Error for TacticsError {}
+
+/// Returns the positions of the default tactical suite.
+///
+/// # Arguments
+///
+/// * `move_gen` - A reference to the move generator, used to check the best moves.
+pub fn tactical_positions(move_gen: &MoveGen) -> Vec<TacticalPosition> {
+    parse_tactics(TACTICAL_SUITE, move_gen).expect("the default tactical suite is valid")
+}
+
+/// Parses a tactical suite in EPD format, with one position per line.
+///
+/// Blank lines and lines starting with `#` are skipped. Each line has the four position fields
+/// of a FEN, followed by opcodes separated by `;`. The `bm` opcode gives the best move in SAN,
+/// and the optional `id` opcode names the position; other opcodes are ignored. Check and
+/// annotation marks on the best move are optional.
+///
+/// # Arguments
+///
+/// * `text` - The contents of the suite.
+/// * `move_gen` - A reference to the move generator, used to check the best moves.
+///
+/// # Returns
+///
+/// The positions in the suite, or the error for the first line that is malformed or whose
+/// best move is missing or illegal.
+pub fn parse_tactics(text: &str, move_gen: &MoveGen) -> Result<Vec<TacticalPosition>, TacticsError> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| parse_line(number, line, move_gen))
+        .collect()
+}
+
+/// Parses one EPD line of a tactical suite.
+fn parse_line(number: usize, line: &str, move_gen: &MoveGen) -> Result<TacticalPosition, TacticsError> {
+    let fields: Vec<&str> = line.splitn(5, char::is_whitespace).collect();
+    let fen = format!("{} 0 1", fields[..fields.len().min(4)].join(" "));
+    let board = Board::try_from_fen_legal(&fen, move_gen).map_err(|e| TacticsError::Fen(number, e))?;
+
+    let mut best_move = None;
+    let mut id = String::new();
+    for operation in fields.get(4).unwrap_or(&"").split(';').map(str::trim) {
+        let (opcode, operand) = operation.split_once(char::is_whitespace).unwrap_or((operation, ""));
+        match opcode {
+            "bm" => best_move = Some(operand.trim()),
+            "id" => id = operand.trim().trim_matches('"').to_string(),
+            _ => {}
+        }
+    }
+
+    let san = best_move.filter(|san| !san.is_empty()).ok_or(TacticsError::MissingBestMove(number))?;
+    let best_move = find_san_move(&board, san, move_gen).ok_or_else(|| TacticsError::IllegalBestMove(number, san.to_string()))?;
+    Ok(TacticalPosition { board, best_move, id })
+}
+
+/// Finds the legal move written in SAN, ignoring check and annotation marks.
+fn find_san_move(board: &Board, san: &str, move_gen: &MoveGen) -> Option<Move> {
+    let strip = |san: &str| san.trim_end_matches(['+', '#', '!', '?']).to_string();
+    let san = strip(san);
+    let (captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+    captures.into_iter().chain(moves)
+        .filter(|&m| board.apply_move_to_board(m).is_legal(move_gen))
+        .find(|&m| strip(&board.move_to_san(m, move_gen)) == san)
+}
@@ -2,32 +2,81 @@
 //!
 //! This module implements a transposition table, which is used to cache and retrieve
 //! information about previously analyzed chess positions, improving search efficiency.
+//!
+//! The table has a fixed number of buckets of `BUCKET_SIZE` entries, indexed by the Zobrist
+//! hash of the position, and can be kept across the searches of a game. Each search starts a
+//! new generation with `new_search`, so that entries left over from earlier moves are the first
+//! to be replaced. Within a bucket the shallowest entry is replaced, so deep results survive
+//! collisions with the many shallow positions near the leaves.
 
-use std::collections::HashMap;
 use crate::board::Board;
 use crate::move_types::Move;
 
 /// Represents an entry in the transposition table.
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct TranspositionEntry {
+    /// The full Zobrist hash of the position, to detect index collisions.
+    pub(crate) hash: u64,
     /// The depth at which this position was searched.
     pub(crate) depth: i32,
     /// The evaluation score for this position.
     pub(crate) score: i32,
     /// The best move found for this position.
     pub(crate) best_move: Move,
+    /// The search generation in which this entry was stored.
+    pub(crate) generation: u8,
 }
 
+/// The default maximum number of entries in a transposition table.
+pub const DEFAULT_TT_CAPACITY: usize = 1 << 18;
+
+/// The number of entries in each bucket of the table.
+pub const BUCKET_SIZE: usize = 4;
+
+/// The number of slots sampled by `hashfull`.
+const HASHFULL_SAMPLE: usize = 1000;
+
 /// A transposition table for caching chess positions and their evaluations.
 pub struct TranspositionTable {
-    /// The underlying hash map storing positions and their corresponding entries.
-    table: HashMap<u64, TranspositionEntry>,
+    /// The buckets of the table, indexed by the position's hash modulo the number of buckets.
+    table: Vec<[Option<TranspositionEntry>; BUCKET_SIZE]>,
+    /// The current search generation.
+    generation: u8,
 }
 
 impl TranspositionTable {
-    /// Creates a new transposition table.
+    /// Creates a new transposition table with the default capacity.
     pub fn new() -> Self {
-        TranspositionTable { table: HashMap::new() }
+        TranspositionTable::with_capacity(DEFAULT_TT_CAPACITY)
+    }
+
+    /// Creates a new transposition table holding at least `capacity` entries, rounded up to
+    /// a whole number of buckets.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let buckets = capacity.div_ceil(BUCKET_SIZE).max(1);
+        TranspositionTable { table: vec![Default::default(); buckets], generation: 0 }
+    }
+
+    /// Starts a new search generation.
+    ///
+    /// Entries stored during earlier searches stay available for probing, but are replaced
+    /// in preference to entries from the current search.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Reports how full the table is, in permille, as used by the UCI `hashfull` field.
+    ///
+    /// Only entries from the current generation are counted, based on a sample of the buckets.
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.table[..self.table.len().min(HASHFULL_SAMPLE / BUCKET_SIZE)];
+        let used = sample.iter().flatten().filter(|slot| matches!(slot, Some(entry) if entry.generation == self.generation)).count();
+        used * 1000 / (sample.len() * BUCKET_SIZE)
+    }
+
+    /// Returns the bucket index for a hash.
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.table.len() as u64) as usize
     }
 
     /// Checks the table for a given board position and search depth.
@@ -42,23 +91,18 @@ impl TranspositionTable {
     /// An `Option` containing a reference to the `TranspositionEntry` if found and the stored depth
     /// is greater than or equal to the current depth, otherwise `None`.
     pub fn probe(&self, board: &Board, depth: i32) -> Option<&TranspositionEntry> {
-        // Check the table for a given board position and search depth
-        // If it exists, return a reference to the entry
-        // Else, return None
-        let out = self.table.get(&board.zobrist_hash);
-        if out == None {
-            return None;
-        }
-        let entry = out.unwrap();
-        if entry.depth >= depth {
-            Some(entry)
-        } else {
-            None
-        }
+        self.table[self.index(board.zobrist_hash)].iter().flatten()
+            .find(|entry| entry.hash == board.zobrist_hash)
+            .filter(|entry| entry.depth >= depth)
     }
 
     /// Adds a position to the transposition table or updates an existing entry.
     ///
+    /// An existing entry for the same position is only replaced by a deeper search, unless it
+    /// is from an earlier generation. Otherwise the new entry goes into an empty slot of the
+    /// bucket or replaces the least valuable entry: one from an earlier generation if there is
+    /// one, and the shallowest among those.
+    ///
     /// # Arguments
     ///
     /// * `board` - A reference to the `Bitboard` position to store.
@@ -66,21 +110,31 @@ impl TranspositionTable {
     /// * `score` - The evaluation score for this position.
     /// * `best_move` - The best move found for this position, if any.
     pub fn store(&mut self, board: &Board, depth: i32, score: i32, best_move: Move) {
-        // Add a position to the table
-        // If the position already exists, update it if the depth is greater
-        let entry = self.table.get(&board.zobrist_hash);
-        if entry == None {
-            self.table.insert(board.zobrist_hash, TranspositionEntry {depth, score, best_move});
-        } else {
-            let entry = entry.unwrap();
-            if depth > entry.depth {
-                self.table.insert(board.zobrist_hash, TranspositionEntry {depth, score, best_move});
+        let hash = board.zobrist_hash;
+        let generation = self.generation;
+        let index = self.index(hash);
+        let bucket = &mut self.table[index];
+        let new_entry = Some(TranspositionEntry { hash, depth, score, best_move, generation });
+
+        // Same position: keep the deeper search from this generation
+        if let Some(slot) = bucket.iter_mut().find(|slot| matches!(slot, Some(entry) if entry.hash == hash)) {
+            let entry = slot.as_ref().unwrap();
+            if entry.generation != generation || depth > entry.depth {
+                *slot = new_entry;
             }
+            return;
         }
+
+        // Otherwise use an empty slot, or replace the least valuable entry
+        let victim = bucket.iter_mut().min_by_key(|slot| match slot {
+            None => (0, i32::MIN),
+            Some(entry) => (if entry.generation == generation { 2 } else { 1 }, entry.depth),
+        }).unwrap();
+        *victim = new_entry;
     }
 
     /// Clears the transposition table.
     pub fn clear(&mut self) {
-        self.table.clear();
+        self.table.iter_mut().for_each(|bucket| *bucket = Default::default());
     }
-}
\ No newline at end of file
+}
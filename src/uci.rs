@@ -3,17 +3,63 @@
 //! This module implements the UCI interface for the chess engine. It allows the user to interact with the engine using UCI commands.
 
 use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
 use std::time::{Duration, Instant};
 use crate::boardstack::BoardStack;
 use crate::eval::PestoEval;
-use crate::move_types::Move;
 use crate::move_generation::MoveGen;
-use crate::search::iterative_deepening_ab_search;
+use crate::move_types::Move;
+use crate::search::{allocate_time, iterative_deepening_ab_search, SearchContext, SearchLimits};
+use crate::transposition::TranspositionTable;
+
+/// Default depth for the `bench` command.
+pub const BENCH_DEPTH: i32 = 5;
+
+/// Positions searched by the `bench` command.
+const BENCH_FENS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+];
+
+/// The parameters of the `go` command, which end the move list of `searchmoves`.
+const GO_PARAMETERS: [&str; 12] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc", "movestogo", "depth", "nodes", "mate", "movetime", "infinite",
+];
+
+/// The result of searching one `bench` position.
+pub struct BenchResult {
+    /// The position searched.
+    pub fen: &'static str,
+    /// The number of nodes searched.
+    pub nodes: u64,
+    /// The time taken by the search.
+    pub time: Duration,
+    /// The time taken to complete each depth that iterative deepening searches, from an empty
+    /// transposition table.
+    pub depth_times: Vec<(i32, Duration)>,
+}
+
+impl BenchResult {
+    /// Returns the search speed in nodes per second.
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(0.001)) as u64
+    }
+}
 
 pub struct UCIEngine {
     board: BoardStack,
     move_gen: MoveGen,
     pesto: PestoEval,
+    tt: TranspositionTable,
     time_left: Duration,
     increment: Duration,
     moves_to_go: Option<u32>,
@@ -21,6 +67,12 @@ pub struct UCIEngine {
     nodes: Option<u64>,
     mate: Option<i32>,
     movetime: Option<Duration>,
+    infinite: bool,
+    search_moves: Vec<Move>,
+    contempt: i32,
+    analyse_mode: bool,
+    last_score: Option<i32>,
+    stop: Arc<AtomicBool>,
 }
 
 impl UCIEngine {
@@ -29,6 +81,7 @@ impl UCIEngine {
             board: BoardStack::new(),
             move_gen: MoveGen::new(),
             pesto: PestoEval::new(),
+            tt: TranspositionTable::new(),
             time_left: Duration::from_secs(0),
             increment: Duration::from_secs(0),
             moves_to_go: None,
@@ -36,73 +89,232 @@ impl UCIEngine {
             nodes: None,
             mate: None,
             movetime: None,
+            infinite: false,
+            search_moves: Vec::new(),
+            contempt: 0,
+            analyse_mode: false,
+            last_score: None,
+            stop: Arc::new(AtomicBool::new(false)),
         }
     }
 
     pub fn run(&mut self) {
-        let stdin = io::stdin();
-        for line in stdin.lock().lines() {
-            let input = line.unwrap();
-            let tokens: Vec<&str> = input.split_whitespace().collect();
-
-            if tokens.is_empty() {
-                continue;
+        // Read input on its own thread, so that commands can arrive while searching
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                if sender.send(line.unwrap()).is_err() {
+                    break;
+                }
             }
+        });
 
-            match tokens[0] {
-                "uci" => {
-                    println!("id name Kingfisher");
-                    println!("id author Adam Holmes");
-                    println!("uciok");
-                },
-                "isready" => println!("readyok"),
-                "ucinewgame" => self.board = BoardStack::new(),
-                "position" => self.handle_position(&tokens[1..]),
-                "go" => self.handle_go(&tokens[1..]),
-                "quit" => break,
-                _ => println!("Unknown command: {}", tokens[0]),
+        while let Ok(input) = receiver.recv() {
+            let running = self.run_command(&input, &receiver);
+            io::stdout().flush().unwrap();
+            if !running {
+                break;
             }
+        }
+    }
 
-            io::stdout().flush().unwrap();
+    /// Runs a command from the command loop, searching on a separate thread for `go`.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the engine should quit, `true` otherwise.
+    fn run_command(&mut self, input: &str, receiver: &mpsc::Receiver<String>) -> bool {
+        if input.split_whitespace().next() == Some("go") {
+            self.run_search(input, receiver)
+        } else {
+            self.handle_command(input)
         }
     }
 
-    fn handle_position(&mut self, args: &[&str]) {
-        if args.is_empty() {
-            return;
+    /// Runs a `go` command on a separate thread, handling `stop`, `isready` and `quit` while the
+    /// search is running. Other commands wait until the search has finished.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the engine should quit, `true` otherwise.
+    fn run_search(&mut self, go_command: &str, receiver: &mpsc::Receiver<String>) -> bool {
+        // A `stop` sent before this search started is stale. Clear it before the search thread
+        // exists, so that a `stop` arriving while it starts up is not lost
+        let stop = self.stop_flag();
+        stop.store(false, Ordering::Relaxed);
+        let mut pending = Vec::new();
+        let mut quit = false;
+        thread::scope(|scope| {
+            let search = scope.spawn(|| self.handle_command(go_command));
+            while !search.is_finished() {
+                match receiver.recv_timeout(Duration::from_millis(10)) {
+                    Ok(input) => match input.trim() {
+                        "stop" => stop.store(true, Ordering::Relaxed),
+                        "isready" => println!("readyok"),
+                        "quit" => {
+                            stop.store(true, Ordering::Relaxed);
+                            quit = true;
+                        },
+                        _ => pending.push(input),
+                    },
+                    Err(RecvTimeoutError::Timeout) => {},
+                    // The input was closed, so no `stop` can arrive: end the search and quit
+                    Err(RecvTimeoutError::Disconnected) => {
+                        stop.store(true, Ordering::Relaxed);
+                        quit = true;
+                        break;
+                    },
+                }
+            }
+        });
+        if quit {
+            return false;
         }
+        pending.iter().all(|input| self.run_command(input, receiver))
+    }
 
-        if args[0] == "startpos" {
-            self.board = BoardStack::new();
-            let mut moves_idx = 2;
+    /// Handles a single line of UCI input.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The command line, e.g. "position startpos moves e2e4".
+    ///
+    /// # Returns
+    ///
+    /// `false` if the engine should quit, `true` otherwise.
+    pub fn handle_command(&mut self, input: &str) -> bool {
+        let tokens: Vec<&str> = input.split_whitespace().collect();
 
-            if args.len() > 1 && args[1] == "moves" {
-                for move_str in &args[moves_idx..] {
-                    if let Some(chess_move) = Move::from_uci(move_str) {
-                        self.board.make_move(chess_move);
-                    }
+        if tokens.is_empty() {
+            return true;
+        }
+
+        match tokens[0] {
+            "uci" => {
+                println!("id name Kingfisher");
+                println!("id author Adam Holmes");
+                println!("option name Contempt type spin default 0 min -1000 max 1000");
+                println!("option name UCI_AnalyseMode type check default false");
+                println!("uciok");
+            },
+            "isready" => println!("readyok"),
+            "setoption" => self.handle_setoption(&tokens[1..]),
+            "ucinewgame" => self.new_game(),
+            "position" => {
+                if let Err(e) = self.handle_position(&tokens[1..]) {
+                    println!("info string {}", e);
                 }
+            },
+            "go" => self.handle_go(&tokens[1..]),
+            "stop" => self.stop.store(true, Ordering::Relaxed),
+            "bench" => self.handle_bench(&tokens[1..]),
+            "quit" => return false,
+            _ => println!("Unknown command: {}", tokens[0]),
+        }
+        true
+    }
+
+    /// Forgets everything learned during the previous game: the position, the transposition
+    /// table and the last search score. Options set with `setoption` are kept.
+    fn new_game(&mut self) {
+        self.board = BoardStack::new();
+        self.tt.clear();
+        self.last_score = None;
+        self.stop.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns the transposition table kept between searches.
+    pub fn transposition_table(&self) -> &TranspositionTable {
+        &self.tt
+    }
+
+    /// Returns the flag that stops the current search, so that it can be stopped from another
+    /// thread.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Returns the score of the last search, from the point of view of the side to move.
+    pub fn last_score(&self) -> Option<i32> {
+        self.last_score
+    }
+
+    /// Returns the contempt used by the search.
+    ///
+    /// In analysis mode (`UCI_AnalyseMode`) there is no contempt, so that draws are scored as 0
+    /// and the scores reflect the position rather than the engine's wish to avoid draws. The
+    /// search limits of `go` still apply.
+    pub fn search_contempt(&self) -> i32 {
+        if self.analyse_mode { 0 } else { self.contempt }
+    }
+
+    /// Returns the position that the next `go` command will search.
+    pub fn board(&self) -> &BoardStack {
+        &self.board
+    }
+
+    /// Sets up the position from `startpos` or `fen <fen>`, then plays any moves after `moves`.
+    ///
+    /// Each move must be legal in the position it is played from. On an illegal or malformed
+    /// move the remaining moves are ignored, leaving the board at the last legal position.
+    fn handle_position(&mut self, args: &[&str]) -> Result<(), String> {
+        // Find the index where "moves" starts, if present
+        let moves_idx = args.iter().position(|&x| x == "moves").unwrap_or(args.len());
+
+        match args.first() {
+            Some(&"startpos") => self.board = BoardStack::new(),
+            Some(&"fen") => {
+                // Join the FEN parts
+                let fen = args[1..moves_idx].join(" ");
+                self.board = BoardStack::try_from_fen(&fen)
+                    .map_err(|e| format!("Invalid FEN: {}", e))?;
+            },
+            _ => return Err("Invalid position command".to_string()),
+        }
+
+        // Apply moves if present
+        for move_str in args.iter().skip(moves_idx + 1) {
+            match self.find_legal_move(move_str) {
+                Some(chess_move) => self.board.make_move(chess_move),
+                None => return Err(format!("Illegal move: {}", move_str)),
             }
-        } else if args[0] == "fen" {
-            // Find the index where "moves" starts, if present
-            let moves_idx = args.iter().position(|&x| x == "moves").unwrap_or(args.len());
+        }
+        Ok(())
+    }
+
+    /// Finds the legal move in the current position matching a move in UCI notation.
+    fn find_legal_move(&self, move_str: &str) -> Option<Move> {
+        let uci_move = Move::from_uci(move_str)?;
+        let board = self.board.current_state();
+        let (captures, moves) = self.move_gen.gen_pseudo_legal_moves(board);
+        captures.into_iter().chain(moves).find(|m| {
+            m.from == uci_move.from && m.to == uci_move.to && m.promotion == uci_move.promotion &&
+                board.apply_move_to_board(*m).is_legal(&self.move_gen)
+        })
+    }
 
-            // Join the FEN parts
-            let fen = args[1..moves_idx].join(" ");
+    fn handle_setoption(&mut self, args: &[&str]) {
+        // Expected form: name <id> value <x>
+        let name_idx = args.iter().position(|&x| x == "name");
+        let value_idx = args.iter().position(|&x| x == "value");
 
-            // Create a new board from the FEN
-            self.board = BoardStack::new_from_fen(&fen);
+        if let (Some(name_idx), Some(value_idx)) = (name_idx, value_idx) {
+            let name = args[name_idx + 1..value_idx].join(" ");
+            let value = args[value_idx + 1..].join(" ");
 
-            // Apply moves if present
-            if moves_idx < args.len() {
-                for move_str in &args[moves_idx + 1..] {
-                    if let Some(chess_move) = Move::from_uci(move_str) {
-                        self.board.make_move(chess_move);
+            match name.to_lowercase().as_str() {
+                "contempt" => {
+                    if let Ok(contempt) = value.parse::<i32>() {
+                        self.contempt = contempt.clamp(-1000, 1000);
                     }
-                }
+                },
+                "uci_analysemode" => {
+                    if let Ok(analyse_mode) = value.to_lowercase().parse::<bool>() {
+                        self.analyse_mode = analyse_mode;
+                    }
+                },
+                _ => println!("info string Unknown option: {}", name),
             }
-        } else {
-            println!("info string Invalid position command");
         }
     }
 
@@ -110,30 +322,102 @@ impl UCIEngine {
         self.parse_go_command(args);
 
         let allocated_time = self.calculate_allocated_time();
-        let start_time = Instant::now();
 
         let max_depth = self.depth.unwrap_or(100);
 
-        let (depth, score, current_best_move, nodes) = iterative_deepening_ab_search(
-            &mut self.board,
-            &self.move_gen,
-            &self.pesto,
-            max_depth,
-            4,
-            Some(allocated_time),
-            false
-        );
+        let limits = SearchLimits::with_stop_flag(allocated_time, self.nodes, self.stop_flag())
+            .with_root_moves(self.search_moves.clone())
+            .with_info_sink(Box::new(|info| println!("{}", info.to_uci())));
+        let ctx = SearchContext::new(&self.move_gen, &self.pesto, &limits).with_contempt(self.search_contempt());
 
-        let elapsed = start_time.elapsed();
+        // The info sink prints an info line for each completed depth
+        let (_depth, score, current_best_move, _nodes) = iterative_deepening_ab_search(&mut self.board, &mut self.tt, &ctx, max_depth);
+        self.last_score = Some(score);
 
         // Update best move
         let best_move = current_best_move;
 
-        // Print info
-        println!("info depth {} score cp {} nodes {} time {} pv {}",
-                 depth, score, nodes, elapsed.as_millis(), &best_move.print_algebraic());
+        println!("bestmove {}", best_move.to_uci());
+    }
+
+    fn handle_bench(&mut self, args: &[&str]) {
+        let depth = args.first().and_then(|d| d.parse().ok()).unwrap_or(BENCH_DEPTH);
+        let results = self.bench_positions(depth);
+        println!();
+        for (i, result) in results.iter().enumerate() {
+            let depth_times = result.depth_times.iter()
+                .map(|(d, time)| format!("{}:{}", d, time.as_millis()))
+                .collect::<Vec<String>>()
+                .join(" ");
+            println!("Position {:2}: nodes {:9} time {:6} ms nps {:9} depth:ms {}",
+                     i + 1, result.nodes, result.time.as_millis(), result.nps(), depth_times);
+        }
+        let nodes: u64 = results.iter().map(|r| r.nodes).sum();
+        let elapsed: Duration = results.iter().map(|r| r.time).sum();
+        let nps = (nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+        println!("Nodes searched  : {}", nodes);
+        println!("Time (ms)       : {}", elapsed.as_millis());
+        println!("Nodes/second    : {}", nps);
+    }
+
+    /// Searches a fixed set of positions to a fixed depth and counts the nodes.
+    ///
+    /// The search is deterministic, so the node count serves as a signature for detecting
+    /// unintended changes to search behaviour. The transposition table is cleared before
+    /// each position, and the engine's own table is not used.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth to search each position to.
+    ///
+    /// # Returns
+    ///
+    /// The total number of nodes searched over all positions.
+    pub fn bench(&self, depth: i32) -> u64 {
+        let mut total_nodes: u64 = 0;
+        let mut tt = TranspositionTable::new();
+        for fen in BENCH_FENS {
+            total_nodes += self.bench_search(fen, depth, &mut tt);
+        }
+        total_nodes
+    }
+
+    /// Searches the `bench` positions like `bench`, and also measures the speed of each search.
+    ///
+    /// The time to reach each depth is measured with a separate search to that depth, which
+    /// roughly doubles the running time compared to `bench`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth to search each position to.
+    ///
+    /// # Returns
+    ///
+    /// The nodes, time and time to each depth for every position.
+    pub fn bench_positions(&self, depth: i32) -> Vec<BenchResult> {
+        let mut tt = TranspositionTable::new();
+        BENCH_FENS.iter().map(|&fen| {
+            // Iterative deepening completes the even depths below the maximum, then the maximum
+            let mut depth_times = Vec::new();
+            for d in (2..depth).step_by(2) {
+                let start_time = Instant::now();
+                self.bench_search(fen, d, &mut tt);
+                depth_times.push((d, start_time.elapsed()));
+            }
+            let start_time = Instant::now();
+            let nodes = self.bench_search(fen, depth, &mut tt);
+            let time = start_time.elapsed();
+            depth_times.push((depth, time));
+            BenchResult { fen, nodes, time, depth_times }
+        }).collect()
+    }
 
-        println!("bestmove {}", &best_move.print_algebraic());
+    /// Searches one position to the given depth from a cleared table and returns the node count.
+    fn bench_search(&self, fen: &str, depth: i32, tt: &mut TranspositionTable) -> u64 {
+        let mut board = BoardStack::new_from_fen(fen);
+        tt.clear();
+        let (_, _, _, nodes) = iterative_deepening_ab_search(&mut board, tt, &SearchContext::new(&self.move_gen, &self.pesto, &SearchLimits::new(None, None)), depth);
+        nodes as u64
     }
 
     fn parse_go_command(&mut self, args: &[&str]) {
@@ -144,44 +428,64 @@ impl UCIEngine {
         self.nodes = None;
         self.mate = None;
         self.movetime = None;
+        self.infinite = false;
+        self.search_moves.clear();
 
         let mut i = 0;
         while i < args.len() {
-            match args[i] {
-                "wtime" | "btime" => {
+            // A parameter missing its value at the end of the command is skipped
+            let value = args.get(i + 1);
+            match (args[i], value) {
+                ("wtime" | "btime", Some(value)) => {
                     if (args[i] == "wtime" && self.board.current_state().w_to_move) ||
                         (args[i] == "btime" && !self.board.current_state().w_to_move) {
-                        self.time_left = Duration::from_millis(args[i + 1].parse().unwrap_or(0));
+                        self.time_left = Duration::from_millis(value.parse().unwrap_or(0));
                     }
                     i += 2;
                 },
-                "winc" | "binc" => {
+                ("winc" | "binc", Some(value)) => {
                     if (args[i] == "winc" && self.board.current_state().w_to_move) ||
                         (args[i] == "binc" && !self.board.current_state().w_to_move) {
-                        self.increment = Duration::from_millis(args[i + 1].parse().unwrap_or(0));
+                        self.increment = Duration::from_millis(value.parse().unwrap_or(0));
                     }
                     i += 2;
                 },
-                "movestogo" => {
-                    self.moves_to_go = Some(args[i + 1].parse().unwrap_or(30));
+                ("movestogo", Some(value)) => {
+                    // At least one move is left to play, which also keeps the time per move finite
+                    self.moves_to_go = Some(value.parse().unwrap_or(30).max(1));
                     i += 2;
                 },
-                "depth" => {
-                    self.depth = Some(args[i + 1].parse().unwrap_or(100));
+                ("depth", Some(value)) => {
+                    self.depth = Some(value.parse().unwrap_or(100));
                     i += 2;
                 },
-                "nodes" => {
-                    self.nodes = Some(args[i + 1].parse().unwrap_or(0));
+                ("nodes", Some(value)) => {
+                    self.nodes = Some(value.parse().unwrap_or(0));
                     i += 2;
                 },
-                "mate" => {
-                    self.mate = Some(args[i + 1].parse().unwrap_or(0));
+                ("mate", Some(value)) => {
+                    self.mate = Some(value.parse().unwrap_or(0));
                     i += 2;
                 },
-                "movetime" => {
-                    self.movetime = Some(Duration::from_millis(args[i + 1].parse().unwrap_or(0)));
+                ("movetime", Some(value)) => {
+                    self.movetime = Some(Duration::from_millis(value.parse().unwrap_or(0)));
                     i += 2;
                 },
+                ("infinite", _) => {
+                    self.infinite = true;
+                    i += 1;
+                },
+                ("searchmoves", _) => {
+                    // The moves run until the next parameter
+                    i += 1;
+                    while i < args.len() && !GO_PARAMETERS.contains(&args[i]) {
+                        match self.find_legal_move(args[i]) {
+                            Some(m) => self.search_moves.push(m),
+                            None => println!("info string Ignoring illegal search move: {}", args[i]),
+                        }
+                        i += 1;
+                    }
+                },
                 _ => i += 1,
             }
         }
@@ -189,22 +493,26 @@ impl UCIEngine {
 
     /// This function calculates the allocated time for a chess move based on the time control settings.
     ///
-    /// Formula: time left per move until time control is reached (or 5% of time left if no time
-    /// control is specified) + 50% of increment
-    /// Defaults to 5 seconds if no time control is specified.
-    fn calculate_allocated_time(&self) -> Duration {
+    /// The time left and the increment are divided up by `allocate_time`.
+    /// Defaults to 5 seconds if no time control is specified, unless the search is limited by
+    /// depth or nodes instead, in which case there is no time limit. There is no time limit for
+    /// `go infinite` either, which searches until `stop`.
+    fn calculate_allocated_time(&self) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+
         if let Some(movetime) = self.movetime {
-            return movetime;
+            return Some(movetime);
         }
 
         if self.time_left.as_millis() == 0 {
-            return Duration::from_secs(5); // Default to 5 seconds if no time control is specified
+            if self.depth.is_some() || self.nodes.is_some() {
+                return None;
+            }
+            return Some(Duration::from_secs(5)); // Default to 5 seconds if no time control is specified
         }
 
-        let moves_left = self.moves_to_go.unwrap_or(20) as f32;
-        let base_time = self.time_left.as_secs_f32() / moves_left;
-        let bonus_time = self.increment.as_secs_f32();
-
-        Duration::from_secs_f32(base_time + bonus_time * 0.5)
+        Some(allocate_time(self.time_left, self.increment, self.moves_to_go))
     }
 }
\ No newline at end of file
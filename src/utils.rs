@@ -50,7 +50,7 @@ pub fn print_move(the_move: &Move) -> String {
     let from = sq_ind_to_algebraic(the_move.from);
     let to = sq_ind_to_algebraic(the_move.to);
     let mut promotion = String::from("");
-    if the_move.promotion.is_some() {
+    if the_move.is_promotion() {
         promotion = String::from("=");
         match the_move.promotion.unwrap() {
             n if n == KNIGHT => promotion.push('N'),
@@ -0,0 +1,322 @@
+use std::env;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use kingfisher::agent::{Agent, AgentMove, AgentResult, ExternalUciAgent, SearchAlgorithm, SimpleAgent};
+use kingfisher::arena::Arena;
+use kingfisher::boardstack::BoardStack;
+use kingfisher::eval::PestoEval;
+use kingfisher::move_generation::MoveGen;
+use kingfisher::search::{format_uci_score, MATE_SCORE, MATE_THRESHOLD};
+
+#[test]
+fn test_agent_reports_checkmate() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let agent = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    // Fool's mate: white is checkmated
+    let mut board = BoardStack::new_from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    assert_eq!(agent.get_move(&mut board), AgentResult::Checkmate);
+}
+
+#[test]
+fn test_agent_reports_stalemate() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let agent = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    let mut board = BoardStack::new_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+    assert_eq!(agent.get_move(&mut board), AgentResult::Stalemate);
+}
+
+#[test]
+fn test_arena_stops_at_checkmate() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let white = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    let black = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    let mut arena = Arena::new(&white, &black, 10);
+    // White mates with Ra8, after which black has no move
+    arena.boardstack = BoardStack::new_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    assert_eq!(arena.play_game(), Some(AgentResult::Checkmate));
+    assert!(!arena.boardstack.current_state().w_to_move);
+}
+
+#[test]
+fn test_agent_plays_legal_move_with_each_algorithm() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    for algorithm in [SearchAlgorithm::AlphaBeta, SearchAlgorithm::AspirationWindow] {
+        let agent = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto).with_algorithm(algorithm);
+        assert_eq!(agent.algorithm, algorithm);
+        let mut board = BoardStack::new();
+        let m = match agent.get_move(&mut board) {
+            AgentResult::Move(agent_move) => agent_move.best_move,
+            result => panic!("{:?} returned {:?}", algorithm, result),
+        };
+        board.make_move(m);
+        assert!(board.current_state().is_legal(&move_gen), "{:?} played an illegal move", algorithm);
+    }
+}
+
+#[test]
+fn test_agent_reports_mate_score_and_pv() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let agent = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    let mut board = BoardStack::new_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    let mating_move = board.current_state().move_from_uci("a1a8").unwrap();
+    let AgentMove { best_move, score, pv, .. } = match agent.get_move(&mut board) {
+        AgentResult::Move(agent_move) => agent_move,
+        result => panic!("expected a move, got {:?}", result),
+    };
+    assert_eq!(best_move, mating_move);
+    assert_eq!(format_uci_score(score), "mate 1");
+    assert_eq!(pv, vec![mating_move]);
+}
+
+#[test]
+fn test_agent_reports_score_and_pv() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let agent = SimpleAgent::new(1, 4, 4, false, &move_gen, &pesto);
+    let mut board = BoardStack::new();
+    let agent_move = match agent.get_move(&mut board) {
+        AgentResult::Move(agent_move) => agent_move,
+        result => panic!("expected a move, got {:?}", result),
+    };
+    assert_eq!(agent_move.depth, 4);
+    assert!(agent_move.score.abs() < 100);
+    assert!(agent_move.nodes > 0);
+    assert!(!agent_move.pv.is_empty() && agent_move.pv.len() <= 4);
+    assert_eq!(agent_move.pv[0], agent_move.best_move);
+    // The board is left unchanged
+    assert_eq!(board.current_state().zobrist_hash(), BoardStack::new().current_state().zobrist_hash());
+}
+
+/// An agent that thinks for a fixed time before letting another agent choose the move
+struct SlowAgent<'a> {
+    delay: Duration,
+    agent: SimpleAgent<'a>,
+}
+
+impl Agent for SlowAgent<'_> {
+    fn get_move(&self, board: &mut BoardStack) -> AgentResult {
+        thread::sleep(self.delay);
+        self.agent.get_move(board)
+    }
+}
+
+#[test]
+fn test_arena_flags_slow_agent_on_time() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let white = SlowAgent { delay: Duration::from_millis(50), agent: SimpleAgent::new(1, 1, 0, false, &move_gen, &pesto) };
+    let black = SimpleAgent::new(1, 1, 0, false, &move_gen, &pesto);
+
+    // The first move already takes longer than the whole budget
+    let mut arena = Arena::new(&white, &black, 10).with_clock(Duration::from_millis(20), Duration::ZERO);
+    assert_eq!(arena.play_game(), Some(AgentResult::TimeForfeit));
+    assert!(arena.boardstack.current_state().w_to_move);
+    assert_eq!(arena.boardstack.ply(), 0);
+
+    // With enough time, the game runs until the move limit
+    let mut arena = Arena::new(&white, &black, 4).with_clock(Duration::from_secs(10), Duration::from_millis(10));
+    assert_eq!(arena.play_game(), None);
+    let clock = arena.clock.unwrap();
+    assert!(clock[0] < Duration::from_secs(10));
+    assert!(clock[1] > Duration::from_secs(9));
+}
+
+#[test]
+fn test_agent_budgets_moves_from_the_clock() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    // Without the clock these agents would search every move to depth 100
+    let white = SimpleAgent::new(3, 100, 4, false, &move_gen, &pesto);
+    let black = SimpleAgent::new(3, 100, 4, false, &move_gen, &pesto);
+
+    // Each move takes about a twentieth of the time left, so both clocks keep a good part of their time
+    let mut arena = Arena::new(&white, &black, 6).with_clock(Duration::from_secs(1), Duration::from_millis(50));
+    assert_eq!(arena.play_game(), None);
+    let clock = arena.clock.unwrap();
+    assert!(clock[0] > Duration::from_millis(250) && clock[1] > Duration::from_millis(250), "clocks left: {:?}", clock);
+}
+
+#[test]
+fn test_agent_resigns_after_confirmations() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let agent = SimpleAgent::new(1, 2, 4, false, &move_gen, &pesto).with_resignation(600, 3);
+
+    // Black is a queen down, so every search confirms the loss
+    let mut board = BoardStack::new_from_fen("4k3/8/8/8/8/8/3Q4/4K3 b - - 0 1");
+    for _ in 0..2 {
+        assert!(matches!(agent.get_move(&mut board), AgentResult::Move(_)));
+    }
+    assert_eq!(agent.get_move(&mut board), AgentResult::Resign);
+
+    // The arena ends the game with the resignation, with the resigning side to move
+    let white = SimpleAgent::new(1, 2, 4, false, &move_gen, &pesto);
+    let black = SimpleAgent::new(1, 2, 4, false, &move_gen, &pesto).with_resignation(600, 2);
+    let mut arena = Arena::new(&white, &black, 20);
+    arena.boardstack = BoardStack::new_from_fen("4k3/8/8/8/8/8/3Q4/4K3 b - - 0 1");
+    assert_eq!(arena.play_game(), Some(AgentResult::Resign));
+    assert!(!arena.boardstack.current_state().w_to_move);
+}
+
+#[test]
+fn test_agent_claims_draw_with_low_material() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let agent = SimpleAgent::new(1, 2, 4, false, &move_gen, &pesto).with_draw_claim(50);
+
+    // A rook each and no pawns
+    let mut board = BoardStack::new_from_fen("8/3k4/8/2r5/8/5R2/8/4K3 w - - 0 1");
+    assert_eq!(agent.get_move(&mut board), AgentResult::DrawClaim);
+
+    // Level material with pawns on the board is played on
+    let mut board = BoardStack::new_from_fen("r3k3/4p3/8/8/8/8/4P3/4K2R w - - 0 1");
+    assert!(matches!(agent.get_move(&mut board), AgentResult::Move(_)));
+}
+
+#[test]
+fn test_agent_stops_at_first_search_limit() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let fen = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8";
+
+    // A tiny time limit stops the search long before the node limit or the depth
+    let node_limit = 50_000_000;
+    let agent = SimpleAgent::new(1, 100, 4, false, &move_gen, &pesto).with_search_limits(Some(Duration::from_millis(20)), Some(node_limit));
+    let mut board = BoardStack::new_from_fen(fen);
+    match agent.get_move(&mut board) {
+        AgentResult::Move(agent_move) => {
+            assert!(agent_move.nodes < node_limit / 10, "searched {} nodes", agent_move.nodes);
+            assert!(agent_move.depth < 100);
+            board.make_move(agent_move.best_move);
+            assert!(board.current_state().is_legal(&move_gen));
+        }
+        result => panic!("expected a move, got {:?}", result),
+    }
+
+    // A small node limit stops the search without any time limit, with either search algorithm
+    for algorithm in [SearchAlgorithm::AlphaBeta, SearchAlgorithm::AspirationWindow] {
+        let agent = SimpleAgent::new(1, 100, 4, false, &move_gen, &pesto).with_algorithm(algorithm).with_search_limits(None, Some(5000));
+        let mut board = BoardStack::new_from_fen(fen);
+        match agent.get_move(&mut board) {
+            AgentResult::Move(agent_move) => assert!(agent_move.nodes < 10_000, "{:?} searched {} nodes", algorithm, agent_move.nodes),
+            result => panic!("expected a move, got {:?}", result),
+        }
+    }
+
+    // The limits also bound the mate search. With queens on an open board the checks go on and
+    // on, so a mate search this deep would run for a long time. The nodes reported include those
+    // of the mate search
+    let agent = SimpleAgent::new(20, 100, 4, false, &move_gen, &pesto).with_search_limits(None, Some(5000));
+    let mut board = BoardStack::new_from_fen("q3k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+    match agent.get_move(&mut board) {
+        AgentResult::Move(agent_move) => {
+            assert!(agent_move.nodes < 10_000, "searched {} nodes", agent_move.nodes);
+            board.make_move(agent_move.best_move);
+            assert!(board.current_state().is_legal(&move_gen));
+        }
+        result => panic!("expected a move, got {:?}", result),
+    }
+}
+
+/// Set in the child process of `test_quiet_agent_prints_nothing`
+const QUIET_SEARCH_CHILD: &str = "KINGFISHER_QUIET_SEARCH_CHILD";
+
+#[test]
+fn test_quiet_agent_prints_nothing() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // In the child, search a mate and a normal position between markers
+    if env::var_os(QUIET_SEARCH_CHILD).is_some() {
+        println!("search start");
+        let agent = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+        for fen in ["6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPPPPPP/RNBQKB1R w KQkq - 2 3"] {
+            agent.get_move(&mut BoardStack::new_from_fen(fen));
+        }
+        println!("search end");
+        return;
+    }
+
+    // The test harness captures output in-process, so rerun this test in a child process with
+    // capturing turned off and check what it printed
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "test_quiet_agent_prints_nothing", "--nocapture", "--test-threads", "1"])
+        .env(QUIET_SEARCH_CHILD, "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let start = stdout.find("search start\n").expect("child did not search") + "search start\n".len();
+    let end = stdout.find("search end").expect("child did not finish");
+    assert_eq!(&stdout[start..end], "");
+}
+
+/// The engine to play against in the external agent tests: the engine named by
+/// `KINGFISHER_EXTERNAL_ENGINE`, or else this crate's own UCI binary
+fn external_engine() -> String {
+    env::var("KINGFISHER_EXTERNAL_ENGINE").unwrap_or_else(|_| env!("CARGO_BIN_EXE_kingfisher").to_string())
+}
+
+#[test]
+fn test_external_agent_plays_legal_move() {
+    let move_gen = MoveGen::new();
+    let agent = ExternalUciAgent::new(&external_engine(), &move_gen).unwrap().with_depth(2);
+    assert!(!agent.name.is_empty());
+
+    // The game so far is sent as moves from the start, including castling
+    let mut board = BoardStack::new();
+    for uci in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"] {
+        let m = board.current_state().move_from_uci(uci).unwrap();
+        board.make_move(m);
+    }
+    let AgentResult::Move(agent_move) = agent.get_move(&mut board) else {
+        panic!("external agent did not move");
+    };
+    let (captures, moves) = move_gen.gen_pseudo_legal_moves(board.current_state());
+    assert!(captures.contains(&agent_move.best_move) || moves.contains(&agent_move.best_move));
+    assert!(board.current_state().apply_move_to_board(agent_move.best_move).is_legal(&move_gen));
+
+    // The search is reported from the engine's last info line
+    assert_eq!(agent_move.depth, 2);
+    assert!(agent_move.nodes > 0);
+    assert_eq!(agent_move.pv[0], agent_move.best_move);
+    assert!(agent_move.score.abs() < MATE_THRESHOLD);
+
+    // Mate scores are converted back from moves to plies
+    let mut board = BoardStack::new_from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+    let AgentResult::Move(agent_move) = agent.get_move(&mut board) else {
+        panic!("external agent did not move");
+    };
+    assert_eq!(agent_move.best_move.to_uci(), "a1a8");
+    assert_eq!(agent_move.score, MATE_SCORE - 1);
+
+    // Game-ending positions are reported without asking the engine
+    let mut board = BoardStack::new_from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    assert_eq!(agent.get_move(&mut board), AgentResult::Checkmate);
+}
+
+#[test]
+fn test_external_agent_plays_timed_arena_game() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let external = ExternalUciAgent::new(&external_engine(), &move_gen).unwrap();
+    let simple = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+
+    // The arena's clock is forwarded to the engine, which must keep within it
+    let mut arena = Arena::new(&external, &simple, 6).with_clock(Duration::from_secs(5), Duration::from_millis(100));
+    let result = arena.play_game();
+    assert_ne!(result, Some(AgentResult::TimeForfeit));
+    assert_ne!(result, Some(AgentResult::Resign));
+}
+
+#[test]
+fn test_external_agent_reports_bad_engine() {
+    let move_gen = MoveGen::new();
+    assert!(ExternalUciAgent::new("/nonexistent/uci-engine", &move_gen).is_err());
+}
@@ -1,8 +1,9 @@
-use kingfisher::board::Board;
+use kingfisher::board::{Board, FenError, IllegalPositionError};
 use kingfisher::boardstack::BoardStack;
 use kingfisher::move_generation::MoveGen;
-use kingfisher::move_types::Move;
-use kingfisher::piece_types::{PAWN, KNIGHT, ROOK, KING, WHITE, BLACK};
+use kingfisher::piece_types::{PAWN, KNIGHT, ROOK, QUEEN, KING, WHITE, BLACK};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 #[test]
 fn test_initial_position() {
@@ -37,7 +38,7 @@ fn test_get_piece_bitboard() {
     assert_eq!(board.get_piece_bitboard(BLACK, KING), 0x1000000000000000);
 
     // Make a move and test again
-    let e2e4 = Move::from_uci("e2e4").unwrap();
+    let e2e4 = board.move_from_uci("e2e4").unwrap();
     let new_board = board.apply_move_to_board(e2e4);
 
     // White pawns should have changed
@@ -91,4 +92,329 @@ fn test_stalemate_detection() {
     let (is_checkmate, is_stalemate) = board.is_checkmate_or_stalemate(&move_gen);
     assert!(!is_checkmate);
     assert!(is_stalemate);
-}
\ No newline at end of file
+}
+
+/// Checks that the occupancy bitboards agree with the per-piece bitboards
+fn assert_occupancy_consistent(board: &Board) {
+    let mut all = 0;
+    for color in [WHITE, BLACK] {
+        let mut occ = 0;
+        for piece in PAWN..=KING {
+            let bb = board.get_piece_bitboard(color, piece);
+            assert_eq!(occ & bb, 0, "Two pieces share a square");
+            occ |= bb;
+        }
+        assert_eq!(all & occ, 0, "Both colors occupy the same square");
+        all |= occ;
+    }
+    assert_eq!(board.get_all_occupancy(), all);
+    for sq in 0..64 {
+        assert_eq!(board.get_piece(sq).is_some(), all & (1u64 << sq) != 0);
+        assert_eq!(board.get_piece_type_on_sq(sq), board.get_piece(sq).map(|(_, piece)| piece));
+    }
+}
+
+#[test]
+fn test_set_and_clear_square() {
+    let mut board = Board::new();
+    board.set_square(28, WHITE, QUEEN); // Empty square
+    assert_eq!(board.get_piece(28), Some((WHITE, QUEEN)));
+    board.set_square(52, WHITE, KNIGHT); // Replaces a black pawn
+    assert_eq!(board.get_piece(52), Some((WHITE, KNIGHT)));
+    board.clear_square(0);
+    assert_eq!(board.get_piece(0), None);
+    assert_eq!(board.get_piece_type_on_sq(4), Some(KING));
+    assert_occupancy_consistent(&board);
+}
+
+#[test]
+fn test_random_square_updates_keep_occupancy_in_sync() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut board = Board::new_from_fen("r1bqkbnr/ppp2ppp/2np4/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4");
+    for _ in 0..1000 {
+        let sq = rng.gen_range(0..64);
+        if rng.gen_bool(0.5) {
+            board.clear_square(sq);
+        } else {
+            board.set_square(sq, rng.gen_range(0..2), rng.gen_range(PAWN..=KING));
+        }
+        assert_occupancy_consistent(&board);
+    }
+}
+
+#[test]
+fn test_attackers_to_finds_every_attacker() {
+    let move_gen = MoveGen::new();
+    // Knight on c4, pawn on d4 and rook on e2 all hit e5
+    let board = Board::new_from_fen("4k3/8/8/8/2NP4/8/4R3/7K w - - 0 1");
+    let expected = (1u64 << 26) | (1u64 << 27) | (1u64 << 12);
+    assert_eq!(board.attackers_to(36, true, &move_gen), expected);
+    assert_eq!(board.attackers_to(36, false, &move_gen), 0);
+    assert!(board.is_square_attacked(36, true, &move_gen));
+    assert!(!board.is_square_attacked(36, false, &move_gen));
+}
+
+#[test]
+fn test_pawn_attacks_do_not_wrap_around_edges() {
+    let move_gen = MoveGen::new();
+    // White pawns on a2 and h2, black pawns on a7 and h7
+    let board = Board::new_from_fen("4k3/p6p/8/8/8/8/P6P/4K3 w - - 0 1");
+    // White pawns attack only towards the centre
+    assert_eq!(board.attackers_to(17, true, &move_gen), 1u64 << 8); // b3
+    assert_eq!(board.attackers_to(22, true, &move_gen), 1u64 << 15); // g3
+    assert!(!board.is_square_attacked(16, true, &move_gen)); // a3
+    assert!(!board.is_square_attacked(23, true, &move_gen)); // h3
+    assert!(!board.is_square_attacked(24, true, &move_gen)); // a4
+    // Black pawns attack only towards the centre
+    assert_eq!(board.attackers_to(41, false, &move_gen), 1u64 << 48); // b6
+    assert_eq!(board.attackers_to(46, false, &move_gen), 1u64 << 55); // g6
+    assert!(!board.is_square_attacked(40, false, &move_gen)); // a6
+    assert!(!board.is_square_attacked(47, false, &move_gen)); // h6
+    assert!(!board.is_square_attacked(39, false, &move_gen)); // h5
+}
+
+#[test]
+fn test_attacks_from_corners() {
+    let move_gen = MoveGen::new();
+    // White knight on a8, black rook on a1, black knight on h1
+    let board = Board::new_from_fen("N3k3/8/8/8/8/8/8/r3K2n w - - 0 1");
+    assert_eq!(board.attackers_to(50, true, &move_gen), 1u64 << 56); // c7
+    assert_eq!(board.attackers_to(41, true, &move_gen), 1u64 << 56); // b6
+    assert_eq!(board.attackers_to(22, false, &move_gen), 1u64 << 7); // g3
+    assert_eq!(board.attackers_to(13, false, &move_gen), 1u64 << 7); // f2
+    assert!(!board.is_square_attacked(17, false, &move_gen)); // b3
+    // Rook on a1 sees along both the first rank and the a-file
+    assert_eq!(board.attackers_to(4, false, &move_gen), 1u64); // e1
+    assert_eq!(board.attackers_to(56, false, &move_gen), 1u64); // a8
+    assert!(board.is_check(&move_gen));
+}
+
+#[test]
+fn test_pawn_attackers_match_diagonals_on_every_square() {
+    let move_gen = MoveGen::new();
+    for color in [WHITE, BLACK] {
+        // Pawns never stand on the back ranks
+        for pawn_sq in 8..56 {
+            let mut board = Board::new_from_fen("8/8/8/8/8/8/8/8 w - - 0 1");
+            board.set_square(pawn_sq, color, PAWN);
+            let forward: i32 = if color == WHITE { 1 } else { -1 };
+            for target in 0..64 {
+                let file_diff = (target % 8) as i32 - (pawn_sq % 8) as i32;
+                let rank_diff = (target / 8) as i32 - (pawn_sq / 8) as i32;
+                let expected = file_diff.abs() == 1 && rank_diff == forward;
+                assert_eq!(
+                    board.attackers_to(target, color == WHITE, &move_gen) != 0,
+                    expected,
+                    "pawn of color {} on {} vs target {}", color, pawn_sq, target
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_try_from_fen_accepts_valid_fen() {
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    assert_eq!(Board::try_from_fen(fen).unwrap().to_fen(), fen);
+    assert!(Board::try_from_fen("8/8/8/K2Pp2q/8/8/8/k7 w - e6 0 2").is_ok());
+}
+
+#[test]
+fn test_try_from_fen_reports_malformed_fields() {
+    let cases = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", FenError::WrongFieldCount(4)),
+        ("", FenError::WrongFieldCount(0)),
+        ("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::WrongRankCount(7)),
+        ("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadRankLength(6)),
+        ("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadRankLength(7)),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1", FenError::BadRankLength(1)),
+        ("rnbqkbnr/pppppppp/8/8/4X3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::InvalidPiece('X')),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", FenError::InvalidSideToMove("x".to_string())),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1", FenError::InvalidCastling("KQkx".to_string())),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKq - 0 1", FenError::InvalidCastling("KKq".to_string())),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1", FenError::InvalidEnPassant("e9".to_string())),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1", FenError::InvalidEnPassant("e3".to_string())),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1", FenError::InvalidHalfmoveClock("x".to_string())),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0", FenError::InvalidFullmoveNumber("0".to_string())),
+    ];
+    for (fen, error) in cases {
+        assert_eq!(Board::try_from_fen(fen).err(), Some(error), "{}", fen);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Invalid FEN")]
+fn test_new_from_fen_panics_on_malformed_fen() {
+    Board::new_from_fen("not a fen");
+}
+
+#[test]
+fn test_validate_legal_accepts_legal_positions() {
+    let move_gen = MoveGen::new();
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+        "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+    ] {
+        assert_eq!(Board::new_from_fen(fen).validate_legal(&move_gen), Ok(()), "{}", fen);
+        assert!(Board::try_from_fen_legal(fen, &move_gen).is_ok());
+    }
+}
+
+#[test]
+fn test_validate_legal_rejects_illegal_positions() {
+    let move_gen = MoveGen::new();
+    let cases = [
+        ("4k3/8/8/8/8/8/8/8 w - - 0 1", IllegalPositionError::WrongKingCount(WHITE)),
+        ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", IllegalPositionError::WrongKingCount(WHITE)),
+        ("8/8/8/8/8/8/8/4K3 w - - 0 1", IllegalPositionError::WrongKingCount(BLACK)),
+        ("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1", IllegalPositionError::TooManyPieces(WHITE)),
+        ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", IllegalPositionError::PawnOnBackRank),
+        ("4k2p/8/8/8/8/8/8/4K3 w - - 0 1", IllegalPositionError::PawnOnBackRank),
+        ("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1", IllegalPositionError::OpponentInCheck),
+        ("4k3/8/8/8/8/8/8/5K1R w K - 0 1", IllegalPositionError::InvalidCastlingRights),
+        ("r3k3/8/8/8/8/8/8/4K3 w k - 0 1", IllegalPositionError::InvalidCastlingRights),
+        ("4k3/8/8/8/8/8/8/4K3 w - e6 0 1", IllegalPositionError::InvalidEnPassant),
+        ("4k3/4p3/8/4p3/8/8/8/4K3 w - e6 0 1", IllegalPositionError::InvalidEnPassant),
+    ];
+    for (fen, error) in cases {
+        assert_eq!(Board::new_from_fen(fen).validate_legal(&move_gen), Err(error.clone()), "{}", fen);
+        assert_eq!(Board::try_from_fen_legal(fen, &move_gen).err(), Some(FenError::IllegalPosition(error)), "{}", fen);
+    }
+}
+
+#[test]
+fn test_halfmove_clock_resets_on_captures_and_pawn_moves() {
+    let counters = |board: &Board| board.to_fen().split(' ').skip(4).collect::<Vec<&str>>().join(" ");
+    let start = Board::new_from_fen("4k3/1P6/8/n2pP3/8/8/8/R3K1N1 w - d6 10 20");
+    let play = |board: &Board, uci: &str| board.apply_move_to_board(board.move_from_uci(uci).unwrap());
+
+    // Quiet piece moves increment the clock, and Black's move completes a full move
+    let board = play(&start, "g1f3");
+    assert_eq!(counters(&board), "11 20");
+    assert_eq!(counters(&play(&board, "e8d7")), "12 21");
+
+    // Captures, en passant, promotions and pawn pushes reset it
+    for uci in ["a1a5", "e5d6", "b7b8q", "e5e6"] {
+        assert_eq!(counters(&play(&start, uci)), "0 20", "{}", uci);
+    }
+}
+
+#[test]
+fn test_null_move_round_trip_restores_en_passant_and_hash() {
+    let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+    let mut board = BoardStack::new_from_fen(fen);
+    let hash = board.current_state().zobrist_hash();
+
+    board.make_null_move();
+    let passed = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR b KQkq - 1 3";
+    assert_eq!(board.current_state().to_fen(), passed);
+    assert_eq!(board.current_state().zobrist_hash(), Board::new_from_fen(passed).zobrist_hash());
+    assert!(!board.is_draw_by_repetition());
+
+    // A real move on top of the null move is undone normally
+    let m = board.current_state().move_from_uci("g8f6").unwrap();
+    board.make_move(m);
+    board.undo_move();
+
+    board.undo_null_move();
+    assert_eq!(board.current_state().to_fen(), fen);
+    assert_eq!(board.current_state().zobrist_hash(), hash);
+    assert_eq!(board.ply(), 0);
+    // The en passant capture is available again
+    assert!(board.current_state().move_from_uci("e5f6").is_some());
+}
+
+#[test]
+fn test_mirror() {
+    let board = Board::new_from_fen("r3k2r/pp3ppp/8/3Pp3/8/8/PPP2PPP/R3K3 w Qkq e6 0 12");
+    let mirrored = board.mirror();
+    assert_eq!(mirrored.to_fen(), "r3k3/ppp2ppp/8/8/3pP3/8/PP3PPP/R3K2R b KQq e3 0 12");
+    assert_eq!(mirrored.mirror().to_fen(), board.to_fen());
+    assert_eq!(mirrored.zobrist_hash(), Board::new_from_fen(&mirrored.to_fen()).zobrist_hash());
+
+    // The starting position mirrors onto itself, apart from the side to move
+    assert_eq!(Board::new().mirror().to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1");
+}
+
+#[test]
+fn test_board_rendering() {
+    let board = Board::new();
+    assert_eq!(board.to_string_ascii(), "\
+r n b q k b n r
+p p p p p p p p
+. . . . . . . .
+. . . . . . . .
+. . . . . . . .
+. . . . . . . .
+P P P P P P P P
+R N B Q K B N R
+");
+    assert_eq!(board.to_string_unicode().lines().next(), Some("♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜"));
+    assert_eq!(board.to_string_unicode().lines().last(), Some("♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖"));
+
+    // Coordinates frame the board, as printed by `print`
+    let board = Board::new_from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+    let rendering = board.render(false, true, true);
+    let lines: Vec<&str> = rendering.lines().collect();
+    assert_eq!(lines.len(), 12);
+    assert_eq!(lines[0], "  +-----------------+");
+    assert_eq!(lines[1], "8 | . . . . k . . . |");
+    assert_eq!(lines[8], "1 | . . . . K . . R |");
+    assert_eq!(lines[10], "    a b c d e f g h");
+    assert_eq!(lines[11], "Black to move");
+}
+
+#[test]
+fn test_invariants_hold_through_a_game() {
+    let move_gen = MoveGen::new();
+    let mut board = BoardStack::new();
+    for uci in ["e2e4", "d7d5", "e4d5", "g8f6", "f1b5", "c7c6", "d5c6", "d8a5", "c6b7", "a5b5", "b7a8q", "e7e5", "g1f3", "f8c5", "e1g1"] {
+        let m = board.current_state().move_from_uci(uci).unwrap();
+        board.make_move(m);
+        board.current_state().assert_invariants();
+    }
+    board.make_null_move();
+    board.undo_null_move();
+    board.undo_moves(15);
+    board.current_state().assert_invariants();
+    assert_eq!(board.current_state().to_fen(), Board::new().to_fen());
+    assert!(board.current_state().is_legal(&move_gen));
+}
+
+#[test]
+#[should_panic(expected = "Zobrist hash out of sync")]
+fn test_invariants_catch_stale_hash() {
+    // Editing squares directly does not update the hash
+    let mut board = Board::new();
+    board.set_square(28, WHITE, QUEEN);
+    board.assert_invariants();
+}
+
+#[test]
+fn test_en_passant_square_hashed_only_when_capturable() {
+    // No black pawn can capture on e3, so the square does not matter
+    let irrelevant = Board::new_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    let without = Board::new_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert_eq!(irrelevant.zobrist_hash(), without.zobrist_hash());
+
+    // The d4 pawn can take on e3, so the positions differ
+    let capturable = Board::new_from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3");
+    let without = Board::new_from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3");
+    assert_ne!(capturable.zobrist_hash(), without.zobrist_hash());
+
+    // A pawn on the far side of the board cannot reach the square
+    let edge = Board::new_from_fen("rnbqkbnr/1pppppp1/8/p6P/8/8/PPPPPPP1/RNBQKBNR w KQkq a6 0 3");
+    let without = Board::new_from_fen("rnbqkbnr/1pppppp1/8/p6P/8/8/PPPPPPP1/RNBQKBNR w KQkq - 0 3");
+    assert_eq!(edge.zobrist_hash(), without.zobrist_hash());
+
+    // The same holds for a hash reached by making the moves
+    let mut board = BoardStack::new();
+    for uci in ["g1f3", "g8f6", "e2e4"] {
+        let m = board.current_state().move_from_uci(uci).unwrap();
+        board.make_move(m);
+    }
+    let transposed = Board::new_from_fen("rnbqkb1r/pppppppp/5n2/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 2");
+    assert_eq!(board.current_state().zobrist_hash(), transposed.zobrist_hash());
+}
@@ -0,0 +1,73 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use kingfisher::bits::{bits, popcnt};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Allocator that counts the allocations made by each thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_bits_yields_set_bit_indices() {
+    assert_eq!(bits(&0).collect::<Vec<_>>(), Vec::<usize>::new());
+    assert_eq!(bits(&1).collect::<Vec<_>>(), vec![0]);
+    assert_eq!(bits(&(1 << 63)).collect::<Vec<_>>(), vec![63]);
+    assert_eq!(bits(&0x8000_0000_0000_0001).collect::<Vec<_>>(), vec![0, 63]);
+    assert_eq!(bits(&0x000000000000FF00).collect::<Vec<_>>(), (8..16).collect::<Vec<_>>());
+    assert_eq!(bits(&u64::MAX).collect::<Vec<_>>(), (0..64).collect::<Vec<_>>());
+
+    let n = 0x0042_0000_1800_0081u64;
+    let iter = bits(&n);
+    assert_eq!(iter.len(), n.count_ones() as usize);
+    assert!(iter.clone().all(|sq| n & (1 << sq) != 0));
+    assert_eq!(iter.fold(0u64, |acc, sq| acc | (1 << sq)), n);
+}
+
+#[test]
+fn test_bits_does_not_allocate() {
+    let boards = [0u64, 1, 0x00FF00000000FF00, 0xFFFF00000000FFFF, u64::MAX];
+    let before = ALLOCATIONS.with(|count| count.get());
+    let mut total = 0;
+    for _ in 0..1000 {
+        for board in &boards {
+            for sq in bits(board) {
+                total += sq;
+            }
+        }
+    }
+    let after = ALLOCATIONS.with(|count| count.get());
+    assert_eq!(after, before);
+    assert!(total > 0);
+}
+
+#[test]
+fn test_popcnt_matches_count_ones() {
+    for n in [0u64, 1, 1 << 63, 0x00FF00000000FF00, u64::MAX] {
+        assert_eq!(popcnt(n), n.count_ones() as i32);
+    }
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..10000 {
+        // Mask to a random density, so that sparse and dense bitboards are both covered
+        let n = (rng.gen::<u64>() & rng.gen::<u64>()) | (rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>());
+        assert_eq!(popcnt(n), n.count_ones() as i32);
+        assert_eq!(popcnt(n) as usize, bits(&n).count());
+    }
+}
@@ -0,0 +1,146 @@
+use kingfisher::agent::{Agent, AgentResult, SimpleAgent};
+use kingfisher::arena::Arena;
+use kingfisher::board::Board;
+use kingfisher::boardstack::BoardStack;
+use kingfisher::egtb::{Tablebase, TablebaseResult, Tablebases, Wdl};
+use kingfisher::eval::PestoEval;
+use kingfisher::move_generation::MoveGen;
+use kingfisher::search::MATE_SCORE;
+use kingfisher::piece_types::{KNIGHT, BISHOP, ROOK, QUEEN, KING, WHITE, BLACK};
+
+/// Plays the tablebase's best moves for both sides until the game ends, returning the final
+/// position and the number of plies played
+fn play_out(tablebase: &Tablebase, move_gen: &MoveGen, fen: &str) -> (Board, u32) {
+    let mut board = Board::new_from_fen(fen);
+    let mut plies = 0;
+    while let Some(m) = tablebase.best_move(&board, move_gen) {
+        board = board.apply_move_to_board(m);
+        plies += 1;
+        assert!(plies <= 100, "no mate after 100 plies from {}", fen);
+    }
+    (board, plies)
+}
+
+#[test]
+fn test_krk_is_solved() {
+    let move_gen = MoveGen::new();
+    let tablebase = Tablebase::generate(&move_gen, &[ROOK]);
+    // The longest KRK mate takes 16 moves, counted here from a position with Black to move
+    assert_eq!(tablebase.longest_mate(), 32);
+
+    // Every legal position with White to move is a win
+    let empty = Board::new_from_fen("8/8/8/8/8/8/8/8 w - - 0 1");
+    for white_king in 0..64 {
+        for rook in 0..64 {
+            for black_king in 0..64 {
+                if white_king == rook || rook == black_king || white_king == black_king {
+                    continue;
+                }
+                let mut board = empty.clone();
+                board.set_square(white_king, WHITE, KING);
+                board.set_square(rook, WHITE, ROOK);
+                board.set_square(black_king, BLACK, KING);
+                if board.validate_legal(&move_gen).is_err() {
+                    continue;
+                }
+                assert_eq!(tablebase.probe(&board).unwrap().wdl, Wdl::Win, "{}", board.to_fen());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tablebase_plays_optimal_mates() {
+    let move_gen = MoveGen::new();
+    let krk = Tablebase::generate(&move_gen, &[ROOK]);
+    let kqk = Tablebase::generate(&move_gen, &[QUEEN]);
+    assert_eq!(kqk.longest_mate(), 20);
+
+    for (tablebase, fen) in [(&krk, "8/8/8/4k3/8/8/8/R3K3 w - - 0 1"), (&krk, "8/8/8/3k4/8/8/8/R3K3 b - - 0 1"), (&kqk, "8/8/2k5/8/8/8/8/3QK3 w - - 0 1")] {
+        let start = tablebase.probe(&Board::new_from_fen(fen)).unwrap();
+        assert_ne!(start.wdl, Wdl::Draw);
+        let (end, plies) = play_out(tablebase, &move_gen, fen);
+        assert!(end.is_checkmate_or_stalemate(&move_gen).0, "{} ended in {}", fen, end.to_fen());
+        assert_eq!(plies, start.dtm);
+    }
+
+    // A rook mate in one
+    let board = Board::new_from_fen("6k1/8/6K1/8/8/8/8/R7 w - - 0 1");
+    assert_eq!(krk.probe(&board), Some(TablebaseResult { wdl: Wdl::Win, dtm: 1 }));
+    assert_eq!(krk.best_move(&board, &move_gen), board.move_from_uci("a1a8"));
+}
+
+#[test]
+fn test_tablebase_probe() {
+    let move_gen = MoveGen::new();
+    let krk = Tablebase::generate(&move_gen, &[ROOK]);
+
+    // Colors are mirrored when Black has the rook
+    let white = krk.probe(&Board::new_from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1")).unwrap();
+    let black = krk.probe(&Board::new_from_fen("r3k3/8/8/8/4K3/8/8/8 b - - 0 1")).unwrap();
+    assert_eq!(black, white);
+
+    // Black can take the undefended rook
+    assert_eq!(krk.probe(&Board::new_from_fen("8/8/8/8/8/8/3k4/3R3K b - - 0 1")).unwrap().wdl, Wdl::Draw);
+    // Stalemate
+    assert_eq!(krk.probe(&Board::new_from_fen("k7/1R6/1K6/8/8/8/8/8 b - - 0 1")), Some(TablebaseResult { wdl: Wdl::Draw, dtm: 0 }));
+    // Other material is not in the table
+    assert_eq!(krk.probe(&Board::new_from_fen("8/8/8/4k3/8/8/8/Q3K3 w - - 0 1")), None);
+    assert_eq!(krk.probe(&Board::new_from_fen("8/8/8/4k3/8/8/4P3/R3K3 w - - 0 1")), None);
+
+    // A lone minor piece cannot mate
+    for piece in [KNIGHT, BISHOP] {
+        assert_eq!(Tablebase::generate(&move_gen, &[piece]).longest_mate(), 0);
+    }
+}
+
+#[test]
+fn test_kbnk_is_solved() {
+    let move_gen = MoveGen::new();
+    let kbnk = Tablebase::generate(&move_gen, &[BISHOP, KNIGHT]);
+    // The longest KBNK mate takes 33 moves, counted here from a position with Black to move
+    assert_eq!(kbnk.longest_mate(), 66);
+
+    // Either side can have the pieces
+    let board = Board::new_from_fen("8/8/8/4k3/8/8/8/KBN5 w - - 0 1");
+    let start = kbnk.probe(&board).unwrap();
+    assert_eq!(start.wdl, Wdl::Win);
+    assert_eq!(kbnk.probe(&board.mirror()), Some(start));
+
+    // Mate can only be forced in a corner of the bishop's color
+    let (end, plies) = play_out(&kbnk, &move_gen, "8/8/8/4k3/8/8/8/KBN5 w - - 0 1");
+    assert!(end.is_checkmate_or_stalemate(&move_gen).0, "ended in {}", end.to_fen());
+    assert_eq!(plies, start.dtm);
+    let color = |sq: u32| (sq / 8 + sq % 8) % 2;
+    let king = end.get_piece_bitboard(BLACK, KING).trailing_zeros();
+    let bishop = end.get_piece_bitboard(WHITE, BISHOP).trailing_zeros();
+    assert!([0, 7, 56, 63].contains(&king) && color(king) == color(bishop), "mated on {}", king);
+
+    // The king can take an undefended piece
+    assert_eq!(kbnk.probe(&Board::new_from_fen("8/8/8/8/8/8/2k5/1NB1K3 b - - 0 1")).unwrap().wdl, Wdl::Draw);
+}
+
+#[test]
+fn test_agent_mates_with_tablebases() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let tablebases = Tablebases::generate(&move_gen);
+
+    // Bishop and knight take too long to mate for the search alone; with the tablebases White
+    // mates against a searching defender at least as fast as against perfect defence
+    let fen = "8/8/8/4k3/8/8/8/KBN5 w - - 0 1";
+    let dtm = tablebases.probe(&Board::new_from_fen(fen)).unwrap().dtm;
+    let white = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto).with_tablebases(&tablebases);
+    let black = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    let mut arena = Arena::new(&white, &black, dtm as i32 + 1);
+    arena.boardstack = BoardStack::new_from_fen(fen);
+    assert_eq!(arena.play_game(), Some(AgentResult::Checkmate));
+    assert!(!arena.boardstack.current_state().w_to_move);
+
+    // Tablebase moves are scored like the mates found by the search
+    let AgentResult::Move(agent_move) = white.get_move(&mut BoardStack::new_from_fen("6k1/8/6K1/8/8/8/8/R7 w - - 0 1")) else {
+        panic!("agent did not move");
+    };
+    assert_eq!(agent_move.score, MATE_SCORE - 1);
+    assert_eq!(agent_move.best_move.to_uci(), "a1a8");
+}
@@ -1,38 +1,422 @@
 use kingfisher::board::Board;
-use kingfisher::eval::PestoEval;
+use kingfisher::boardstack::BoardStack;
+use kingfisher::eval::{Evaluator, MaterialEval, PestoEval};
+use kingfisher::eval_constants::PIECE_VALUES;
+use kingfisher::move_generation::MoveGen;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::thread;
 
 #[test]
 fn test_initial_position_eval() {
+    let move_gen = MoveGen::new();
     let board = Board::new();
     let evaluator = PestoEval::new();
-    let score = evaluator.eval(&board);
+    let score = evaluator.eval(&board, &move_gen);
     assert_eq!(score, 0); // Initial position should be equal
 }
 
 #[test]
 fn test_material_advantage() {
+    let move_gen = MoveGen::new();
     let board = Board::new_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1"); // White is missing a rook
     let evaluator = PestoEval::new();
-    let score = evaluator.eval(&board);
+    let score = evaluator.eval(&board, &move_gen);
     assert!(score < 0); // Black should have an advantage
 }
 
 #[test]
 fn test_positional_evaluation() {
+    let move_gen = MoveGen::new();
     let initial_board = Board::new();
     let developed_board = Board::new_from_fen("rnbqkbnr/1ppppppp/p7/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
     let evaluator = PestoEval::new();
-    let initial_score = evaluator.eval(&initial_board);
-    let developed_score = evaluator.eval(&developed_board);
+    let initial_score = evaluator.eval(&initial_board, &move_gen);
+    let developed_score = evaluator.eval(&developed_board, &move_gen);
     assert!(developed_score > initial_score); // Developed position should be better for White
 }
 
 #[test]
 fn test_eval_flipped_for_black() {
+    let move_gen = MoveGen::new();
     let board_w_to_move = Board::new_from_fen("rnbqkbnr/1ppppppp/p7/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
     let board_b_to_move = Board::new_from_fen("rnbqkbnr/1ppppppp/p7/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
     let evaluator = PestoEval::new();
-    let score_w_to_move = evaluator.eval(&board_w_to_move);
-    let score_b_to_move = evaluator.eval(&board_b_to_move);
+    let score_w_to_move = evaluator.eval(&board_w_to_move, &move_gen);
+    let score_b_to_move = evaluator.eval(&board_b_to_move, &move_gen);
     assert!(score_b_to_move == -score_w_to_move); // Score should be from the perspective of the side to move
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_connected_passed_pawns() {
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+
+    // Same material: connected passers on d5/e5 against isolated passers on b5/e5
+    let connected = Board::new_from_fen("4k3/8/8/3PP3/8/8/8/4K3 w - - 0 1");
+    let isolated = Board::new_from_fen("4k3/8/8/1P2P3/8/8/8/4K3 w - - 0 1");
+    assert!(evaluator.eval(&connected, &move_gen) > evaluator.eval(&isolated, &move_gen));
+
+    // A passer defended by a rook behind it is worth more than one without support
+    let protected = Board::new_from_fen("4k3/8/8/3P4/8/8/8/3RK3 w - - 0 1");
+    let unprotected = Board::new_from_fen("4k3/8/8/3P4/8/8/8/R3K3 w - - 0 1");
+    assert!(evaluator.eval(&protected, &move_gen) > evaluator.eval(&unprotected, &move_gen));
+}
+
+#[test]
+fn test_king_danger_grows_non_linearly() {
+    use kingfisher::piece_types::BLACK;
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+
+    // Black king on g8 behind its pawns, attacked by a queen, a knight, or both
+    let queen = Board::new_from_fen("6k1/5ppp/8/7Q/8/8/5PPP/6K1 w - - 0 1");
+    let knight = Board::new_from_fen("6k1/5ppp/8/6N1/8/8/5PPP/6K1 w - - 0 1");
+    let both = Board::new_from_fen("6k1/5ppp/8/6NQ/8/8/5PPP/6K1 w - - 0 1");
+    let three = Board::new_from_fen("6k1/5ppp/8/6NQ/8/3B4/5PPP/6K1 w - - 0 1");
+
+    let danger_queen = evaluator.king_danger(&queen, &move_gen, BLACK);
+    let danger_knight = evaluator.king_danger(&knight, &move_gen, BLACK);
+    let danger_both = evaluator.king_danger(&both, &move_gen, BLACK);
+    let danger_three = evaluator.king_danger(&three, &move_gen, BLACK);
+
+    // A lone attacker is not dangerous, but attackers together are worth more than the sum of their parts
+    assert_eq!(danger_queen, 0);
+    assert_eq!(danger_knight, 0);
+    assert!(danger_both > danger_queen + danger_knight);
+    assert!(danger_three > danger_both);
+
+    // A rook sweeping the empty squares in front of the king counts as an attacker too
+    let knight = Board::new_from_fen("8/8/4k3/6N1/8/8/8/4K3 w - - 0 1");
+    let rook_and_knight = Board::new_from_fen("8/R7/4k3/6N1/8/8/8/4K3 w - - 0 1");
+    assert_eq!(evaluator.king_danger(&knight, &move_gen, BLACK), 0);
+    assert!(evaluator.king_danger(&rook_and_knight, &move_gen, BLACK) > 0);
+}
+
+#[test]
+fn test_mop_up_drives_lone_king_to_edge() {
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+
+    // KQvK with the black king driven from the centre to the edge and then the corner
+    let centre = Board::new_from_fen("8/8/8/4k3/8/8/3Q4/4K3 w - - 0 1");
+    let edge = Board::new_from_fen("4k3/8/8/8/8/8/3Q4/4K3 w - - 0 1");
+    let corner = Board::new_from_fen("7k/8/8/8/8/8/3Q4/4K3 w - - 0 1");
+    assert!(evaluator.eval(&edge, &move_gen) > evaluator.eval(&centre, &move_gen));
+    assert!(evaluator.eval(&corner, &move_gen) > evaluator.eval(&edge, &move_gen));
+
+    // Bringing the white king closer also helps
+    let close = Board::new_from_fen("7k/8/5K2/8/8/8/3Q4/8 w - - 0 1");
+    assert!(evaluator.eval(&close, &move_gen) > evaluator.eval(&corner, &move_gen));
+
+    // No bonus while the defender has anything besides the king, or the attacker cannot mate
+    use kingfisher::piece_types::{WHITE, BLACK};
+    assert_eq!(evaluator.mop_up_bonus(&Board::new_from_fen("7k/7p/8/8/8/8/3Q4/4K3 w - - 0 1"), WHITE), 0);
+    assert_eq!(evaluator.mop_up_bonus(&Board::new_from_fen("7k/8/8/8/8/8/3N4/4K3 w - - 0 1"), WHITE), 0);
+    assert_eq!(evaluator.mop_up_bonus(&corner, BLACK), 0);
+}
+
+#[test]
+fn test_mop_up_prefers_bishop_colored_corner() {
+    let evaluator = PestoEval::new();
+
+    // Dark-squared bishop on c1: mate is only possible on a1 or h8, not a8 or h1
+    let right_corner = Board::new_from_fen("7k/8/8/8/8/8/3N4/2B1K3 w - - 0 1");
+    let wrong_corner = Board::new_from_fen("k7/8/8/8/8/8/3N4/2B1K3 w - - 0 1");
+    use kingfisher::piece_types::WHITE;
+    assert!(evaluator.mop_up_bonus(&right_corner, WHITE) > evaluator.mop_up_bonus(&wrong_corner, WHITE));
+}
+
+#[test]
+fn test_rule_of_the_square() {
+    use kingfisher::piece_types::{WHITE, BLACK};
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+
+    // The black king on g6 is outside the square of the a4 pawn, and the pawn queens
+    let won = Board::new_from_fen("8/8/6k1/8/P7/8/8/7K b - - 0 1");
+    assert!(evaluator.unstoppable_passer_bonus(&won, WHITE) > 0);
+    assert!(evaluator.eval(&won, &move_gen) < -500);
+
+    // With the black king one file closer, it catches the pawn
+    let caught = Board::new_from_fen("8/8/5k2/8/P7/8/8/7K b - - 0 1");
+    assert_eq!(evaluator.unstoppable_passer_bonus(&caught, WHITE), 0);
+
+    // Unless it is white to move
+    let outside_if_white_moves = Board::new_from_fen("8/8/5k2/8/P7/8/8/7K w - - 0 1");
+    assert!(evaluator.unstoppable_passer_bonus(&outside_if_white_moves, WHITE) > 0);
+
+    // A pawn on its starting rank can move two squares: b5 outruns the king on h5
+    let double_push = Board::new_from_fen("8/1p6/8/7K/8/8/8/k7 b - - 0 1");
+    assert!(evaluator.unstoppable_passer_bonus(&double_push, BLACK) > 0);
+}
+
+#[test]
+fn test_kpk_key_squares() {
+    use kingfisher::piece_types::WHITE;
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+
+    // White king on a key square of the e4 pawn (d6, e6, f6) wins
+    let won = Board::new_from_fen("8/4k3/3K4/8/4P3/8/8/8 b - - 0 1");
+    assert_eq!(evaluator.kpk_result(&won), Some(Some(WHITE)));
+
+    // Black king in front of the pawn, with the white king behind it, draws
+    let drawn = Board::new_from_fen("4k3/8/8/8/8/4P3/8/4K3 w - - 0 1");
+    assert_eq!(evaluator.kpk_result(&drawn), Some(None));
+
+    // Rook pawn with the defender in the corner draws
+    let rook_pawn = Board::new_from_fen("k7/8/8/P7/8/8/8/4K3 w - - 0 1");
+    assert_eq!(evaluator.kpk_result(&rook_pawn), Some(None));
+
+    // The won position is clearly winning, the drawn ones are close to equal, despite the extra pawn
+    assert!(-evaluator.eval(&won, &move_gen) > 300);
+    assert!(evaluator.eval(&drawn, &move_gen).abs() < 50);
+    assert!(evaluator.eval(&rook_pawn, &move_gen).abs() < 50);
+}
+
+#[test]
+fn test_eval_config_disables_terms() {
+    use kingfisher::eval::EvalConfig;
+    use kingfisher::piece_types::{WHITE, BLACK};
+    let move_gen = MoveGen::new();
+
+    // All pieces on the board, so the middlegame-only king safety term counts in full
+    let board = Board::new_from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4");
+    let full = PestoEval::new();
+    assert_eq!(full.config(), EvalConfig::default());
+
+    let no_king_safety = PestoEval::with_config(EvalConfig { king_safety: false, ..EvalConfig::default() });
+    let king_safety = full.king_danger(&board, &move_gen, BLACK) - full.king_danger(&board, &move_gen, WHITE);
+    assert!(king_safety > 0);
+    assert_eq!(full.eval(&board, &move_gen), no_king_safety.eval(&board, &move_gen) + king_safety);
+
+    // Without any extra terms only material and piece-square tables are left, which are
+    // equal in the starting position and symmetric when the colors are swapped
+    let baseline = PestoEval::with_config(EvalConfig { pawn_structure: false, king_safety: false, bishops: false, space: false, mobility: false, endgame_knowledge: false });
+    assert_eq!(baseline.eval(&Board::new(), &move_gen), 0);
+    let passer = Board::new_from_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+    let no_passer = Board::new_from_fen("4k3/8/8/8/3P4/8/8/4K3 w - - 0 1");
+    assert!(full.eval(&passer, &move_gen) - full.eval(&no_passer, &move_gen) > baseline.eval(&passer, &move_gen) - baseline.eval(&no_passer, &move_gen));
+}
+
+#[test]
+fn test_doubled_pawn_penalty() {
+    use kingfisher::eval::EvalConfig;
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let no_pawn_structure = PestoEval::with_config(EvalConfig { pawn_structure: false, ..EvalConfig::default() });
+
+    // Equal material and no passed pawns: white's c-pawns are doubled, or even tripled
+    let healthy = Board::new_from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1");
+    let doubled = Board::new_from_fen("4k3/pppp4/8/8/8/2P5/PPP5/4K3 w - - 0 1");
+    let tripled = Board::new_from_fen("4k3/pppp4/8/8/2P5/2P5/1PP5/4K3 w - - 0 1");
+    let penalty = |board: &Board| no_pawn_structure.eval(board, &move_gen) - evaluator.eval(board, &move_gen);
+
+    assert_eq!(penalty(&healthy), 0);
+    assert!(penalty(&doubled) > 0);
+    assert!(penalty(&tripled) > penalty(&doubled));
+}
+
+#[test]
+fn test_bad_bishop_and_bishop_pair() {
+    use kingfisher::eval::EvalConfig;
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let no_bishops = PestoEval::with_config(EvalConfig { bishops: false, ..EvalConfig::default() });
+    // The bishop terms from white's point of view
+    let bishop_terms = |board: &Board| evaluator.eval(board, &move_gen) - no_bishops.eval(board, &move_gen);
+
+    // French structure: black's pawns on d5 and e6 are on light squares, hemming in the
+    // light-squared bishop on c8, while the dark-squared bishop on f8 is free
+    let bad_bishop = Board::new_from_fen("2b1k3/pp3ppp/4p3/3pP3/3P4/8/PP3PPP/4KB2 w - - 0 1");
+    let good_bishop = Board::new_from_fen("4kb2/pp3ppp/4p3/3pP3/3P4/8/PP3PPP/4KB2 w - - 0 1");
+    assert!(bishop_terms(&bad_bishop) > bishop_terms(&good_bishop));
+
+    // The bishop pair is worth more in an open position
+    let closed_pair = Board::new_from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/2B1KB2 w - - 0 1");
+    let closed_single = Board::new_from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4KB2 w - - 0 1");
+    let open_pair = Board::new_from_fen("4k3/pp6/8/8/8/8/PP6/2B1KB2 w - - 0 1");
+    let open_single = Board::new_from_fen("4k3/pp6/8/8/8/8/PP6/4KB2 w - - 0 1");
+    let closed_bonus = bishop_terms(&closed_pair) - bishop_terms(&closed_single);
+    let open_bonus = bishop_terms(&open_pair) - bishop_terms(&open_single);
+    assert!(open_bonus > closed_bonus);
+}
+
+#[test]
+fn test_space() {
+    use kingfisher::eval::EvalConfig;
+    use kingfisher::piece_types::{WHITE, BLACK};
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let no_space = PestoEval::with_config(EvalConfig { space: false, ..EvalConfig::default() });
+
+    // Equal material: a broad pawn centre on the fourth rank against a passive one on the third
+    let big_centre = Board::new_from_fen("rnbqkbnr/pppppppp/8/8/2PPPP2/8/PP4PP/RNBQKBNR w KQkq - 0 1");
+    let passive = Board::new_from_fen("rnbqkbnr/pppppppp/8/8/8/2PPPP2/PP4PP/RNBQKBNR w KQkq - 0 1");
+    assert!(evaluator.space(&big_centre, &move_gen, WHITE) > evaluator.space(&passive, &move_gen, WHITE));
+    assert_eq!(evaluator.space(&big_centre, &move_gen, BLACK), evaluator.space(&passive, &move_gen, BLACK));
+    let space_term = |board: &Board| evaluator.eval(board, &move_gen) - no_space.eval(board, &move_gen);
+    assert!(space_term(&big_centre) > space_term(&passive));
+
+    // The starting position is symmetric
+    assert_eq!(evaluator.space(&Board::new(), &move_gen, WHITE), evaluator.space(&Board::new(), &move_gen, BLACK));
+}
+
+#[test]
+fn test_mobility() {
+    use kingfisher::eval::EvalConfig;
+    use kingfisher::piece_types::{WHITE, BLACK};
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let no_mobility = PestoEval::with_config(EvalConfig { mobility: false, ..EvalConfig::default() });
+
+    // Knights only reach b3, d3 and the unattacked squares in front of the pawns
+    let (mg, eg) = evaluator.mobility(&Board::new(), &move_gen, WHITE);
+    assert_eq!((mg, eg), evaluator.mobility(&Board::new(), &move_gen, BLACK));
+    assert_eq!(mg, 4 * 4);
+
+    // With all pieces on the board only the middlegame counts, so disabling mobility leaves
+    // the material, piece-square tables and remaining bonuses, less White's developed pieces'
+    // extra mobility
+    let board = Board::new_from_fen("rnbqkbnr/ppp2ppp/3p4/4p3/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 0 4");
+    assert_eq!(board.phase(), 24);
+    let mobility = evaluator.mobility(&board, &move_gen, WHITE).0 - evaluator.mobility(&board, &move_gen, BLACK).0;
+    assert!(mobility > 0);
+    assert_eq!(evaluator.eval(&board, &move_gen), no_mobility.eval(&board, &move_gen) + mobility);
+
+    // A bishop hemmed in by its own pawns is worth less than an active one
+    let open = Board::new_from_fen("4k3/8/8/8/8/2B5/8/4K3 w - - 0 1");
+    let closed = Board::new_from_fen("4k3/8/8/8/8/8/1P1P4/2B1K3 w - - 0 1");
+    assert!(evaluator.mobility(&open, &move_gen, WHITE).1 > evaluator.mobility(&closed, &move_gen, WHITE).1);
+}
+
+#[test]
+fn test_board_phase_and_material() {
+    use kingfisher::piece_types::{PAWN, KNIGHT, BISHOP, ROOK, QUEEN, WHITE, BLACK};
+    let board = Board::new();
+    assert_eq!(board.phase(), 24);
+    assert_eq!(board.material(WHITE), 8 * PIECE_VALUES[PAWN] + 2 * PIECE_VALUES[KNIGHT] + 2 * PIECE_VALUES[BISHOP] + 2 * PIECE_VALUES[ROOK] + PIECE_VALUES[QUEEN]);
+    assert_eq!(board.material(BLACK), board.material(WHITE));
+
+    let bare_kings = Board::new_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(bare_kings.phase(), 0);
+    assert_eq!(bare_kings.material(WHITE), 0);
+
+    // Rook and knight against two pawns
+    let board = Board::new_from_fen("4k3/pp6/8/8/8/8/8/1N2K2R w - - 0 1");
+    assert_eq!(board.phase(), 3);
+    assert_eq!(board.material(WHITE), PIECE_VALUES[ROOK] + PIECE_VALUES[KNIGHT]);
+    assert_eq!(board.material(BLACK), 2 * PIECE_VALUES[PAWN]);
+
+    // Extra queens from promotions do not push the phase past the middlegame
+    let board = Board::new_from_fen("QQQ1k3/8/8/8/8/8/8/QQQ1K3 w - - 0 1");
+    assert_eq!(board.phase(), 24);
+}
+
+#[test]
+fn test_eval_is_color_symmetric() {
+    // Evaluations are relative to the side to move, so a position and its mirror image must
+    // evaluate the same (i.e. opposite scores from White's point of view)
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..20 {
+        let mut board = BoardStack::new();
+        for _ in 0..80 {
+            let position = board.current_state().clone();
+            assert_eq!(evaluator.eval(&position, &move_gen), evaluator.eval(&position.mirror(), &move_gen), "{}", position.to_fen());
+
+            let (captures, moves) = move_gen.gen_pseudo_legal_moves(&position);
+            let legal: Vec<_> = captures.into_iter().chain(moves)
+                .filter(|&m| position.apply_move_to_board(m).is_legal(&move_gen))
+                .collect();
+            if legal.is_empty() {
+                break;
+            }
+            board.make_move(legal[rng.gen_range(0..legal.len())]);
+        }
+    }
+}
+
+#[test]
+fn test_eval_white_pov() {
+    use kingfisher::piece_types::KNIGHT;
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let board_w_to_move = Board::new_from_fen("rnbqkbnr/1ppppppp/p7/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+    let board_b_to_move = Board::new_from_fen("rnbqkbnr/1ppppppp/p7/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert_eq!(evaluator.eval_white_pov(&board_w_to_move, &move_gen), evaluator.eval(&board_w_to_move, &move_gen));
+    assert_eq!(evaluator.eval_white_pov(&board_b_to_move, &move_gen), -evaluator.eval(&board_b_to_move, &move_gen));
+    assert_eq!(evaluator.eval_white_pov(&board_w_to_move, &move_gen), evaluator.eval_white_pov(&board_b_to_move, &move_gen));
+
+    // White is a knight up, whoever is to move
+    let board = Board::new_from_fen("4k3/8/8/8/8/8/8/1N2K3 b - - 0 1");
+    assert_eq!(MaterialEval.eval(&board, &move_gen), -PIECE_VALUES[KNIGHT]);
+    assert_eq!(MaterialEval.eval_white_pov(&board, &move_gen), PIECE_VALUES[KNIGHT]);
+}
+
+#[test]
+fn test_pawn_hash_matches_fresh_eval() {
+    // An evaluator reused across a game serves most pawn structure terms from the thread's pawn
+    // hash table, and must agree with a thread whose table is still empty
+    let move_gen = MoveGen::new();
+    let cached = PestoEval::new();
+    let fresh = |board: &Board| thread::scope(|s| s.spawn(|| cached.eval(board, &move_gen)).join().unwrap());
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..10 {
+        let mut board = BoardStack::new();
+        for _ in 0..60 {
+            let position = board.current_state().clone();
+            let first = cached.eval(&position, &move_gen);
+            assert_eq!(cached.eval(&position, &move_gen), first, "{}", position.to_fen());
+            assert_eq!(fresh(&position), first, "{}", position.to_fen());
+
+            let (captures, moves) = move_gen.gen_pseudo_legal_moves(&position);
+            let legal: Vec<_> = captures.into_iter().chain(moves)
+                .filter(|&m| position.apply_move_to_board(m).is_legal(&move_gen))
+                .collect();
+            if legal.is_empty() {
+                break;
+            }
+            board.make_move(legal[rng.gen_range(0..legal.len())]);
+        }
+    }
+
+    // The same pawns with and without a rook behind the passer: the rook's support is not cached
+    let supported = Board::new_from_fen("4k3/8/8/3P4/8/8/8/3RK3 w - - 0 1");
+    let unsupported = Board::new_from_fen("4k3/8/8/3P4/8/8/8/4K2R w - - 0 1");
+    for board in [&supported, &unsupported, &supported] {
+        assert_eq!(cached.eval(board, &move_gen), fresh(board), "{}", board.to_fen());
+    }
+    assert_eq!(cached.pawn_structure(&supported), cached.pawn_structure(&unsupported));
+    let (mg, eg) = cached.pawn_structure(&supported);
+    assert!(mg[0] > 0 && eg[0] > 0 && mg[1] == 0 && eg[1] == 0);
+}
+
+#[test]
+fn test_pesto_eval_is_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PestoEval>();
+
+    // Threads sharing one evaluator each fill their own pawn hash table and agree on every score
+    let move_gen = MoveGen::new();
+    let evaluator = PestoEval::new();
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "4k3/8/8/3P4/8/8/8/3RK3 w - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+    let boards: Vec<Board> = fens.iter().map(|fen| Board::new_from_fen(fen)).collect();
+    let expected: Vec<i32> = boards.iter().map(|board| evaluator.eval(board, &move_gen)).collect();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for (board, &score) in boards.iter().zip(&expected) {
+                    assert_eq!(evaluator.eval(board, &move_gen), score, "{}", board.to_fen());
+                }
+            });
+        }
+    });
+}
@@ -2,7 +2,8 @@ use kingfisher::board::Board;
 use kingfisher::boardstack::BoardStack;
 use kingfisher::eval::PestoEval;
 use kingfisher::move_generation::MoveGen;
-use kingfisher::move_types::Move;
+use kingfisher::move_types::{Move, MoveFlag};
+use kingfisher::piece_types::{PAWN, WHITE};
 
 #[test]
 fn test_initial_move_count() {
@@ -118,7 +119,7 @@ fn test_pawn_fork_ordering() {
     ];
 
     for mv_str in moves.iter() {
-        let mv = Move::from_uci(mv_str).unwrap();
+        let mv = boardstack.current_state().move_from_uci(mv_str).unwrap();
         boardstack.make_move(mv);
     }
 
@@ -137,4 +138,312 @@ fn test_pawn_fork_ordering() {
         println!("{}. {} ({})", i+1, m, pesto.move_eval(&board, &move_gen, m.from, m.to));
     }
     assert!(pesto.move_eval(&board, &move_gen, non_captures[0].from, non_captures[0].to) == 600);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_knight_fork_ordering() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Nc7+ forks the king on e8 and the rook on a8
+    let board = Board::new_from_fen("r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1");
+    let (_captures, non_captures) = move_gen.gen_pseudo_legal_moves_with_evals(&board, &pesto);
+    assert_eq!((non_captures[0].from, non_captures[0].to), (33, 50));
+    assert_eq!(pesto.move_eval(&board, &move_gen, 33, 50), 875);
+}
+
+/// Finds the generated move between two squares (first promotion piece, if several).
+fn find_move(board: &Board, move_gen: &MoveGen, from: usize, to: usize) -> Move {
+    let (mut captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+    captures.extend(moves);
+    *captures.iter().find(|m| m.from == from && m.to == to).unwrap()
+}
+
+#[test]
+fn test_move_flags_pawn_moves() {
+    let board = Board::new();
+    let move_gen = MoveGen::new();
+    assert_eq!(find_move(&board, &move_gen, 12, 20).flag, MoveFlag::Normal); // e2e3
+    assert_eq!(find_move(&board, &move_gen, 12, 28).flag, MoveFlag::DoublePawnPush); // e2e4
+    assert_eq!(find_move(&board, &move_gen, 6, 21).flag, MoveFlag::Normal); // Ng1f3
+
+    // Black double push
+    let board = Board::new_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    assert_eq!(find_move(&board, &move_gen, 51, 35).flag, MoveFlag::DoublePawnPush); // d7d5
+    assert_eq!(find_move(&board, &move_gen, 51, 43).flag, MoveFlag::Normal); // d7d6
+}
+
+#[test]
+fn test_move_flags_en_passant() {
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    assert_eq!(find_move(&board, &move_gen, 36, 45).flag, MoveFlag::EnPassant); // e5xf6 e.p.
+    assert_eq!(find_move(&board, &move_gen, 36, 44).flag, MoveFlag::Normal); // e5e6
+
+    let board = Board::new_from_fen("rnbqkbnr/ppp1pppp/8/8/2Pp4/8/PP1PPPPP/RNBQKBNR b KQkq c3 0 3");
+    assert_eq!(find_move(&board, &move_gen, 27, 18).flag, MoveFlag::EnPassant); // d4xc3 e.p.
+}
+
+#[test]
+fn test_move_flags_castling() {
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1");
+    assert_eq!(find_move(&board, &move_gen, 4, 6).flag, MoveFlag::KingsideCastle);
+    assert_eq!(find_move(&board, &move_gen, 4, 2).flag, MoveFlag::QueensideCastle);
+    assert_eq!(find_move(&board, &move_gen, 4, 5).flag, MoveFlag::Normal);
+
+    let board = Board::new_from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1");
+    assert_eq!(find_move(&board, &move_gen, 60, 62).flag, MoveFlag::KingsideCastle);
+    assert_eq!(find_move(&board, &move_gen, 60, 58).flag, MoveFlag::QueensideCastle);
+}
+
+#[test]
+fn test_move_flags_promotion() {
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("1r6/P7/K7/8/k7/8/8/8 w - - 0 1");
+    let (captures, _) = move_gen.gen_pseudo_legal_moves(&board);
+    let promotions: Vec<&Move> = captures.iter().filter(|m| m.from == 48).collect();
+    assert_eq!(promotions.len(), 8);
+    assert!(promotions.iter().all(|m| m.flag == MoveFlag::Promotion && m.promotion.is_some()));
+}
+
+#[test]
+fn test_move_from_uci_sets_flags() {
+    let board = Board::new_from_fen("r3k2r/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/R3K2R w KQkq e6 0 1");
+    assert_eq!(board.move_from_uci("e1g1").unwrap().flag, MoveFlag::KingsideCastle);
+    assert_eq!(board.move_from_uci("e1c1").unwrap().flag, MoveFlag::QueensideCastle);
+    assert_eq!(board.move_from_uci("d5e6").unwrap().flag, MoveFlag::EnPassant);
+    assert_eq!(board.move_from_uci("a2a4").unwrap().flag, MoveFlag::DoublePawnPush);
+    assert_eq!(board.move_from_uci("h1g1").unwrap().flag, MoveFlag::Normal);
+    assert!(board.move_from_uci("e4e5").is_none()); // No piece on e4
+
+    // Applying the parsed en passant capture removes the captured pawn
+    let new_board = board.apply_move_to_board(board.move_from_uci("d5e6").unwrap());
+    assert_eq!(new_board.get_piece(36), None);
+    assert_eq!(new_board.get_piece(44), Some((WHITE, PAWN)));
+}
+
+#[test]
+fn test_en_passant_exposing_king_is_illegal() {
+    // White king a5, pawns d5/e5, black queen h5: d5xe6 e.p. removes both pawns from the
+    // fifth rank and leaves the white king in check, so the capture must be rejected.
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("8/8/8/K2Pp2q/8/8/8/k7 w - e6 0 2");
+    let ep = find_move(&board, &move_gen, 35, 44);
+    assert_eq!(ep.flag, MoveFlag::EnPassant);
+    assert!(!board.apply_move_to_board(ep).is_legal(&move_gen));
+
+    // Without the queen on the rank the same capture is legal.
+    let board = Board::new_from_fen("8/8/8/K2Pp3/8/8/8/k7 w - e6 0 2");
+    let ep = find_move(&board, &move_gen, 35, 44);
+    assert!(board.apply_move_to_board(ep).is_legal(&move_gen));
+}
+
+#[test]
+fn test_independent_move_generators_agree() {
+    // Sliding piece tables are shared, so generators built separately must give the same moves
+    let board = Board::new_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let first = MoveGen::new();
+    let second = MoveGen::new();
+    assert_eq!(first.gen_pseudo_legal_moves(&board), second.gen_pseudo_legal_moves(&board));
+}
+
+#[test]
+fn test_gives_check_direct() {
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1");
+    assert!(board.gives_check(&board.move_from_uci("a1a4").unwrap(), &move_gen));
+    assert!(board.gives_check(&board.move_from_uci("a1e5").unwrap(), &move_gen));
+    assert!(!board.gives_check(&board.move_from_uci("a1a2").unwrap(), &move_gen));
+}
+
+#[test]
+fn test_gives_check_discovered() {
+    let move_gen = MoveGen::new();
+    // Knight on e2 shields the black king from the rook on e1
+    let board = Board::new_from_fen("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1");
+    assert!(board.gives_check(&board.move_from_uci("e2c3").unwrap(), &move_gen));
+    assert!(board.gives_check(&board.move_from_uci("e2g3").unwrap(), &move_gen));
+    assert!(!board.gives_check(&board.move_from_uci("g1h1").unwrap(), &move_gen));
+}
+
+#[test]
+fn test_gives_check_en_passant_discovered() {
+    let move_gen = MoveGen::new();
+    // d5xe6 e.p. clears both pawns from the fifth rank, opening the rook on a5
+    let board = Board::new_from_fen("8/8/8/R2Pp2k/8/8/8/K7 w - e6 0 2");
+    let ep = find_move(&board, &move_gen, 35, 44);
+    assert_eq!(ep.flag, MoveFlag::EnPassant);
+    assert!(board.gives_check(&ep, &move_gen));
+    assert!(!board.gives_check(&board.move_from_uci("d5d6").unwrap(), &move_gen));
+}
+
+#[test]
+fn test_gives_check_promotion_and_castling() {
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("7k/4P3/8/8/8/8/8/K7 w - - 0 1");
+    assert!(board.gives_check(&board.move_from_uci("e7e8q").unwrap(), &move_gen));
+    assert!(!board.gives_check(&board.move_from_uci("e7e8n").unwrap(), &move_gen));
+
+    let board = Board::new_from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1");
+    let castle = board.move_from_uci("e1g1").unwrap();
+    assert_eq!(castle.flag, MoveFlag::KingsideCastle);
+    assert!(board.gives_check(&castle, &move_gen));
+}
+
+#[test]
+fn test_gives_check_matches_making_the_move() {
+    let move_gen = MoveGen::new();
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 b - - 0 10",
+    ];
+    for fen in fens {
+        let board = Board::new_from_fen(fen);
+        let (captures, moves) = move_gen.gen_pseudo_legal_moves(&board);
+        for m in captures.iter().chain(moves.iter()) {
+            let expected = board.apply_move_to_board(*m).is_check(&move_gen);
+            assert_eq!(board.gives_check(m, &move_gen), expected, "{} in {}", m, fen);
+        }
+    }
+}
+
+/// Returns the legal moves of a position in UCI notation, sorted.
+fn legal_moves_uci(fen: &str, move_gen: &MoveGen) -> Vec<String> {
+    let board = Board::new_from_fen(fen);
+    let (captures, moves) = move_gen.gen_pseudo_legal_moves(&board);
+    let mut legal: Vec<String> = captures.into_iter().chain(moves)
+        .filter(|&m| board.apply_move_to_board(m).is_legal(move_gen))
+        .map(|m| m.to_string())
+        .collect();
+    legal.sort();
+    legal
+}
+
+#[test]
+fn test_pinned_pieces_stay_on_the_pin_line() {
+    let move_gen = MoveGen::new();
+    // The knight is pinned and cannot move at all
+    let legal = legal_moves_uci("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1", &move_gen);
+    assert!(legal.iter().all(|m| !m.starts_with("e2")));
+    // The rook may only move along the pin, up to capturing the pinner
+    let legal = legal_moves_uci("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1", &move_gen);
+    let rook_moves: Vec<&String> = legal.iter().filter(|m| m.starts_with("e2")).collect();
+    assert_eq!(rook_moves, ["e2e3", "e2e4", "e2e5", "e2e6", "e2e7"]);
+}
+
+#[test]
+fn test_king_cannot_move_onto_attacked_squares() {
+    let move_gen = MoveGen::new();
+    // d1, e2 and f2 are attacked by the rook, which itself is undefended
+    assert_eq!(legal_moves_uci("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1", &move_gen), ["e1d2", "e1f1"]);
+    // f1 is attacked through the king's own square
+    assert_eq!(legal_moves_uci("4k3/8/8/8/8/8/8/r3K3 w - - 0 1", &move_gen), ["e1d2", "e1e2", "e1f2"]);
+}
+
+#[test]
+fn test_castling_out_of_through_or_into_check_is_illegal() {
+    let move_gen = MoveGen::new();
+    let castles = |fen: &str| legal_moves_uci(fen, &move_gen).into_iter()
+        .filter(|m| m == "e1g1" || m == "e1c1")
+        .collect::<Vec<String>>();
+    assert_eq!(castles("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1", "e1g1"]);
+    // Out of check
+    assert!(castles("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1").is_empty());
+    // Through check
+    assert_eq!(castles("4k3/5r2/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1"]);
+    assert_eq!(castles("4k3/3r4/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1g1"]);
+    // Into check
+    assert_eq!(castles("4k3/6r1/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1"]);
+    // Only the rook passes the attacked b1 square
+    assert_eq!(castles("4k3/1r6/8/8/8/8/8/R3K2R w KQ - 0 1"), ["e1c1", "e1g1"]);
+}
+
+#[test]
+fn test_is_legal_rejects_missing_king() {
+    let move_gen = MoveGen::new();
+    assert!(!Board::new_from_fen("4k3/8/8/8/8/8/8/8 b - - 0 1").is_legal(&move_gen));
+    assert!(Board::new_from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").is_legal(&move_gen));
+}
+
+#[test]
+fn test_move_to_san() {
+    let move_gen = MoveGen::new();
+    let san = |fen: &str, uci: &str| {
+        let board = Board::new_from_fen(fen);
+        board.move_to_san(board.move_from_uci(uci).unwrap(), &move_gen)
+    };
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(san(start, "e2e4"), "e4");
+    assert_eq!(san(start, "g1f3"), "Nf3");
+    assert_eq!(san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", "e4d5"), "exd5");
+    assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+    assert_eq!(san("r3k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"), "O-O");
+    assert_eq!(san("r3k3/8/8/8/8/8/8/4K2R b q - 0 1", "e8c8"), "O-O-O");
+    // Promotion with check, and checkmate
+    assert_eq!(san("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+    assert_eq!(san("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8n"), "axb8=N");
+    assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    // Disambiguation by file, by rank, and by both
+    assert_eq!(san("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1"), "Rad1");
+    assert_eq!(san("R7/8/7k/8/8/8/8/R3K3 w - - 0 1", "a1a4"), "R1a4");
+    assert_eq!(san("8/8/8/7k/8/2Q5/8/2Q1Q2K w - - 0 1", "c1d2"), "Qc1d2");
+}
+
+#[test]
+fn test_move_to_uci() {
+    assert_eq!(Move::from_uci("e2e4").unwrap().to_uci(), "e2e4");
+    assert_eq!(Move::from_uci("e7e8q").unwrap().to_uci(), "e7e8q");
+    assert_eq!(Move::null().to_uci(), "0000");
+}
+
+#[test]
+fn test_promotion_uci_round_trip_for_both_colors() {
+    let move_gen = MoveGen::new();
+    for (fen, from, to) in [("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7", ["a8", "b8"]),
+                            ("4k3/8/8/8/8/8/p7/1N2K3 b - - 0 1", "a2", ["a1", "b1"])] {
+        let board = Board::new_from_fen(fen);
+        let (captures, moves) = move_gen.gen_pseudo_legal_moves(&board);
+        let mut promotions: Vec<String> = captures.into_iter().chain(moves)
+            .filter(|m| m.promotion.is_some())
+            .map(|m| {
+                let uci = m.to_uci();
+                assert_eq!(Move::from_uci(&uci), Some(m));
+                assert_eq!(board.move_from_uci(&uci), Some(m));
+                uci
+            })
+            .collect();
+        promotions.sort();
+        let mut expected: Vec<String> = to.iter()
+            .flat_map(|to| ["b", "n", "q", "r"].map(|piece| format!("{}{}{}", from, to, piece)))
+            .collect();
+        expected.sort();
+        assert_eq!(promotions, expected);
+    }
+}
+
+#[test]
+fn test_gen_checking_moves_matches_filter() {
+    let move_gen = MoveGen::new();
+    for fen in [
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        "4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1",
+        "8/8/8/R2Pp2k/8/8/8/K7 w - e6 0 2",
+        "7k/4P3/8/8/8/8/8/K7 w - - 0 1",
+        "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+        "3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+    ] {
+        let board = Board::new_from_fen(fen);
+        // Every pseudo-legal move that leaves the opponent in check, found by making the move
+        let (captures, moves) = move_gen.gen_pseudo_legal_moves(&board);
+        let expected: Vec<Move> = captures.into_iter().chain(moves)
+            .filter(|&m| board.apply_move_to_board(m).is_check(&move_gen))
+            .collect();
+        assert_eq!(move_gen.gen_checking_moves(&board), expected, "{}", fen);
+    }
+}
@@ -0,0 +1,66 @@
+use kingfisher::agent::SimpleAgent;
+use kingfisher::arena::{play_match, MatchResult};
+use kingfisher::board::{Board, FenError};
+use kingfisher::eval::PestoEval;
+use kingfisher::move_generation::MoveGen;
+use kingfisher::openings::{default_openings, parse_openings, sample_openings, DEFAULT_OPENINGS};
+
+#[test]
+fn test_default_openings_are_legal() {
+    let move_gen = MoveGen::new();
+    let openings = default_openings();
+    assert_eq!(openings.len(), DEFAULT_OPENINGS.len());
+    for (board, fen) in openings.iter().zip(DEFAULT_OPENINGS) {
+        assert_eq!(board.validate_legal(&move_gen), Ok(()), "{}", fen);
+        assert_eq!(board.to_fen(), fen);
+    }
+}
+
+#[test]
+fn test_parse_openings_reads_fen_and_epd() {
+    let text = "
+        # Two openings
+        rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+
+        rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - id \"Sicilian\";
+    ";
+    let openings = parse_openings(text).unwrap();
+    assert_eq!(openings.len(), 2);
+    assert_eq!(openings[0].to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+    assert_eq!(openings[1].to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+
+    assert_eq!(parse_openings("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -\nfoo").err(),
+               Some(FenError::WrongFieldCount(1)));
+}
+
+#[test]
+fn test_match_plays_each_opening_with_both_colors() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let first = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    let second = SimpleAgent::new(2, 2, 4, false, &move_gen, &pesto);
+    // White mates at once, so each agent wins the game in which it plays White
+    let openings = [Board::new_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")];
+    assert_eq!(play_match(&first, &second, &openings, 10), MatchResult { wins: 1, draws: 0, losses: 1 });
+
+    // Bare kings: both games reach the move limit
+    let openings = [Board::new_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1")];
+    assert_eq!(play_match(&first, &second, &openings, 4), MatchResult { wins: 0, draws: 2, losses: 0 });
+}
+
+#[test]
+fn test_sample_openings_is_reproducible() {
+    let openings = default_openings();
+    let fens = |sample: Vec<Board>| sample.iter().map(Board::to_fen).collect::<Vec<String>>();
+    let sample = fens(sample_openings(&openings, 8, 1));
+    assert_eq!(sample.len(), 8);
+    assert_eq!(fens(sample_openings(&openings, 8, 1)), sample);
+    assert_ne!(fens(sample_openings(&openings, 8, 2)), sample);
+
+    // Each opening is drawn at most once, and asking for more than the suite returns all of it
+    let mut all = fens(sample_openings(&openings, 100, 1));
+    assert_eq!(all.len(), openings.len());
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), openings.len());
+}
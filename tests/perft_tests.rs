@@ -264,4 +264,21 @@ fn test_pos5_perft5() {
     let board = Board::new_from_fen("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10");
     let move_gen = MoveGen::new();
     assert_eq!(perft(board, &move_gen, 5, false), 164075551);
-}
\ No newline at end of file
+}
+
+// En passant captures that would expose the capturing side's king along the rank.
+// Both pawns leave the rank at once, so the pseudo-legal en passant capture is generated
+// but must be rejected by the legality check. Only the king moves and the single pawn push remain.
+#[test]
+fn test_en_passant_exposes_king_white_perft1() {
+    let board = Board::new_from_fen("8/8/8/K2Pp2q/8/8/8/k7 w - e6 0 2");
+    let move_gen = MoveGen::new();
+    assert_eq!(perft(board, &move_gen, 1, false), 6);
+}
+
+#[test]
+fn test_en_passant_exposes_king_black_perft1() {
+    let board = Board::new_from_fen("8/8/8/8/k2Pp2Q/8/8/7K b - d3 0 1");
+    let move_gen = MoveGen::new();
+    assert_eq!(perft(board, &move_gen, 1, false), 6);
+}
@@ -1,7 +1,6 @@
 #[cfg(test)]
 mod tests {
     use kingfisher::boardstack::BoardStack;
-    use kingfisher::move_types::Move;
 
     #[test]
     fn test_threefold_repetition() {
@@ -18,7 +17,7 @@ mod tests {
         ];
 
         for mv_str in moves.iter() {
-            let mv = Move::from_uci(mv_str).unwrap();
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
             board.make_move(mv);
         }
 
@@ -38,7 +37,7 @@ mod tests {
         ];
 
         for mv_str in repeating_moves.iter() {
-            let mv = Move::from_uci(mv_str).unwrap();
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
             board.make_move(mv);
         }
 
@@ -70,7 +69,7 @@ mod tests {
         ];
 
         for mv_str in moves.iter() {
-            let mv = Move::from_uci(mv_str).unwrap();
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
             board.make_move(mv);
         }
 
@@ -88,7 +87,7 @@ mod tests {
         ];
 
         for mv_str in repeating_moves.iter() {
-            let mv = Move::from_uci(mv_str).unwrap();
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
             board.make_move(mv);
         }
 
@@ -110,7 +109,7 @@ mod tests {
         ];
 
         for mv_str in moves.iter() {
-            let mv = Move::from_uci(mv_str).unwrap();
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
             board.make_move(mv);
         }
 
@@ -122,10 +121,28 @@ mod tests {
         ];
 
         for mv_str in repeating_moves.iter() {
-            let mv = Move::from_uci(mv_str).unwrap();
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
             board.make_move(mv);
         }
 
         assert!(board.is_draw_by_repetition(), "Should be a draw by repetition");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_undo_restores_position_history() {
+        let mut board = BoardStack::new();
+        let moves = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        for mv_str in moves.iter() {
+            let mv = board.current_state().move_from_uci(mv_str).unwrap();
+            board.make_move(mv);
+        }
+        assert_eq!(board.position_history.len(), 4);
+        assert!(board.position_history.values().any(|&count| count == 2));
+
+        // Undoing the moves should remove exactly the positions that were left
+        board.undo_moves(4);
+        assert_eq!(board.position_history.len(), 1);
+        assert!(board.position_history.values().all(|&count| count == 1));
+    }
+}
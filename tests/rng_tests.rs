@@ -0,0 +1,20 @@
+use kingfisher::rng::SplitMix64;
+
+#[test]
+fn test_splitmix64_reference_output() {
+    // The reference SplitMix64 stream for seed 0; the Zobrist keys depend on it staying fixed
+    let mut rng = SplitMix64::new(0);
+    assert_eq!(rng.next_u64(), 0xe220a8397b1dcdaf);
+    assert_eq!(rng.next_u64(), 0x6e789e6aa1b965f4);
+    assert_eq!(rng.next_u64(), 0x06c45d188009454f);
+}
+
+#[test]
+fn test_next_index_stays_in_range() {
+    let mut rng = SplitMix64::new(7);
+    let mut seen = [false; 5];
+    for _ in 0..1000 {
+        seen[rng.next_index(5)] = true;
+    }
+    assert!(seen.iter().all(|&s| s));
+}
@@ -1,15 +1,22 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use kingfisher::boardstack::BoardStack;
-use kingfisher::search::{mate_search, negamax_search};
+use kingfisher::search::{mate_search, negamax_search, MateSearchMode};
 use kingfisher::move_generation::MoveGen;
-use kingfisher::search::{alpha_beta_search, iterative_deepening_ab_search};
-use kingfisher::eval::PestoEval;
+use kingfisher::search::{alpha_beta_search, analyze_root_moves, aspiration_window_ab_search, AspirationWindow, ASPIRATION_GROWTH, ASPIRATION_INITIAL_DELTA, ASPIRATION_MAX_RESEARCHES, MATE_THRESHOLD, see, see_with_values, iterative_deepening_ab_search, quiescence, QuiescenceMode, ReductionTable, SearchInfo, SearchLimits, LMR_BASE, LMR_DIVISOR, MATE_SCORE, format_uci_score, Pruning, SearchContext};
+use kingfisher::board::Board;
+use kingfisher::move_types::Move;
+use kingfisher::eval::{Evaluator, MaterialEval, PestoEval};
+use kingfisher::piece_types::{PAWN, KNIGHT, ROOK, QUEEN, WHITE, BLACK};
+use kingfisher::eval_constants::PIECE_VALUES;
 use kingfisher::transposition::TranspositionTable;
 
 #[test]
 fn test_mate_in_one_detection() {
     let mut board = BoardStack::new_from_fen("3qk3/3ppp2/8/8/8/8/3PPP2/3QK2R w K - 0 1");
     let move_gen = MoveGen::new();
-    let (score, best_move, _) = mate_search(&mut board, &move_gen, 1, false);
+    let (score, best_move, _, _) = mate_search(&mut board, &move_gen, 1, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
     assert!(score > 900000); // Should detect mate
     assert_eq!(best_move.to, 63); // Rh8# (assuming 0-63 board representation)
 }
@@ -18,26 +25,77 @@ fn test_mate_in_one_detection() {
 fn test_mate_in_two_detection() {
     let mut board = BoardStack::new_from_fen("3qk3/3ppp2/5n2/8/8/8/3PPP2/3QK2R w K - 0 1");
     let move_gen = MoveGen::new();
-    let (score, _, _) = mate_search(&mut board, &move_gen, 1, false);
+    let (score, _, _, _) = mate_search(&mut board, &move_gen, 1, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
     assert!(score < 900000); // Should not detect mate in 1
-    let (score, best_move, _) = mate_search(&mut board, &move_gen, 2, false);
+    let (score, best_move, _, _) = mate_search(&mut board, &move_gen, 2, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
     assert!(score > 900000); // Should detect mate in 2
     assert_eq!(best_move.to, 63); // Rh8+ (assuming 0-63 board representation)
 }
 
+#[test]
+fn test_mate_search_returns_mating_line() {
+    let mut board = BoardStack::new_from_fen("3qk3/3ppp2/5n2/8/8/8/3PPP2/3QK2R w K - 0 1");
+    let move_gen = MoveGen::new();
+    let (score, best_move, _, line) = mate_search(&mut board, &move_gen, 2, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+    assert!(score > 900000);
+    // Rh8+ Ng8 Rxg8#
+    let line: Vec<String> = line.iter().map(|m| m.print_algebraic()).collect();
+    assert_eq!(line, vec!["h1h8", "f6g8", "h8g8"]);
+    assert_eq!(best_move.print_algebraic(), line[0]);
+
+    // No mate, no line
+    let (_, _, _, line) = mate_search(&mut board, &move_gen, 1, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+    assert!(line.is_empty());
+}
+
+#[test]
+fn test_full_mate_search_finds_quiet_first_move() {
+    // 1. Kf7 Kh7 2. Rh1#, where the first move does not give check
+    let mut board = BoardStack::new_from_fen("7k/8/5K2/8/8/8/8/6R1 w - - 0 1");
+    let move_gen = MoveGen::new();
+    let (score, _, _, _) = mate_search(&mut board, &move_gen, 2, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+    assert!(score < 900000); // The checks-only search misses it
+    let (score, best_move, _, line) = mate_search(&mut board, &move_gen, 2, MateSearchMode::Full, &SearchLimits::new(None, None), false);
+    assert!(score > 900000);
+    assert_eq!(best_move.print_algebraic(), "f6f7");
+    let line: Vec<String> = line.iter().map(|m| m.print_algebraic()).collect();
+    assert_eq!(line, vec!["f6f7", "h8h7", "g1h1"]);
+}
+
 #[test]
 fn test_mate_in_three_detection() {
     let mut board = BoardStack::new_from_fen("3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - 0 1");
     let move_gen = MoveGen::new();
-    let (score, _, _) = mate_search(&mut board, &move_gen, 1, false);
+    let (score, _, _, _) = mate_search(&mut board, &move_gen, 1, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
     assert!(score < 900000); // Should not detect mate in 1
-    let (score, _, _) = mate_search(&mut board, &move_gen, 2, false);
+    let (score, _, _, _) = mate_search(&mut board, &move_gen, 2, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
     assert!(score < 900000); // Should not detect mate in 2
-    let (score, best_move, _) = mate_search(&mut board, &move_gen, 3, false);
+    let (score, best_move, _, _) = mate_search(&mut board, &move_gen, 3, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
     assert!(score > 900000); // Should detect mate in 3
     assert_eq!(best_move.to, 63); // Rh8+ (assuming 0-63 board representation)
 }
 
+#[test]
+fn test_mate_search_reports_mate_distance() {
+    let move_gen = MoveGen::new();
+    let mate_in_one = "3qk3/3ppp2/8/8/8/8/3PPP2/3QK2R w K - 0 1";
+    let mate_in_two = "3qk3/3ppp2/5n2/8/8/8/3PPP2/3QK2R w K - 0 1";
+    let mate_in_three = "3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - 0 1";
+    for (fen, plies) in [(mate_in_one, 1), (mate_in_two, 3), (mate_in_three, 5)] {
+        let mut board = BoardStack::new_from_fen(fen);
+        // Searching deeper than needed still reports the shortest mate
+        let (score, _, _, line) = mate_search(&mut board, &move_gen, 4, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+        assert_eq!(score, MATE_SCORE - plies, "{}", fen);
+        assert_eq!(line.len() as i32, plies, "{}", fen);
+    }
+
+    let mut board = BoardStack::new();
+    let (score, best_move, _, line) = mate_search(&mut board, &move_gen, 2, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+    assert_eq!(score, 0);
+    assert!(best_move == Move::null());
+    assert!(line.is_empty());
+}
+
 #[test]
 fn test_alpha_beta_pruning_effectiveness() {
     let mut board = BoardStack::new_from_fen("r1bqkbnr/ppp2ppp/2np4/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4");
@@ -47,10 +105,10 @@ fn test_alpha_beta_pruning_effectiveness() {
 
     let depth = 4;
     let infinity = 1000000;
-    let (score_full, _, nodes_full, _) = alpha_beta_search(&mut board, &move_gen, &pesto, &mut tt, depth, -infinity, infinity, 0, false, None, None);
+    let (score_full, _, nodes_full) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(0), depth, -infinity, infinity);
 
     // Now search with a narrow window
-    let (score_narrow, _, nodes_narrow, _) = alpha_beta_search(&mut board, &move_gen, &pesto, &mut tt, depth, score_full - 50, score_full + 50, 0, false, None, None);
+    let (score_narrow, _, nodes_narrow) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(0), depth, score_full - 50, score_full + 50);
 
     println!("Full window (White) - Score: {}, Nodes: {}", score_full, nodes_full);
     println!("Narrow window (White) - Score: {}, Nodes: {}", score_narrow, nodes_narrow);
@@ -60,8 +118,8 @@ fn test_alpha_beta_pruning_effectiveness() {
 
     // Test for black
     board = BoardStack::new_from_fen("r1bqkbnr/ppp2ppp/2np4/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 4");
-    let (score_full_black, _, nodes_full_black, _) = alpha_beta_search(&mut board, &move_gen, &pesto, &mut tt, depth, -infinity, infinity, 0, false, None, None);
-    let (score_narrow_black, _, nodes_narrow_black, _) = alpha_beta_search(&mut board, &move_gen, &pesto, &mut tt, depth, score_full_black - 50, score_full_black + 50, 0, false, None, None);
+    let (score_full_black, _, nodes_full_black) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(0), depth, -infinity, infinity);
+    let (score_narrow_black, _, nodes_narrow_black) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(0), depth, score_full_black - 50, score_full_black + 50);
 
     println!("Full window (Black) - Score: {}, Nodes: {}", score_full_black, nodes_full_black);
     println!("Narrow window (Black) - Score: {}, Nodes: {}", score_narrow_black, nodes_narrow_black);
@@ -78,8 +136,8 @@ fn test_search_stability() {
 
     let max_depth = 6;
     let q_search_max_depth = 99;
-    let (depth1, score1, best_move1, eval1) = iterative_deepening_ab_search(&mut board, &move_gen, &pesto, max_depth, q_search_max_depth, None, false);
-    let (depth2, score2, best_move2, eval2) = iterative_deepening_ab_search(&mut board, &move_gen, &pesto, max_depth, q_search_max_depth, None, false);
+    let (depth1, score1, best_move1, eval1) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(q_search_max_depth), max_depth);
+    let (depth2, score2, best_move2, eval2) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(q_search_max_depth), max_depth);
 
     // The scores and best moves should be the same across multiple runs
     assert_eq!(depth1, depth2);
@@ -98,10 +156,672 @@ fn test_pruning() {
     let mut tt = TranspositionTable::new();
     for depth in 1..6 {
         let (negamax_eval, negamax_move, negamax_nodes) = negamax_search(&mut board, &move_gen, &pesto, depth);
-        let (alpha_beta_eval, alpha_beta_move, alpha_beta_nodes, _) = alpha_beta_search(&mut board, &move_gen, &pesto, &mut tt, depth, -1000000, 1000000, 0, false, None, None);
+        let (alpha_beta_eval, alpha_beta_move, alpha_beta_nodes) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_q_search_depth(0), depth, -1000000, 1000000);
         assert!(negamax_eval == alpha_beta_eval, "Evals don't match for depth {}, negamax eval: {}, alpha-beta eval: {}", depth, negamax_eval, alpha_beta_eval);
         assert!(negamax_move == alpha_beta_move, "Moves don't match for depth {}, negamax move: {}, alpha-beta move: {}", depth, negamax_move.print_algebraic(), alpha_beta_move.print_algebraic());
         println!("Move, eval = {}, {}", &negamax_move.print_algebraic(), negamax_eval);
         println!("Depth: {}, Negamax nodes: {}, Alpha-beta nodes: {}", depth, negamax_nodes, alpha_beta_nodes);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_contempt_avoids_fifty_move_draw() {
+    // White is slightly worse and the halfmove clock is at 99: any king move draws by the
+    // fifty-move rule, while a pawn move resets the clock and keeps the (slightly worse) game going.
+    let fen = "4k3/2p5/8/8/8/8/P7/4K3 w - - 99 80";
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Without contempt the draw is preferred
+    let mut board = BoardStack::new_from_fen(fen);
+    let mut tt = TranspositionTable::new();
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 2, -1000000, 1000000);
+    assert_eq!(score, 0);
+    assert_eq!(best_move.from, 4); // King move
+
+    // With contempt the engine keeps playing
+    let mut board = BoardStack::new_from_fen(fen);
+    let mut tt = TranspositionTable::new();
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)).with_contempt(50), 2, -1000000, 1000000);
+    assert!(score > -50 && score < 0);
+    assert_eq!(best_move.from, 8); // Pawn move
+
+    // The aspiration window search applies the same contempt
+    let mut board = BoardStack::new_from_fen(fen);
+    let limits = SearchLimits::new(None, None);
+    let ctx = SearchContext::new(&move_gen, &pesto, &limits).with_contempt(50);
+    let (_, score, best_move, _) = aspiration_window_ab_search(&mut board, &mut TranspositionTable::new(), &ctx, 1, &AspirationWindow::default());
+    assert!(score > -50 && score < 0);
+    assert_eq!(best_move.from, 8);
+}
+
+// Forward pruning (futility pruning and razoring) should reduce the tree on quiet positions
+// without changing the solutions to tactical positions.
+#[test]
+fn test_forward_pruning() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Quiet position: the same search with futility pruning and razoring turned off must visit more nodes
+    let fen = "r1bqkbnr/ppp2ppp/2np4/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4";
+    let limits = SearchLimits::new(None, None);
+    let unpruned = Pruning { futility: false, razoring: false, ..Pruning::ALL };
+    let (_, best_move, nodes) = alpha_beta_search(&mut BoardStack::new_from_fen(fen), &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 5, -1000000, 1000000);
+    let (_, _, unpruned_nodes) = alpha_beta_search(&mut BoardStack::new_from_fen(fen), &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits).with_pruning(unpruned), 5, -1000000, 1000000);
+    assert!(nodes < unpruned_nodes, "Forward pruning did not reduce the node count: {} vs {}", nodes, unpruned_nodes);
+    assert_eq!(best_move.print_algebraic(), "d2d4");
+
+    // Tactical positions: (fen, solution)
+    let tactics = [
+        ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7"),
+        ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "d1d8"),
+        ("r3k2r/ppp2ppp/2n5/3q4/3P4/2N5/PPP2PPP/R2QK2R w KQkq - 0 1", "c3d5"),
+    ];
+    for (fen, solution) in tactics.iter() {
+        let mut board = BoardStack::new_from_fen(fen);
+        let mut tt = TranspositionTable::new();
+        let (_, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4, -1000000, 1000000);
+        assert_eq!(best_move.print_algebraic(), *solution, "Wrong solution for {}", fen);
+    }
+}
+
+// Reverse futility pruning should cut quiet middlegame nodes without changing tactical solutions.
+#[test]
+fn test_reverse_futility_pruning() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Quiet middlegame position: the same search without reverse futility pruning must visit more nodes
+    let fen = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8";
+    let limits = SearchLimits::new(None, None);
+    let unpruned = Pruning { reverse_futility: false, ..Pruning::ALL };
+    let (_, _, nodes) = alpha_beta_search(&mut BoardStack::new_from_fen(fen), &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 5, -1000000, 1000000);
+    let (_, _, unpruned_nodes) = alpha_beta_search(&mut BoardStack::new_from_fen(fen), &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits).with_pruning(unpruned), 5, -1000000, 1000000);
+    assert!(nodes < unpruned_nodes, "Reverse futility pruning did not reduce the node count: {} vs {}", nodes, unpruned_nodes);
+
+    // Tactical positions, including forced mates which must not be pruned away
+    let tactics = [
+        ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7", true),
+        ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "d1d8", true),
+        ("r3k2r/ppp2ppp/2n5/3q4/3P4/2N5/PPP2PPP/R2QK2R w KQkq - 0 1", "c3d5", false),
+    ];
+    for (fen, solution, is_mate) in tactics.iter() {
+        let mut board = BoardStack::new_from_fen(fen);
+        let mut tt = TranspositionTable::new();
+        let (eval, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 5, -1000000, 1000000);
+        assert_eq!(best_move.print_algebraic(), *solution, "Wrong solution for {}", fen);
+        assert_eq!(eval > 900000, *is_mate, "Wrong score for {}: {}", fen, eval);
+    }
+}
+
+/// An evaluator that counts how often it is called
+struct CountingEval {
+    inner: PestoEval,
+    calls: Cell<u64>,
+}
+
+impl Evaluator for CountingEval {
+    fn eval(&self, board: &Board, move_gen: &MoveGen) -> i32 {
+        self.calls.set(self.calls.get() + 1);
+        self.inner.eval(board, move_gen)
+    }
+
+    fn move_eval(&self, board: &Board, move_gen: &MoveGen, from_sq_ind: usize, to_sq_ind: usize) -> i32 {
+        self.inner.move_eval(board, move_gen, from_sq_ind, to_sq_ind)
+    }
+}
+
+// The static eval is computed at most once per node, and reused by forward pruning and quiescence search.
+#[test]
+fn test_static_eval_computed_once_per_node() {
+    let mut board = BoardStack::new_from_fen("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8");
+    let move_gen = MoveGen::new();
+    let counting = CountingEval { inner: PestoEval::new(), calls: Cell::new(0) };
+    let mut tt = TranspositionTable::new();
+    let (_, _, nodes) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &counting, &SearchLimits::new(None, None)), 3, -1000000, 1000000);
+    assert!(counting.calls.get() <= nodes as u64, "{} evals for {} nodes", counting.calls.get(), nodes);
+}
+
+// Quiescence search must not stand pat when in check: the checked side has to play an evasion.
+#[test]
+fn test_quiescence_search_in_check() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Nxf7+ forks king and queen; standing pat after the check would miss that the queen is lost
+    let mut board = BoardStack::new_from_fen("3q3k/5ppp/8/6N1/8/8/5PPP/6K1 w - - 0 1");
+    let mut tt = TranspositionTable::new();
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 1, -1000000, 1000000);
+    assert_eq!(best_move.print_algebraic(), "g5f7");
+    assert!(score > 0, "Quiescence search overlooked the fork: {}", score);
+
+    // Qxf7# leaves no evasions, so quiescence search must report checkmate
+    let mut board = BoardStack::new_from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4");
+    let mut tt = TranspositionTable::new();
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 1, -1000000, 1000000);
+    assert_eq!(best_move.print_algebraic(), "h5f7");
+    assert_eq!(score, 1000000 - 1);
+}
+
+#[test]
+fn test_quiescence_resolves_hanging_piece() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // The black queen on d5 is hanging to the e4 pawn
+    let board = Board::new_from_fen("rnb1kbnr/ppp1pppp/8/3q4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3");
+    let static_eval = pesto.eval(&board, &move_gen);
+    let (q_eval, nodes) = quiescence(&board, &move_gen, &pesto, -1000000, 1000000, 4, QuiescenceMode::CapturesOnly);
+    assert!(nodes > 1);
+    assert!(q_eval > static_eval + 500, "Static eval {}, quiescence {}", static_eval, q_eval);
+}
+
+#[test]
+fn test_quiescence_checks_mode() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Rd8# is a quiet move, so only the checks mode sees the mate
+    let board = Board::new_from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+    let (captures_only, _) = quiescence(&board, &move_gen, &pesto, -1000000, 1000000, 4, QuiescenceMode::CapturesOnly);
+    let (with_checks, _) = quiescence(&board, &move_gen, &pesto, -1000000, 1000000, 4, QuiescenceMode::CapturesAndChecks);
+    assert!(captures_only < 900000);
+    assert_eq!(with_checks, 1000000 - 1);
+}
+
+#[test]
+fn test_search_is_deterministic() {
+    // Zobrist keys come from a fixed seed and the in-tree generator, so hashes are identical across
+    // runs and dependency upgrades
+    assert_eq!(Board::new().zobrist_hash(), 0xdc32c113737bd822);
+
+    // Two independent searches of the same position agree on everything, including node counts
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let fen = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8";
+    let mut board1 = BoardStack::new_from_fen(fen);
+    let mut board2 = BoardStack::new_from_fen(fen);
+    let result1 = iterative_deepening_ab_search(&mut board1, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4);
+    let result2 = iterative_deepening_ab_search(&mut board2, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4);
+    assert_eq!(result1, result2);
+}
+
+#[test]
+fn test_seldepth_reaches_search_depth() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut tt = TranspositionTable::new();
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    ] {
+        let mut board = BoardStack::new_from_fen(fen);
+        for depth in 1..4 {
+            tt.clear();
+            alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), depth, -1000000, 1000000);
+            assert!(board.seldepth() >= depth as usize, "seldepth {} below depth {} in {}", board.seldepth(), depth, fen);
+        }
+        // The search must leave the board where it started
+        assert_eq!(board.current_state().to_fen(), fen);
+    }
+}
+
+#[test]
+fn test_depth_limit() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut board = BoardStack::new();
+    let (depth, _, best_move, _) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4);
+    assert_eq!(depth, 4);
+    assert_ne!(best_move, kingfisher::move_types::Move::null());
+}
+
+#[test]
+fn test_node_limit() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ] {
+        for node_limit in [5000, 50000] {
+            let mut board = BoardStack::new_from_fen(fen);
+            let (depth, _, _, nodes) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, Some(node_limit as u64))), 100);
+            // Only the quiescence search at the last leaf can run past the limit
+            assert!(nodes >= node_limit && nodes <= node_limit + 100, "searched {} nodes with a limit of {}", nodes, node_limit);
+            assert!(depth < 100);
+            assert_eq!(board.current_state().to_fen(), fen);
+        }
+    }
+}
+
+#[test]
+fn test_mate_distance_pruning() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Forced mate in 3 starting with Rh8+; the node counts were measured without mate distance pruning
+    for (fen, best, unpruned_nodes, mate_plies) in [
+        ("3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - 0 1", "h1h8", 37374, 5),
+        ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7", 220227, 1),
+    ] {
+        let mut board = BoardStack::new_from_fen(fen);
+        let mut tt = TranspositionTable::new();
+        let (score, best_move, nodes) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 6, -1000000, 1000000);
+        assert_eq!(best_move.print_algebraic(), best);
+        assert_eq!(score, MATE_SCORE - mate_plies);
+        assert!(nodes < unpruned_nodes, "{} nodes for {}", nodes, fen);
+    }
+}
+
+#[test]
+fn test_format_uci_score() {
+    assert_eq!(format_uci_score(35), "cp 35");
+    assert_eq!(format_uci_score(-120), "cp -120");
+    // Mating: the last move is ours, so mate in N takes 2N - 1 plies
+    assert_eq!(format_uci_score(MATE_SCORE - 1), "mate 1");
+    assert_eq!(format_uci_score(MATE_SCORE - 5), "mate 3");
+    // Being mated: the last move is the opponent's, so mated in N takes 2N plies
+    assert_eq!(format_uci_score(-MATE_SCORE + 2), "mate -1");
+    assert_eq!(format_uci_score(-MATE_SCORE + 6), "mate -3");
+}
+
+#[test]
+fn test_mate_in_three_reports_mate_score() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut board = BoardStack::new_from_fen("3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - 0 1");
+    let (_, score, best_move, _) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 6);
+    assert_eq!(best_move.print_algebraic(), "h1h8");
+    assert_eq!(format_uci_score(score), "mate 3");
+
+    // After Rh8+ Rg8 Rxg8+ the defender is mated on the next move
+    board.make_move(best_move);
+    let reply = board.current_state().move_from_uci("g7g8").unwrap();
+    board.make_move(reply);
+    board.make_move(board.current_state().move_from_uci("h8g8").unwrap());
+    let (_, score, _, _) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4);
+    assert_eq!(format_uci_score(score), "mate -1");
+}
+
+#[test]
+fn test_fifty_move_rule_draws_unless_mate() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut tt = TranspositionTable::new();
+
+    // Every move reaches the fifty-move limit, so the extra rook is worth nothing
+    let mut board = BoardStack::new_from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 99 80");
+    let (score, _, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 3, -MATE_SCORE, MATE_SCORE);
+    assert_eq!(score, 0);
+
+    let mut board = BoardStack::new_from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 80");
+    tt.clear();
+    let (score, _, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 3, -MATE_SCORE, MATE_SCORE);
+    assert!(score > 300);
+
+    // Checkmate on the move that reaches the limit still wins
+    let mut board = BoardStack::new_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80");
+    tt.clear();
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 3, -MATE_SCORE, MATE_SCORE);
+    assert_eq!(score, MATE_SCORE - 1);
+    assert_eq!(best_move, board.current_state().move_from_uci("a1a8").unwrap());
+    let (score, _, _, _) = mate_search(&mut board, &move_gen, 1, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+    assert_eq!(score, MATE_SCORE - 1);
+
+    // A longer mate cannot be completed before the game is drawn
+    let mut board = BoardStack::new_from_fen("7k/8/5K2/8/8/8/8/6R1 w - - 99 80");
+    let (score, _, _, _) = mate_search(&mut board, &move_gen, 2, MateSearchMode::Full, &SearchLimits::new(None, None), false);
+    assert_eq!(score, 0);
+}
+
+#[test]
+fn test_search_avoids_stalemating_lone_king() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // The black king is already boxed in, so any quiet move that keeps it that way is stalemate
+    for fen in ["7k/8/6Q1/8/8/8/8/K7 w - - 0 1", "7k/5Q2/8/8/8/8/8/4K3 w - - 0 1"] {
+        for depth in 1..=3 {
+            let mut board = BoardStack::new_from_fen(fen);
+            let mut tt = TranspositionTable::new();
+            let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), depth, -MATE_SCORE, MATE_SCORE);
+            let after = board.current_state().apply_move_to_board(best_move);
+            assert!(!after.is_checkmate_or_stalemate(&move_gen).1, "{} at depth {} stalemates with {}", fen, depth, best_move);
+            assert!(score > 0);
+        }
+    }
+}
+
+#[test]
+fn test_search_avoids_stalemate_with_blocked_pawns() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // As above, but Black also has pawns, which are blocked and so cannot move either
+    let fens = [
+        "7k/p7/P5Q1/8/8/8/8/K7 w - - 0 1",
+        "7k/5Q2/8/8/1p6/1P6/8/4K3 w - - 0 1",
+        "7k/p4Q2/P7/8/8/6p1/6P1/4K3 w - - 0 1",
+    ];
+    for fen in fens {
+        for depth in 1..=3 {
+            let mut board = BoardStack::new_from_fen(fen);
+            let mut tt = TranspositionTable::new();
+            let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), depth, -MATE_SCORE, MATE_SCORE);
+            let after = board.current_state().apply_move_to_board(best_move);
+            assert!(!after.is_checkmate_or_stalemate(&move_gen).1, "{} at depth {} stalemates with {}", fen, depth, best_move);
+            assert!(score > 0);
+        }
+    }
+}
+
+#[test]
+fn test_alpha_beta_with_material_eval() {
+    let move_gen = MoveGen::new();
+    let mut tt = TranspositionTable::new();
+
+    // The undefended queen can be taken, leaving a rook ahead
+    let mut board = BoardStack::new_from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1");
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &MaterialEval, &SearchLimits::new(None, None)), 2, -MATE_SCORE, MATE_SCORE);
+    assert_eq!(best_move, board.current_state().move_from_uci("d1d5").unwrap());
+    assert_eq!(score, PIECE_VALUES[ROOK]);
+
+    // Material eval still finds mates
+    let mut board = BoardStack::new_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    tt.clear();
+    let (score, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &MaterialEval, &SearchLimits::new(None, None)), 3, -MATE_SCORE, MATE_SCORE);
+    assert_eq!(score, MATE_SCORE - 1);
+    assert_eq!(best_move, board.current_state().move_from_uci("a1a8").unwrap());
+}
+
+#[test]
+fn test_search_restricted_to_root_moves() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut tt = TranspositionTable::new();
+
+    // Taking the undefended queen is best, but only the listed king and pawn moves are allowed
+    let mut board = BoardStack::new_from_fen("4k3/8/8/3q4/8/8/P7/3RK3 w - - 0 1");
+    let allowed: Vec<Move> = ["e1f2", "a2a3"].iter().map(|m| board.current_state().move_from_uci(m).unwrap()).collect();
+    let limits = SearchLimits::new(None, None).with_root_moves(allowed.clone());
+    let (_, _, best_move, _) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &limits), 4);
+    assert!(allowed.contains(&best_move), "{} is not an allowed move", best_move);
+
+    // Without the restriction the queen is taken
+    tt.clear();
+    let (_, _, best_move, _) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4);
+    assert_eq!(best_move, board.current_state().move_from_uci("d1d5").unwrap());
+}
+
+#[test]
+fn test_see() {
+    let move_gen = MoveGen::new();
+    let see_of = |fen: &str, uci: &str| {
+        let board = Board::new_from_fen(fen);
+        see(&board, &move_gen, board.move_from_uci(uci).unwrap())
+    };
+    let (pawn, knight, rook) = (PIECE_VALUES[PAWN], PIECE_VALUES[KNIGHT], PIECE_VALUES[ROOK]);
+
+    // Undefended rook
+    assert_eq!(see_of("4k3/8/8/3r4/8/8/8/3QK3 w - - 0 1", "d1d5"), rook);
+    // Knight defended by a pawn
+    assert_eq!(see_of("4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1", "d4e5"), knight - pawn);
+    // Pawn defended by a pawn
+    assert_eq!(see_of("4k3/8/3p4/4p3/8/8/8/4R1K1 w - - 0 1", "e1e5"), pawn - rook);
+    // The second rook behind the first wins the exchange back
+    assert_eq!(see_of("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5"), pawn);
+    // The king recaptures an undefended rook, but not a defended one
+    assert_eq!(see_of("8/8/8/4k3/4p3/8/4R3/6K1 w - - 0 1", "e2e4"), pawn - rook);
+    assert_eq!(see_of("8/8/8/4k3/4p3/8/4R3/4R1K1 w - - 0 1", "e2e4"), pawn);
+    // A quiet move onto a square attacked by a pawn loses the piece
+    assert_eq!(see_of("4k3/8/3p4/8/8/3N4/8/4K3 w - - 0 1", "d3e5"), -knight);
+    assert_eq!(see_of("4k3/8/3p4/8/8/3N4/8/4K3 w - - 0 1", "d3f4"), 0);
+}
+
+#[test]
+fn test_see_uses_shared_piece_values() {
+    let move_gen = MoveGen::new();
+    // The queen takes a pawn defended by a pawn
+    let board = Board::new_from_fen("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1");
+    let mv = board.move_from_uci("e1e5").unwrap();
+    assert_eq!(see(&board, &move_gen, mv), PIECE_VALUES[PAWN] - PIECE_VALUES[QUEEN]);
+
+    // SEE and material counting agree on the trade
+    let after = board.apply_move_to_board(mv);
+    let recaptured = after.apply_move_to_board(after.move_from_uci("d6e5").unwrap());
+    assert_eq!(recaptured.material(WHITE) - board.material(WHITE) - (recaptured.material(BLACK) - board.material(BLACK)), see(&board, &move_gen, mv));
+
+    // Other values can be tried for tuning
+    let mut values = PIECE_VALUES;
+    values[QUEEN] = 1000;
+    assert_eq!(see_with_values(&board, &move_gen, mv, &values), PIECE_VALUES[PAWN] - 1000);
+}
+
+#[test]
+fn test_see_en_passant() {
+    let move_gen = MoveGen::new();
+    let see_of = |fen: &str, uci: &str| {
+        let board = Board::new_from_fen(fen);
+        see(&board, &move_gen, board.move_from_uci(uci).unwrap())
+    };
+
+    // The captured pawn is on d5, not on the destination square
+    assert_eq!(see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), PIECE_VALUES[PAWN]);
+    // The king recaptures on d6
+    assert_eq!(see_of("8/4k3/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 0);
+    // Removing the captured pawn opens the d-file for the white rook to recapture
+    assert_eq!(see_of("3r2k1/8/8/3pP3/8/8/8/3R2K1 w - d6 0 1", "e5d6"), PIECE_VALUES[PAWN]);
+}
+
+#[test]
+fn test_quiescence_depth_is_capped() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Both queens can give checks and take pawns with check, so an unbounded quiescence search
+    // could keep going; an absurd maximum depth gives the same result as the built-in cap
+    for fen in ["4r1k1/5ppp/8/1q6/8/1Q6/5PPP/4R1K1 w - - 0 1", "6k1/5p1p/6pQ/8/8/8/q4PPP/6K1 w - - 0 1"] {
+        let board = Board::new_from_fen(fen);
+        let capped = quiescence(&board, &move_gen, &pesto, -MATE_SCORE, MATE_SCORE, 32, QuiescenceMode::CapturesAndChecks);
+        let unbounded = quiescence(&board, &move_gen, &pesto, -MATE_SCORE, MATE_SCORE, i32::MAX, QuiescenceMode::CapturesAndChecks);
+        assert_eq!(unbounded, capped);
+        assert!(unbounded.0.abs() < 900000, "{} scored {}", fen, unbounded.0);
+    }
+}
+
+#[test]
+fn test_analyze_root_moves_reports_refutation() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut tt = TranspositionTable::new();
+
+    // Taking the pawn on d5 loses the queen to the knight
+    let mut board = BoardStack::new_from_fen("4k3/8/5n2/3p4/8/8/8/3QK3 w - - 0 1");
+    let analysis = analyze_root_moves(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 3);
+    let capture = board.current_state().move_from_uci("d1d5").unwrap();
+    let capture_analysis = analysis.iter().find(|root_move| root_move.mv == capture).unwrap();
+    assert_eq!(capture_analysis.reply, Some(Move::new(45, 35, None)));
+    assert!(capture_analysis.score < -200);
+
+    // Every legal move is reported, best first
+    assert_eq!(analysis.len(), 18);
+    assert!(analysis[0].score > 0);
+    assert!(analysis.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    assert_eq!(board.current_state().to_fen(), "4k3/8/5n2/3p4/8/8/8/3QK3 w - - 0 1");
+}
+
+#[test]
+fn test_reduction_table() {
+    let table = ReductionTable::new(LMR_BASE, LMR_DIVISOR);
+    assert_eq!(table.reduction(1, 1), 0);
+    assert_eq!(table.reduction(0, 10), 0);
+    // Reductions never shrink with depth or move number, and grow for late moves at high depth
+    for depth in 1..64 {
+        for move_number in 1..64 {
+            assert!(table.reduction(depth, move_number + 1) >= table.reduction(depth, move_number));
+            assert!(table.reduction(depth + 1, move_number) >= table.reduction(depth, move_number));
+        }
+    }
+    assert!(table.reduction(10, 30) >= 2);
+    // Out of range depths and move numbers use the last entry
+    assert_eq!(table.reduction(200, 200), table.reduction(63, 63));
+
+    // A larger divisor reduces less
+    let gentle = ReductionTable::new(LMR_BASE, 2.0 * LMR_DIVISOR);
+    assert!(gentle.reduction(10, 30) < table.reduction(10, 30));
+}
+
+// Late move reductions should cut quiet middlegame nodes without changing tactical solutions.
+#[test]
+fn test_late_move_reductions() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // Quiet positions: (fen, best move), each also searched without late move reductions
+    let quiet = [
+        ("r1bqkbnr/ppp2ppp/2np4/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4", "d2d4"),
+        ("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8", "b2b3"),
+        ("rnbqkb1r/pp2pppp/3p1n2/8/3NP3/8/PPP2PPP/RNBQKB1R w KQkq - 1 5", "f1b5"),
+        ("r2q1rk1/ppp2ppp/2np1n2/2b1p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2Q1RK1 w - - 0 8", "h2h3"),
+    ];
+    let limits = SearchLimits::new(None, None);
+    let unreduced = Pruning { late_move_reductions: false, ..Pruning::ALL };
+    let mut total_nodes = 0;
+    let mut total_unreduced_nodes = 0;
+    for (fen, best) in quiet {
+        let (_, best_move, nodes) = alpha_beta_search(&mut BoardStack::new_from_fen(fen), &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 5, -1000000, 1000000);
+        let (_, _, unreduced_nodes) = alpha_beta_search(&mut BoardStack::new_from_fen(fen), &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits).with_pruning(unreduced), 5, -1000000, 1000000);
+        assert_eq!(best_move.to_uci(), best, "Wrong move for {}", fen);
+        total_nodes += nodes;
+        total_unreduced_nodes += unreduced_nodes;
+    }
+    assert!(total_nodes < total_unreduced_nodes * 3 / 4, "{} nodes, {} without reductions", total_nodes, total_unreduced_nodes);
+
+    // Tactical positions, including forced mates which must not be reduced away
+    let tactics = [
+        ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7", true),
+        ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "d1d8", true),
+        ("r3k2r/ppp2ppp/2n5/3q4/3P4/2N5/PPP2PPP/R2QK2R w KQkq - 0 1", "c3d5", false),
+        ("3qk3/3pppr1/5n2/8/8/8/3PPP2/3QK1RR w K - 0 1", "h1h8", true),
+    ];
+    for (fen, solution, is_mate) in tactics {
+        let mut board = BoardStack::new_from_fen(fen);
+        let mut tt = TranspositionTable::new();
+        let (eval, best_move, _) = alpha_beta_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 6, -1000000, 1000000);
+        assert_eq!(best_move.to_uci(), solution, "Wrong solution for {}", fen);
+        assert_eq!(eval > 900000, is_mate, "Wrong score for {}: {}", fen, eval);
+    }
+}
+
+#[test]
+fn test_only_move_returned_at_once() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+
+    // The king's only legal move is to take the checking queen
+    let fen = "7k/8/8/8/8/8/1q6/K7 w - - 0 1";
+    let mut board = BoardStack::new_from_fen(fen);
+    let limits = SearchLimits::new(Some(Duration::from_secs(10)), None);
+    let (depth, _, best_move, nodes) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 100);
+    assert_eq!(best_move.to_uci(), "a1b2");
+    assert_eq!(depth, 1);
+    assert!(nodes < 100, "searched {} nodes", nodes);
+    assert!(limits.elapsed() < Duration::from_secs(1));
+
+    // The same holds without a time limit, e.g. for a fixed-depth search
+    let mut board = BoardStack::new_from_fen(fen);
+    let (depth, _, best_move, nodes) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 100);
+    assert_eq!(best_move.to_uci(), "a1b2");
+    assert_eq!(depth, 1);
+    assert!(nodes < 100, "searched {} nodes", nodes);
+}
+
+#[test]
+fn test_info_sink_receives_each_completed_depth() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut board = BoardStack::new_from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+
+    let infos: Rc<RefCell<Vec<SearchInfo>>> = Rc::new(RefCell::new(Vec::new()));
+    let sink = Rc::clone(&infos);
+    let limits = SearchLimits::new(None, None)
+        .with_info_sink(Box::new(move |info| sink.borrow_mut().push(info.clone())));
+    let (depth, eval, best_move, nodes) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 5);
+
+    // Even depths are searched, then the odd maximum depth
+    let infos = infos.borrow();
+    assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<_>>(), vec![2, 4, 5]);
+    let last = infos.last().unwrap();
+    assert_eq!(last.depth, depth);
+    assert_eq!(last.score, eval);
+    assert_eq!(last.nodes, nodes as u64);
+    assert_eq!(last.pv.first(), Some(&best_move));
+    assert!(infos.windows(2).all(|pair| pair[0].nodes < pair[1].nodes));
+    assert!(last.to_uci().starts_with("info depth 5 seldepth "));
+}
+
+#[test]
+fn test_aspiration_window_default_schedule() {
+    let window = AspirationWindow::default();
+    assert_eq!(window.initial_delta, ASPIRATION_INITIAL_DELTA);
+    assert_eq!(window.growth, ASPIRATION_GROWTH);
+    assert_eq!(window.max_researches, Some(ASPIRATION_MAX_RESEARCHES));
+}
+
+#[test]
+fn test_aspiration_window_converges_on_eval_swing() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let schedules = [
+        AspirationWindow::default(),
+        // Slow growth with no limit still reaches the full window
+        AspirationWindow::new(1, 1.5, None),
+        // A catastrophic fail goes straight to the full window
+        AspirationWindow::new(1, 1.5, Some(1)),
+        AspirationWindow::new(10, 4.0, Some(0)),
+    ];
+
+    // The static eval of each position is far from its score: a hanging queen, and a mate in one
+    for (fen, expected) in [("4k3/8/8/3q4/8/8/8/3QK3 w - - 0 1", "d1d5"), ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8")] {
+        for window in &schedules {
+            let mut board = BoardStack::new_from_fen(fen);
+            let (_, eval, best_move, _) = aspiration_window_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 2, window);
+            assert_eq!(best_move.to_uci(), expected, "{:?} in {}", window, fen);
+            assert!(eval > 700, "eval {} with {:?} in {}", eval, window, fen);
+        }
+    }
+
+    let mut board = BoardStack::new_from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    let (_, eval, _, _) = aspiration_window_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 2, &AspirationWindow::new(1, 1.5, None));
+    assert!(eval > MATE_THRESHOLD);
+}
+
+#[test]
+fn test_aspiration_window_search_stops_at_limits() {
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let fen = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8";
+
+    // A node limit stops the search partway, keeping the last completed depth
+    let limits = SearchLimits::new(None, Some(20_000));
+    let mut board = BoardStack::new_from_fen(fen);
+    let (depth, _, best_move, nodes) = aspiration_window_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 50, &AspirationWindow::default());
+    assert!(limits.stopped());
+    assert!(depth >= 2 && depth < 100, "depth {}", depth);
+    assert!((nodes as u64) < 40_000, "searched {} nodes", nodes);
+    assert!(board.current_state().apply_move_to_board(best_move).is_legal(&move_gen));
+
+    // So does a time limit
+    let limits = SearchLimits::new(Some(Duration::from_millis(50)), None);
+    let start = Instant::now();
+    let (depth, _, _, _) = aspiration_window_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &limits), 50, &AspirationWindow::default());
+    assert!(depth < 100);
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+#[should_panic(expected = "must grow")]
+fn test_aspiration_window_must_grow() {
+    AspirationWindow::new(25, 1.0, None);
+}
@@ -0,0 +1,97 @@
+use kingfisher::board::FenError;
+use kingfisher::boardstack::BoardStack;
+use kingfisher::eval::PestoEval;
+use kingfisher::move_generation::MoveGen;
+use kingfisher::search::{alpha_beta_search, mate_search, MateSearchMode, SearchContext, SearchLimits, MATE_SCORE};
+use kingfisher::tactics::{parse_tactics, tactical_positions, TacticsError, TACTICAL_SUITE};
+use kingfisher::transposition::TranspositionTable;
+
+#[test]
+fn test_tactical_positions_have_legal_best_moves() {
+    let move_gen = MoveGen::new();
+    let positions = tactical_positions(&move_gen);
+    let lines = TACTICAL_SUITE.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')).count();
+    assert_eq!(positions.len(), lines);
+    for position in &positions {
+        assert!(!position.id.is_empty());
+        assert_ne!(position.best_move.to_uci(), "0000", "{}", position.id);
+        let (captures, moves) = move_gen.gen_pseudo_legal_moves(&position.board);
+        assert!(captures.contains(&position.best_move) || moves.contains(&position.best_move), "{}", position.id);
+        assert!(position.board.apply_move_to_board(position.best_move).is_legal(&move_gen), "{}", position.id);
+    }
+}
+
+#[test]
+fn test_composed_mates_are_mates() {
+    // The composed mates are checked without the engine's evaluation: each mate in one must
+    // checkmate, and an exhaustive mate search must find the mate in three
+    let move_gen = MoveGen::new();
+    let positions = tactical_positions(&move_gen);
+    let find = |id: &str| positions.iter().find(|position| position.id == id).unwrap();
+    for id in ["scholars mate", "back rank", "back rank, black", "smothered mate"] {
+        let position = find(id);
+        let after = position.board.apply_move_to_board(position.best_move);
+        assert!(after.is_checkmate_or_stalemate(&move_gen).0, "{}", id);
+    }
+
+    let position = find("doubled rooks");
+    let mut board = BoardStack::new_from_board(position.board.clone());
+    let (score, best_move, _, line) = mate_search(&mut board, &move_gen, 3, MateSearchMode::ChecksOnly, &SearchLimits::new(None, None), false);
+    assert_eq!(score, MATE_SCORE - 5);
+    assert_eq!(best_move, position.best_move);
+    assert_eq!(line.len(), 5);
+}
+
+#[test]
+fn test_engine_solves_tactical_positions() {
+    // The best moves of the Win at Chess positions are published with the suite, so they are an
+    // independent check on the search; the hardest of them need more than depth 4
+    let move_gen = MoveGen::new();
+    let pesto = PestoEval::new();
+    let mut unsolved = Vec::new();
+    let mut wac = 0;
+    for position in tactical_positions(&move_gen) {
+        let mut board = BoardStack::new_from_board(position.board.clone());
+        let (_, best_move, _) = alpha_beta_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(None, None)), 4, -1000000, 1000000);
+        if position.id.starts_with("WAC.") {
+            wac += 1;
+        }
+        if best_move != position.best_move {
+            unsolved.push(position.id);
+        }
+    }
+    assert_eq!(wac, 19);
+    assert!(unsolved.iter().all(|id| id.starts_with("WAC.")), "unsolved composed positions: {:?}", unsolved);
+    assert!(unsolved.len() <= 3, "unsolved: {:?}", unsolved);
+}
+
+#[test]
+fn test_parse_tactics_reads_epd() {
+    let move_gen = MoveGen::new();
+    let text = "
+        # Castling and a check mark left off the best move
+        r3k2r/8/8/8/8/8/8/R3K2R w KQkq - c0 \"test\"; bm O-O-O; id \"castle\";
+        6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8
+    ";
+    let positions = parse_tactics(text, &move_gen).unwrap();
+    assert_eq!(positions.len(), 2);
+    assert_eq!(positions[0].best_move.to_uci(), "e1c1");
+    assert_eq!(positions[0].id, "castle");
+    assert_eq!(positions[1].best_move.to_uci(), "d1d8");
+    assert_eq!(positions[1].id, "");
+}
+
+#[test]
+fn test_parse_tactics_rejects_bad_entries() {
+    let move_gen = MoveGen::new();
+    // The best move is missing or empty
+    assert_eq!(parse_tactics("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - id \"none\";", &move_gen).err(), Some(TacticsError::MissingBestMove(1)));
+    assert_eq!(parse_tactics("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm ;", &move_gen).err(), Some(TacticsError::MissingBestMove(1)));
+    // The best move is not legal: the rook cannot reach d8 through a piece, and there is no queen
+    assert_eq!(parse_tactics("\n6k1/5ppp/8/8/3B4/8/5PPP/3R2K1 w - - bm Rd8#;", &move_gen).err(),
+               Some(TacticsError::IllegalBestMove(2, String::from("Rd8#"))));
+    assert_eq!(parse_tactics("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Qd8#;", &move_gen).err(),
+               Some(TacticsError::IllegalBestMove(1, String::from("Qd8#"))));
+    // The position is illegal
+    assert!(matches!(parse_tactics("6k1/8/8/8/8/8/8/8 w - - bm Kg7;", &move_gen).err(), Some(TacticsError::Fen(1, FenError::IllegalPosition(_)))));
+}
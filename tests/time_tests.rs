@@ -4,7 +4,8 @@ mod tests {
     use kingfisher::boardstack::BoardStack;
     use kingfisher::eval::PestoEval;
     use kingfisher::move_generation::MoveGen;
-    use kingfisher::search::iterative_deepening_ab_search;
+    use kingfisher::search::{iterative_deepening_ab_search, SearchContext, SearchLimits};
+    use kingfisher::transposition::TranspositionTable;
 
     #[test]
     fn test_time_management_short_duration() {
@@ -14,9 +15,11 @@ mod tests {
         let max_depth = 10;
         let q_search_max_depth = 5;
         let time_limit = Some(Duration::from_millis(50)); // Very short time limit
+        // Allocate the table before starting the clock
+        let mut tt = TranspositionTable::new();
 
         let start = Instant::now();
-        let (depth, _, _, _) = iterative_deepening_ab_search(&mut board, &move_gen, &pesto, max_depth, q_search_max_depth, time_limit, false);
+        let (depth, _, _, _) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(time_limit, None)).with_q_search_depth(q_search_max_depth), max_depth);
         let elapsed = start.elapsed();
 
         println!("Searched depth: {}", depth);
@@ -32,9 +35,11 @@ mod tests {
         let max_depth = 20;
         let q_search_max_depth = 5;
         let time_limit = Some(Duration::from_secs(2));
+        // Allocate the table before starting the clock
+        let mut tt = TranspositionTable::new();
 
         let start = Instant::now();
-        let (depth, _, _, _) = iterative_deepening_ab_search(&mut board, &move_gen, &pesto, max_depth, q_search_max_depth, time_limit, false);
+        let (depth, _, _, _) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(time_limit, None)).with_q_search_depth(q_search_max_depth), max_depth);
         let elapsed = start.elapsed();
 
         println!("Searched depth: {}", depth);
@@ -50,7 +55,7 @@ mod tests {
         let q_search_max_depth = 3;
         let time_limit = Some(Duration::from_secs(10)); // Generous time limit
 
-        let (depth, _, _, nodes) = iterative_deepening_ab_search(&mut board, &move_gen, &pesto, max_depth, q_search_max_depth, time_limit, false);
+        let (depth, _, _, nodes) = iterative_deepening_ab_search(&mut board, &mut TranspositionTable::new(), &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(time_limit, None)).with_q_search_depth(q_search_max_depth), max_depth);
 
         println!("Searched depth: {}", depth);
 
@@ -66,13 +71,33 @@ mod tests {
         let max_depth = 20;
         let q_search_max_depth = 5;
         let time_limit = Some(Duration::from_secs(1));
+        // Allocate the table before starting the clock
+        let mut tt = TranspositionTable::new();
 
         let start = Instant::now();
-        let (depth, _, _, _) = iterative_deepening_ab_search(&mut board, &move_gen, &pesto, max_depth, q_search_max_depth, time_limit, false);
+        let (depth, _, _, _) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &SearchLimits::new(time_limit, None)).with_q_search_depth(q_search_max_depth), max_depth);
         let elapsed = start.elapsed();
 
         println!("Searched depth: {}", depth);
 
         assert!(elapsed >= (9 * time_limit.unwrap()) / 10, "Search finished too quickly: {:?}", elapsed);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_time_management_tight_limit_is_respected() {
+        // A busy middlegame position, where a single root move can take far longer than the limit
+        let mut board = BoardStack::new_from_fen("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8");
+        let move_gen = MoveGen::new();
+        let pesto = PestoEval::new();
+        let mut tt = TranspositionTable::new();
+
+        // A limit that has already passed is noticed at the first clock reading. The clock is read
+        // every 512 nodes anywhere in the tree, so the search stops inside its first depth rather
+        // than after it. Counting nodes rather than timing the search keeps this independent of
+        // the machine's load
+        let limits = SearchLimits::new(Some(Duration::ZERO), None);
+        let (depth, _, _, nodes) = iterative_deepening_ab_search(&mut board, &mut tt, &SearchContext::new(&move_gen, &pesto, &limits).with_q_search_depth(8), 30);
+        assert_eq!(depth, 0, "The first depth was completed after the limit passed");
+        assert!(nodes <= 2 * 512, "Search took too many nodes: {}", nodes);
+    }
+}
@@ -0,0 +1,90 @@
+use kingfisher::board::Board;
+use kingfisher::move_generation::MoveGen;
+use kingfisher::move_types::Move;
+use kingfisher::transposition::{TranspositionTable, BUCKET_SIZE};
+
+/// Returns the positions reachable in one and two moves from the starting position.
+fn positions_after_two_moves() -> Vec<Board> {
+    let move_gen = MoveGen::new();
+    let successors = |board: &Board| -> Vec<Board> {
+        let (captures, moves) = move_gen.gen_pseudo_legal_moves(board);
+        captures.into_iter().chain(moves).map(|m| board.apply_move_to_board(m)).collect()
+    };
+    let first = successors(&Board::new());
+    let second: Vec<Board> = first.iter().flat_map(|b| successors(b)).collect();
+    first.into_iter().chain(second).collect()
+}
+
+#[test]
+fn test_hashfull_increases_as_table_fills() {
+    let positions = positions_after_two_moves();
+    let mut tt = TranspositionTable::with_capacity(1000);
+    assert_eq!(tt.hashfull(), 0);
+    let mut last = 0;
+    for board in &positions {
+        tt.store(board, 1, 0, Move::null());
+        assert!(tt.hashfull() >= last);
+        last = tt.hashfull();
+    }
+    assert!(last > 200 && last <= 1000, "hashfull {}", last);
+
+    // Storing the same position again does not use more space
+    tt.store(&positions[0], 2, 0, Move::null());
+    assert_eq!(tt.hashfull(), last);
+
+    // Entries from earlier searches are not counted
+    tt.new_search();
+    assert_eq!(tt.hashfull(), 0);
+    tt.clear();
+    assert_eq!(tt.hashfull(), 0);
+}
+
+#[test]
+fn test_current_generation_survives_and_old_entries_are_evicted() {
+    let positions = positions_after_two_moves();
+    // A single bucket, so every position collides
+    let mut tt = TranspositionTable::with_capacity(BUCKET_SIZE);
+
+    // Fill the bucket during the first search
+    for (i, board) in positions.iter().take(BUCKET_SIZE).enumerate() {
+        tt.store(board, 5 - i as i32, 0, Move::null());
+    }
+
+    // In the next search, new entries replace the old ones, shallowest first,
+    // even when the new entries are shallower still
+    tt.new_search();
+    let new_positions = &positions[BUCKET_SIZE..2 * BUCKET_SIZE];
+    for (i, board) in new_positions.iter().enumerate() {
+        tt.store(board, 1, 0, Move::null());
+        for (j, old) in positions.iter().take(BUCKET_SIZE).enumerate() {
+            assert_eq!(tt.probe(old, 0).is_some(), j < BUCKET_SIZE - 1 - i);
+        }
+    }
+
+    // The bucket now only holds entries from the current search, which all survive
+    for board in new_positions {
+        assert!(tt.probe(board, 1).is_some());
+    }
+}
+
+#[test]
+fn test_deep_entry_survives_shallow_collisions() {
+    let positions = positions_after_two_moves();
+    let mut tt = TranspositionTable::with_capacity(BUCKET_SIZE);
+    tt.store(&positions[0], 8, 10, Move::null());
+    for board in &positions[1..20] {
+        tt.store(board, 1, 0, Move::null());
+    }
+    assert!(tt.probe(&positions[0], 8).is_some());
+    // The most recent shallow entry is also kept
+    assert!(tt.probe(&positions[19], 1).is_some());
+}
+
+#[test]
+fn test_same_position_keeps_deepest_entry() {
+    let positions = positions_after_two_moves();
+    let mut tt = TranspositionTable::with_capacity(16);
+    tt.store(&positions[0], 5, 10, Move::null());
+    tt.store(&positions[0], 3, 20, Move::null());
+    assert!(tt.probe(&positions[0], 5).is_some());
+}
@@ -0,0 +1,285 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use kingfisher::board::Board;
+use kingfisher::move_generation::MoveGen;
+use kingfisher::uci::UCIEngine;
+
+#[test]
+fn test_position_startpos_moves() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("position startpos moves e2e4 c7c5 g1f3");
+    assert_eq!(
+        engine.board().current_state().to_fen(),
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+    );
+}
+
+#[test]
+fn test_position_fen_moves_with_castling_and_promotion() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("position fen r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1c1 e8g8 b7a8q");
+    assert_eq!(
+        engine.board().current_state().to_fen(),
+        "Q4rk1/8/8/8/8/8/8/2KR3R b - - 0 2"
+    );
+}
+
+#[test]
+fn test_position_stops_at_illegal_move() {
+    let mut engine = UCIEngine::new();
+    // e1e2 is blocked by the pawn still on e2
+    engine.handle_command("position startpos moves d2d4 e7e5 e1e2 g8f6");
+    assert_eq!(
+        engine.board().current_state().to_fen(),
+        "rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 2"
+    );
+
+    // A later position command replaces the board completely
+    engine.handle_command("position startpos");
+    assert_eq!(engine.board().current_state().to_fen(), Board::new().to_fen());
+}
+
+#[test]
+fn test_fen_round_trip() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/8/8/K2Pp2q/8/8/8/k7 w - e6 0 2",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 12 40",
+    ] {
+        assert_eq!(Board::new_from_fen(fen).to_fen(), fen);
+    }
+}
+
+#[test]
+fn test_fen_position_has_correct_hash() {
+    // A position reached by moves and the same position loaded from FEN must hash alike
+    let mut engine = UCIEngine::new();
+    engine.handle_command("position startpos moves g1f3 g8f6");
+    let fen = engine.board().current_state().to_fen();
+    assert_eq!(
+        Board::new_from_fen(&fen).zobrist_hash(),
+        engine.board().current_state().zobrist_hash()
+    );
+}
+
+#[test]
+fn test_bench_node_count_is_stable() {
+    let engine = UCIEngine::new();
+    let first = engine.bench(3);
+    assert!(first > 0);
+    assert_eq!(engine.bench(3), first);
+    assert_eq!(UCIEngine::new().bench(3), first);
+}
+
+#[test]
+fn test_bench_positions_reports_speed() {
+    let engine = UCIEngine::new();
+    let results = engine.bench_positions(3);
+    assert_eq!(results.iter().map(|r| r.nodes).sum::<u64>(), engine.bench(3));
+    for result in &results {
+        assert!(result.nps() > 0);
+        let depths: Vec<i32> = result.depth_times.iter().map(|&(d, _)| d).collect();
+        assert_eq!(depths, vec![2, 3]);
+        assert_eq!(result.depth_times.last().unwrap().1, result.time);
+    }
+}
+
+#[test]
+fn test_go_with_limits_leaves_position_unchanged() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("position startpos moves e2e4 e7e5");
+    let fen = engine.board().current_state().to_fen();
+    engine.handle_command("go depth 2");
+    engine.handle_command("go nodes 2000");
+    engine.handle_command("go movetime 20");
+    assert_eq!(engine.board().current_state().to_fen(), fen);
+}
+
+/// Reads the engine's output up to and including the next `bestmove` line
+fn read_until_bestmove(output: &mut impl BufRead) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in output.lines() {
+        let line = line.unwrap();
+        let done = line.starts_with("bestmove");
+        lines.push(line);
+        if done {
+            break;
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_stop_aborts_search() {
+    // Run the UCI binary, so that `stop` goes through the command loop while the search runs
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_kingfisher"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = engine.stdin.take().unwrap();
+    let mut output = BufReader::new(engine.stdout.take().unwrap());
+
+    writeln!(input, "position startpos moves e2e4 e7e5").unwrap();
+    writeln!(input, "go movetime 20000").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let start = Instant::now();
+    writeln!(input, "stop").unwrap();
+    let lines = read_until_bestmove(&mut output);
+    let elapsed = start.elapsed();
+    assert!(elapsed < Duration::from_secs(2), "search took {:?} to stop", elapsed);
+    assert!(lines.last().unwrap().starts_with("bestmove"));
+
+    // A stale stop does not affect the next search
+    writeln!(input, "stop").unwrap();
+    writeln!(input, "go depth 2").unwrap();
+    let lines = read_until_bestmove(&mut output);
+    assert!(lines.iter().any(|line| line.starts_with("info depth 2 ")), "{:?}", lines);
+
+    writeln!(input, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn test_closed_input_ends_infinite_search() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_kingfisher"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = engine.stdin.take().unwrap();
+    let mut output = BufReader::new(engine.stdout.take().unwrap());
+
+    // Closing the input, as when the GUI exits, must stop the search rather than leave it running
+    writeln!(input, "position startpos").unwrap();
+    writeln!(input, "go infinite").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    drop(input);
+    let start = Instant::now();
+    while engine.try_wait().unwrap().is_none() && start.elapsed() < Duration::from_secs(30) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let status = engine.try_wait().unwrap();
+    if status.is_none() {
+        engine.kill().unwrap();
+    }
+    assert!(status.is_some_and(|status| status.success()), "engine did not exit");
+    assert!(read_until_bestmove(&mut output).last().unwrap().starts_with("bestmove"));
+}
+
+#[test]
+fn test_analyse_mode_ignores_contempt() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("setoption name Contempt value 50");
+    // Black is stalemated
+    engine.handle_command("position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+
+    engine.handle_command("go depth 2");
+    assert_eq!(engine.last_score(), Some(-50));
+
+    engine.handle_command("setoption name UCI_AnalyseMode value true");
+    assert_eq!(engine.search_contempt(), 0);
+    engine.handle_command("go depth 2");
+    assert_eq!(engine.last_score(), Some(0));
+
+    engine.handle_command("setoption name UCI_AnalyseMode value false");
+    assert_eq!(engine.search_contempt(), 50);
+}
+
+#[test]
+fn test_malformed_fen_keeps_previous_position() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("position startpos moves e2e4");
+    let fen = engine.board().current_state().to_fen();
+    engine.handle_command("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1");
+    assert_eq!(engine.board().current_state().to_fen(), fen);
+}
+
+#[test]
+fn test_go_searchmoves_ignores_illegal_moves() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_kingfisher"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = engine.stdin.take().unwrap();
+    let mut output = BufReader::new(engine.stdout.take().unwrap());
+    let bestmove = |lines: Vec<String>| lines.last().unwrap().split_whitespace().nth(1).unwrap().to_string();
+
+    let move_gen = MoveGen::new();
+    let board = Board::new_from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+    let (captures, moves) = move_gen.gen_pseudo_legal_moves(&board);
+    let legal: Vec<String> = captures.into_iter().chain(moves)
+        .filter(|&m| board.apply_move_to_board(m).is_legal(&move_gen))
+        .map(|m| m.to_uci())
+        .collect();
+
+    // The illegal and malformed moves are dropped, and the best of the legal ones is played,
+    // even though the unrestricted search prefers d2d4
+    writeln!(input, "position startpos moves e2e4 e7e5").unwrap();
+    writeln!(input, "go searchmoves g1f3 e1e3 xyz b1c3 depth 2").unwrap();
+    let best = bestmove(read_until_bestmove(&mut output));
+    assert!(best == "g1f3" || best == "b1c3", "bestmove {}", best);
+
+    // Without any legal moves left, or none given, the whole position is searched
+    for go in ["go searchmoves e1e3 depth 2", "go depth 2 searchmoves"] {
+        writeln!(input, "{}", go).unwrap();
+        let best = bestmove(read_until_bestmove(&mut output));
+        assert!(legal.contains(&best), "{}: bestmove {}", go, best);
+    }
+
+    writeln!(input, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
+
+#[test]
+fn test_ucinewgame_resets_game_state() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("setoption name Contempt value 30");
+    engine.handle_command("position fen r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    engine.handle_command("go depth 4");
+    let searched = engine.board().current_state().clone();
+    assert!(engine.transposition_table().probe(&searched, 0).is_some());
+    assert!(engine.last_score().is_some());
+
+    engine.handle_command("ucinewgame");
+    assert!(engine.transposition_table().probe(&searched, 0).is_none());
+    assert_eq!(engine.transposition_table().hashfull(), 0);
+    assert_eq!(engine.last_score(), None);
+    assert_eq!(engine.board().current_state().to_fen(), Board::new().to_fen());
+    // Options outlast the game
+    assert_eq!(engine.search_contempt(), 30);
+}
+
+#[test]
+fn test_position_moves_history_reaches_search() {
+    // Black has a lone king against a queen and rook. The king shuffle has already reached the
+    // starting position twice, so returning the king to g8 draws by threefold repetition.
+    let fen = "6k1/8/8/8/8/8/8/R2Q2K1 w - - 0 1";
+    let mut engine = UCIEngine::new();
+    engine.handle_command(&format!("position fen {} moves g1h1 g8h8 h1g1 h8g8 g1h1 g8h8 h1g1", fen));
+    assert_eq!(engine.board().moves().len(), 7);
+    engine.handle_command("go depth 3");
+    assert_eq!(engine.last_score(), Some(0));
+
+    // Without the game history the same position is lost for black
+    let shuffled = engine.board().current_state().to_fen();
+    engine.handle_command(&format!("position fen {}", shuffled));
+    engine.handle_command("go depth 3");
+    assert!(engine.last_score().unwrap() < -500);
+}
+
+#[test]
+fn test_go_with_missing_or_zero_values() {
+    let mut engine = UCIEngine::new();
+    engine.handle_command("position startpos");
+    // A parameter without a value at the end of the command is skipped
+    engine.handle_command("go depth 2 movetime");
+    engine.handle_command("go nodes 2000 wtime");
+    // No moves to go is treated as one, rather than dividing the time left by zero
+    engine.handle_command("go wtime 200 btime 200 movestogo 0");
+    assert_eq!(engine.board().current_state().to_fen(), Board::new().to_fen());
+}